@@ -0,0 +1,152 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header, request::Parts},
+};
+use serde::Deserialize;
+
+use crate::{server::AppError, ApiError};
+
+/// The credentials accepted by the server, and the namespace prefixes each may lease.
+///
+/// Any valid credential may get and list origins, since relays need to route to namespaces they don't own.
+/// Creating, refreshing and releasing a lease requires a scope that is the namespace or one of its parents,
+/// ie: `live` and `live/` grant `live/foo` but not `lively`. An empty prefix grants every namespace.
+#[derive(Deserialize, Default, Debug)]
+pub struct AuthConfig {
+    /// Bearer tokens, presented in the `Authorization` header.
+    #[serde(default)]
+    pub tokens: HashMap<String, Vec<String>>,
+
+    /// The hex encoded SHA-256 fingerprints of client certificates, presented using mTLS.
+    #[serde(default)]
+    pub certificates: HashMap<String, Vec<String>>,
+}
+
+impl AuthConfig {
+    /// Load the config from a JSON file.
+    pub fn load(path: &Path) -> Result<Self, ApiError> {
+        let contents = fs::read(path)?;
+        let mut config: Self = serde_json::from_slice(&contents)?;
+
+        // Accept fingerprints in any case, with or without colons.
+        config.certificates = config
+            .certificates
+            .into_iter()
+            .map(|(fingerprint, scopes)| (normalize_fingerprint(&fingerprint), scopes))
+            .collect();
+
+        Ok(config)
+    }
+}
+
+fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint.replace(':', "").to_ascii_lowercase()
+}
+
+/// The client certificate presented during the TLS handshake, inserted into each request on the connection.
+#[derive(Clone, Debug)]
+pub(crate) struct ClientCert {
+    pub fingerprint: String,
+}
+
+impl ClientCert {
+    pub fn new(cert: &[u8]) -> Self {
+        let fingerprint = ring::digest::digest(&ring::digest::SHA256, cert);
+        Self {
+            fingerprint: hex::encode(fingerprint.as_ref()),
+        }
+    }
+}
+
+/// The namespace prefixes the request is allowed to lease.
+pub(crate) enum Scopes {
+    /// Authentication is disabled.
+    Any,
+    Prefixes(Vec<String>),
+}
+
+impl Scopes {
+    /// Make sure the namespace may be leased, otherwise returning [AppError::Forbidden].
+    pub fn check(&self, namespace: &str) -> Result<(), AppError> {
+        let allowed = match self {
+            Self::Any => true,
+            Self::Prefixes(prefixes) => prefixes.iter().any(|prefix| grants(prefix, namespace)),
+        };
+
+        match allowed {
+            true => Ok(()),
+            false => Err(AppError::Forbidden),
+        }
+    }
+}
+
+// Returns true if the scope is the namespace or one of its parents, matching whole path segments.
+fn grants(scope: &str, namespace: &str) -> bool {
+    match namespace.strip_prefix(scope) {
+        Some(rest) => {
+            scope.is_empty() || scope.ends_with('/') || rest.is_empty() || rest.starts_with('/')
+        }
+        None => false,
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Scopes
+where
+    Option<Arc<AuthConfig>>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let config = match Option::<Arc<AuthConfig>>::from_ref(state) {
+            Some(config) => config,
+            None => return Ok(Self::Any),
+        };
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+
+        if let Some(token) = token {
+            // An invalid token is rejected, even if there's a valid certificate.
+            let scopes = config.tokens.get(token).ok_or(AppError::Unauthorized)?;
+            return Ok(Self::Prefixes(scopes.clone()));
+        }
+
+        let scopes = parts
+            .extensions
+            .get::<ClientCert>()
+            .and_then(|cert| config.certificates.get(&cert.fingerprint))
+            .ok_or(AppError::Unauthorized)?;
+
+        Ok(Self::Prefixes(scopes.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scopes() {
+        let scopes = Scopes::Prefixes(vec!["live/".to_string(), "demo".to_string()]);
+        assert!(scopes.check("live/foo").is_ok());
+        assert!(scopes.check("demo").is_ok());
+        assert!(scopes.check("demo/bar").is_ok());
+        assert!(scopes.check("other").is_err());
+
+        // Scopes only match whole path segments.
+        assert!(scopes.check("lively/foo").is_err());
+        assert!(scopes.check("live").is_err());
+        assert!(scopes.check("demos").is_err());
+        assert!(scopes.check("demo-bar/baz").is_err());
+        assert!(Scopes::Any.check("other").is_ok());
+        assert!(Scopes::Prefixes(vec![String::new()]).check("other").is_ok());
+    }
+}
@@ -1,6 +1,22 @@
 use url::Url;
 
-use crate::{ApiError, Origin};
+use crate::{
+    ApiError, Lease, LeaseOptions, LeaseRefresh, LeaseToken, ListOptions, Origin, OriginEvent,
+    OriginList, WatchOptions,
+};
+
+/// Credentials and TLS options used to connect to moq-api.
+#[derive(Clone, Default)]
+pub struct ClientConfig {
+    /// A bearer token sent with every request.
+    pub token: Option<String>,
+
+    /// A certificate chain and private key presented using mTLS, encoded as PEM.
+    pub identity: Option<Vec<u8>>,
+
+    /// Root certificates trusted in addition to the default roots, encoded as PEM.
+    pub roots: Vec<Vec<u8>>,
+}
 
 #[derive(Clone)]
 pub struct Client {
@@ -16,6 +32,34 @@ impl Client {
         Self { url, client }
     }
 
+    /// Create a client that presents the given credentials.
+    pub fn with_config(url: Url, config: ClientConfig) -> Result<Self, ApiError> {
+        let mut builder = reqwest::Client::builder().use_rustls_tls();
+
+        if let Some(token) = config.token {
+            let mut value = reqwest::header::HeaderValue::try_from(format!("Bearer {token}"))
+                .map_err(|_| ApiError::Unauthorized)?;
+            value.set_sensitive(true);
+
+            let mut headers = reqwest::header::HeaderMap::new();
+            headers.insert(reqwest::header::AUTHORIZATION, value);
+            builder = builder.default_headers(headers);
+        }
+
+        if let Some(identity) = config.identity {
+            builder = builder.identity(reqwest::Identity::from_pem(&identity)?);
+        }
+
+        for root in config.roots {
+            for cert in reqwest::Certificate::from_pem_bundle(&root)? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        let client = builder.build()?;
+        Ok(Self { url, client })
+    }
+
     pub async fn get_origin(&self, namespace: &str) -> Result<Option<Origin>, ApiError> {
         let url = self.url.join(&format!("origin/{namespace}"))?;
         let resp = self.client.get(url).send().await?;
@@ -23,34 +67,248 @@ impl Client {
             return Ok(None);
         }
 
-        let origin: Origin = resp.json().await?;
+        let origin: Origin = error_for_status(resp)?.json().await?;
         Ok(Some(origin))
     }
 
-    pub async fn set_origin(&self, namespace: &str, origin: Origin) -> Result<(), ApiError> {
+    /// Lease the namespace with the default TTL, returning [ApiError::Conflict] if it's already leased.
+    pub async fn set_origin(&self, namespace: &str, origin: Origin) -> Result<Lease, ApiError> {
+        self.create_lease(namespace, origin, LeaseOptions::default())
+            .await
+    }
+
+    /// Replace an existing lease held by the same origin URL, ex. after a restart, or create a new lease.
+    pub async fn takeover_origin(
+        &self,
+        namespace: &str,
+        origin: Origin,
+    ) -> Result<Lease, ApiError> {
+        let options = LeaseOptions {
+            takeover: true,
+            ..Default::default()
+        };
+        self.create_lease(namespace, origin, options).await
+    }
+
+    /// Lease the namespace with the given options, ex. to request a TTL.
+    pub async fn create_lease(
+        &self,
+        namespace: &str,
+        origin: Origin,
+        options: LeaseOptions,
+    ) -> Result<Lease, ApiError> {
         let url = self.url.join(&format!("origin/{namespace}"))?;
 
-        let resp = self.client.post(url).json(&origin).send().await?;
-        resp.error_for_status()?;
+        let resp = self
+            .client
+            .post(url)
+            .query(&options)
+            .json(&origin)
+            .send()
+            .await?;
+        let lease = error_for_status(resp)?.json().await?;
 
-        Ok(())
+        Ok(lease)
     }
 
-    pub async fn delete_origin(&self, namespace: &str) -> Result<(), ApiError> {
+    /// Release the lease, returning [ApiError::Conflict] if the token is stale.
+    pub async fn delete_origin(&self, namespace: &str, token: u64) -> Result<(), ApiError> {
         let url = self.url.join(&format!("origin/{namespace}"))?;
 
-        let resp = self.client.delete(url).send().await?;
-        resp.error_for_status()?;
+        let resp = self
+            .client
+            .delete(url)
+            .query(&LeaseToken { token })
+            .send()
+            .await?;
+        error_for_status(resp)?;
 
         Ok(())
     }
 
-    pub async fn patch_origin(&self, namespace: &str, origin: Origin) -> Result<(), ApiError> {
+    /// Reset the lease expiration, returning [ApiError::NotFound] if it already expired,
+    /// or [ApiError::Conflict] if the token is stale.
+    pub async fn refresh_origin(&self, namespace: &str, token: u64) -> Result<(), ApiError> {
+        let refresh = LeaseRefresh {
+            token,
+            origin: None,
+            ttl: None,
+        };
+        self.refresh_lease(namespace, refresh).await
+    }
+
+    /// Refresh the lease and replace the origin metadata, ex. the current load.
+    /// The URL must not change, otherwise [ApiError::Conflict] is returned.
+    pub async fn update_origin(
+        &self,
+        namespace: &str,
+        token: u64,
+        origin: Origin,
+    ) -> Result<(), ApiError> {
+        let refresh = LeaseRefresh {
+            token,
+            origin: Some(origin),
+            ttl: None,
+        };
+        self.refresh_lease(namespace, refresh).await
+    }
+
+    /// Refresh the lease with the given options, ex. to request a TTL.
+    pub async fn refresh_lease(
+        &self,
+        namespace: &str,
+        refresh: LeaseRefresh,
+    ) -> Result<(), ApiError> {
         let url = self.url.join(&format!("origin/{namespace}"))?;
 
-        let resp = self.client.patch(url).json(&origin).send().await?;
-        resp.error_for_status()?;
+        let resp = self.client.patch(url).json(&refresh).send().await?;
+        error_for_status(resp)?;
 
         Ok(())
     }
+
+    /// List a page of origins, sorted by namespace.
+    pub async fn list_origins(&self, options: &ListOptions) -> Result<OriginList, ApiError> {
+        let url = self.url.join("origins")?;
+
+        let resp = self.client.get(url).query(options).send().await?;
+        let list = error_for_status(resp)?.json().await?;
+
+        Ok(list)
+    }
+
+    /// Watch for changes to origins, starting with a `created` event for every current origin.
+    pub async fn watch_origins(&self, options: &WatchOptions) -> Result<OriginWatch, ApiError> {
+        let url = self.url.join("events")?;
+
+        let resp = self.client.get(url).query(options).send().await?;
+        let resp = error_for_status(resp)?;
+
+        Ok(OriginWatch {
+            resp,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+/// A stream of changes to origins, parsed from server-sent events.
+pub struct OriginWatch {
+    resp: reqwest::Response,
+    buffer: Vec<u8>,
+}
+
+impl OriginWatch {
+    /// Returns the next change, or None if the server ended the feed.
+    ///
+    /// The feed ends if we fall behind, so watch again and replace any cached state.
+    pub async fn next(&mut self) -> Result<Option<OriginEvent>, ApiError> {
+        loop {
+            if let Some(event) = parse_event(&mut self.buffer)? {
+                return Ok(Some(event));
+            }
+
+            match self.resp.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+// Parse the next complete event in the buffer, skipping comments used as keep-alives.
+fn parse_event(buffer: &mut Vec<u8>) -> Result<Option<OriginEvent>, ApiError> {
+    while let Some(end) = buffer.windows(2).position(|w| w == b"\n\n") {
+        let block: Vec<u8> = buffer.drain(..end + 2).collect();
+        let block = String::from_utf8_lossy(&block);
+
+        let data: Vec<_> = block
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect();
+
+        if !data.is_empty() {
+            return Ok(Some(serde_json::from_str(&data.join("\n"))?));
+        }
+    }
+
+    Ok(None)
+}
+
+fn error_for_status(resp: reqwest::Response) -> Result<reqwest::Response, ApiError> {
+    match resp.status() {
+        reqwest::StatusCode::CONFLICT => Err(ApiError::Conflict),
+        reqwest::StatusCode::NOT_FOUND => Err(ApiError::NotFound),
+        reqwest::StatusCode::UNAUTHORIZED => Err(ApiError::Unauthorized),
+        reqwest::StatusCode::FORBIDDEN => Err(ApiError::Forbidden),
+        _ => Ok(resp.error_for_status()?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::OriginEventKind;
+
+    fn event(kind: OriginEventKind, namespace: &str) -> OriginEvent {
+        OriginEvent {
+            kind,
+            namespace: namespace.to_string(),
+            lease: Some(Lease {
+                origin: Origin::new("https://a.example".parse().unwrap()),
+                token: 1,
+                registered: 0,
+                ttl: 60,
+            }),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let created = event(OriginEventKind::Created, "live/foo");
+        let expired = OriginEvent {
+            lease: None,
+            ..event(OriginEventKind::Expired, "live/foo")
+        };
+
+        let stream = format!(
+            ":\n\nevent: created\ndata: {}\n\nevent: expired\ndata:{}\n\n:\n\n",
+            serde_json::to_string(&created).unwrap(),
+            serde_json::to_string(&expired).unwrap(),
+        );
+
+        // Feed the stream a few bytes at a time, as if it arrived in small chunks.
+        let mut buffer = Vec::new();
+        let mut events = Vec::new();
+        for chunk in stream.as_bytes().chunks(7) {
+            buffer.extend_from_slice(chunk);
+            while let Some(event) = parse_event(&mut buffer).unwrap() {
+                events.push(event);
+            }
+        }
+
+        assert_eq!(events, vec![created, expired]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn parse_partial() {
+        // Nothing is returned until the blank line ending the event.
+        let mut buffer = b"event: created\ndata: {\"kind\":".to_vec();
+        assert!(parse_event(&mut buffer).unwrap().is_none());
+
+        buffer.extend_from_slice(b"\"created\",\n");
+        assert!(parse_event(&mut buffer).unwrap().is_none());
+
+        // Multiple data lines are joined with newlines.
+        buffer.extend_from_slice(b"data: \"namespace\":\"foo\"}\n\n");
+        let event = parse_event(&mut buffer).unwrap().unwrap();
+        assert_eq!(event.kind, OriginEventKind::Created);
+        assert_eq!(event.namespace, "foo");
+        assert_eq!(event.lease, None);
+
+        // Invalid JSON is an error.
+        buffer.extend_from_slice(b"data: nope\n\n");
+        assert!(parse_event(&mut buffer).is_err());
+    }
 }
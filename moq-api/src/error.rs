@@ -14,6 +14,28 @@ pub enum ApiError {
     #[error("url error: {0}")]
     Url(#[from] url::ParseError),
 
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+
+    #[error("tls error: {0}")]
+    Tls(#[from] rustls::Error),
+
+    /// The namespace is leased by another origin, or the fencing token is stale.
+    #[error("lease conflict")]
+    Conflict,
+
+    /// The lease doesn't exist, possibly because it expired.
+    #[error("lease not found")]
+    NotFound,
+
+    /// The credentials were missing or invalid.
+    #[error("unauthorized")]
+    Unauthorized,
+
+    /// The credentials don't grant access to the namespace.
+    #[error("forbidden")]
+    Forbidden,
 }
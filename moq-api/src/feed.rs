@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time,
+};
+
+use tokio::sync::broadcast;
+
+use crate::{Lease, OriginEvent, OriginEventKind, OriginStore};
+
+// The number of events buffered per watcher; slower watchers are disconnected and must resync.
+const CAPACITY: usize = 1024;
+
+// How often the store is scanned for expired leases and changes made by other moq-api instances, by default.
+pub(crate) const SWEEP_INTERVAL: time::Duration = time::Duration::from_secs(5);
+
+/// Emits changes to origins to any watchers.
+///
+/// Changes made through this instance are emitted immediately.
+/// Expirations, and changes made through other instances sharing the store, are found by periodically
+/// scanning the store; refreshes made through other instances are only noticed if the metadata changed.
+#[derive(Clone)]
+pub(crate) struct Feed {
+    state: Arc<Mutex<FeedState>>,
+}
+
+struct FeedState {
+    // The last known lease for each namespace, including recently expired leases.
+    leases: HashMap<String, Known>,
+    events: broadcast::Sender<OriginEvent>,
+}
+
+struct Known {
+    lease: Option<Lease>,
+
+    // When this was last updated, so a scan doesn't overwrite newer changes.
+    updated: time::Instant,
+}
+
+impl Feed {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(CAPACITY);
+        let state = FeedState {
+            leases: HashMap::new(),
+            events,
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Record the current lease for a namespace, emitting an event if it changed.
+    ///
+    /// A refresh is always emitted, even if the lease didn't change.
+    pub fn update(&self, namespace: &str, lease: Option<Lease>, refresh: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.update(namespace, lease, refresh, time::Instant::now());
+    }
+
+    /// Start watching namespaces with the given prefix.
+    ///
+    /// Returns a `created` event for every known lease, followed by a receiver for any changes,
+    /// so a watcher can build a complete view without a separate listing.
+    pub fn watch(&self, prefix: &str) -> (Vec<OriginEvent>, broadcast::Receiver<OriginEvent>) {
+        let state = self.state.lock().unwrap();
+
+        let mut current: Vec<_> = state
+            .leases
+            .iter()
+            .filter(|(namespace, _)| namespace.starts_with(prefix))
+            .filter_map(|(namespace, known)| {
+                Some(OriginEvent {
+                    kind: OriginEventKind::Created,
+                    namespace: namespace.clone(),
+                    lease: Some(known.lease.clone()?),
+                })
+            })
+            .collect();
+        current.sort_by(|a, b| a.namespace.cmp(&b.namespace));
+
+        (current, state.events.subscribe())
+    }
+
+    /// Scan the store for changes at the given interval, forever.
+    pub async fn sweep(self, store: Arc<dyn OriginStore>, interval: time::Duration) {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let started = time::Instant::now();
+            match store.list("", None, usize::MAX).await {
+                Ok(entries) => {
+                    let leases = entries
+                        .into_iter()
+                        .map(|entry| (entry.namespace, entry.lease))
+                        .collect();
+                    self.state.lock().unwrap().scanned(leases, started);
+                }
+                Err(err) => log::warn!("failed to scan origins: {}", err),
+            }
+        }
+    }
+}
+
+impl FeedState {
+    fn update(&mut self, namespace: &str, lease: Option<Lease>, refresh: bool, at: time::Instant) {
+        let known = Known {
+            lease: lease.clone(),
+            updated: at,
+        };
+        let previous = self
+            .leases
+            .insert(namespace.to_string(), known)
+            .and_then(|known| known.lease);
+
+        let kind = match (&previous, &lease) {
+            (None, None) => return,
+            (Some(_), None) => OriginEventKind::Expired,
+            (None, Some(_)) => OriginEventKind::Created,
+            (Some(previous), Some(lease)) if previous.origin.url != lease.origin.url => {
+                OriginEventKind::Moved
+            }
+            (Some(previous), Some(lease)) if previous.token != lease.token => {
+                OriginEventKind::Created
+            }
+            (Some(previous), Some(lease)) if refresh || previous != lease => {
+                OriginEventKind::Refreshed
+            }
+            _ => return,
+        };
+
+        let event = OriginEvent {
+            kind,
+            namespace: namespace.to_string(),
+            lease,
+        };
+
+        // Ignore the error when there are no watchers.
+        let _ = self.events.send(event);
+    }
+
+    // Apply the result of scanning the store, skipping any namespace changed since the scan started.
+    fn scanned(&mut self, mut leases: HashMap<String, Lease>, started: time::Instant) {
+        let mut namespaces: HashSet<String> = leases.keys().cloned().collect();
+        namespaces.extend(self.leases.keys().cloned());
+
+        for namespace in namespaces {
+            if let Some(known) = self.leases.get(&namespace) {
+                if known.updated >= started {
+                    continue;
+                }
+            }
+
+            let lease = leases.remove(&namespace);
+            self.update(&namespace, lease, false, started);
+        }
+
+        // Forget expired leases, now that the store agrees.
+        self.leases
+            .retain(|_, known| known.lease.is_some() || known.updated > started);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Origin;
+
+    fn lease(url: &str, token: u64) -> Lease {
+        Lease {
+            origin: Origin::new(url.parse().unwrap()),
+            token,
+            registered: 0,
+            ttl: 60,
+        }
+    }
+
+    #[test]
+    fn events() {
+        let feed = Feed::new();
+        feed.update("live/foo", Some(lease("https://a.example", 1)), false);
+
+        let (current, mut events) = feed.watch("live/");
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0].kind, OriginEventKind::Created);
+
+        let mut next = || events.try_recv().ok().map(|event| event.kind);
+
+        // Scanning an unchanged lease is a no-op, but a refresh is always emitted.
+        feed.update("live/foo", Some(lease("https://a.example", 1)), false);
+        assert_eq!(next(), None);
+        feed.update("live/foo", Some(lease("https://a.example", 1)), true);
+        assert_eq!(next(), Some(OriginEventKind::Refreshed));
+
+        feed.update("live/foo", Some(lease("https://a.example", 2)), false);
+        assert_eq!(next(), Some(OriginEventKind::Created));
+        feed.update("live/foo", Some(lease("https://b.example", 3)), false);
+        assert_eq!(next(), Some(OriginEventKind::Moved));
+        feed.update("live/foo", None, false);
+        assert_eq!(next(), Some(OriginEventKind::Expired));
+        feed.update("live/foo", None, false);
+        assert_eq!(next(), None);
+    }
+
+    #[test]
+    fn scanned() {
+        let feed = Feed::new();
+        let (_, mut events) = feed.watch("");
+        let mut next = || events.try_recv().ok().map(|event| event.kind);
+
+        let started = time::Instant::now();
+        feed.update("foo", Some(lease("https://a.example", 1)), false);
+        assert_eq!(next(), Some(OriginEventKind::Created));
+
+        // The scan started before foo was created, so it's not expired.
+        let mut state = feed.state.lock().unwrap();
+        let leases = HashMap::from([("bar".to_string(), lease("https://b.example", 2))]);
+        state.scanned(leases, started);
+        drop(state);
+        assert_eq!(next(), Some(OriginEventKind::Created));
+        assert_eq!(next(), None);
+
+        let mut state = feed.state.lock().unwrap();
+        state.scanned(HashMap::new(), time::Instant::now());
+        drop(state);
+        assert_eq!(next(), Some(OriginEventKind::Expired));
+        assert_eq!(next(), Some(OriginEventKind::Expired));
+        assert!(feed.state.lock().unwrap().leases.is_empty());
+    }
+}
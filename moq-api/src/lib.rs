@@ -1,7 +1,15 @@
+mod auth;
 mod client;
 mod error;
+mod feed;
 mod model;
+mod server;
+mod store;
+mod tls;
 
+pub use auth::AuthConfig;
 pub use client::*;
 pub use error::*;
 pub use model::*;
+pub use server::*;
+pub use store::*;
@@ -1,8 +1,6 @@
 use clap::Parser;
 
-mod server;
-use moq_api::ApiError;
-use server::{Server, ServerConfig};
+use moq_api::{ApiError, Server, ServerConfig};
 
 #[tokio::main]
 async fn main() -> Result<(), ApiError> {
@@ -2,7 +2,179 @@ use serde::{Deserialize, Serialize};
 
 use url::Url;
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
 pub struct Origin {
     pub url: Url,
+
+    /// A unique identifier for the node, ex. the hostname.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<String>,
+
+    /// The region of the node, ex. `us-east`, used to pick the nearest origin.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+
+    /// The current load of the node, ex. the number of sessions, and the maximum it can handle.
+    /// Used to pick the least loaded origin; the units are up to the deployment.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<u64>,
+
+    /// The tracks available in the namespace, if known.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tracks: Vec<String>,
+
+    /// A hash of the catalog, so changes can be detected without fetching it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog_hash: Option<String>,
+}
+
+impl Origin {
+    /// An origin with the given URL and no metadata.
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            node: None,
+            region: None,
+            load: None,
+            capacity: None,
+            tracks: Vec::new(),
+            catalog_hash: None,
+        }
+    }
+}
+
+/// Ownership of a namespace by an origin, which expires unless refreshed.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct Lease {
+    #[serde(flatten)]
+    pub origin: Origin,
+
+    /// The fencing token, which increases every time a lease is created or taken over.
+    /// It must be presented to refresh or release the lease, so a stale origin can't modify its replacement.
+    pub token: u64,
+
+    /// When the lease was created or taken over, in milliseconds since the UNIX epoch.
+    #[serde(default)]
+    pub registered: u64,
+
+    /// The number of seconds the lease lasts without a refresh, as granted by the server.
+    #[serde(default)]
+    pub ttl: u64,
+}
+
+/// The fencing token presented to refresh or release a lease.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+pub struct LeaseToken {
+    pub token: u64,
+}
+
+/// Refresh a lease, optionally replacing the origin metadata.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct LeaseRefresh {
+    pub token: u64,
+
+    /// The updated origin, which must have the same URL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<Origin>,
+
+    /// The requested TTL in seconds, otherwise the server's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+}
+
+/// Options when creating a lease.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default, Debug)]
+pub struct LeaseOptions {
+    /// Replace an existing lease held by the same origin URL, ex. after the origin restarts.
+    #[serde(default)]
+    pub takeover: bool,
+
+    /// The requested TTL in seconds, otherwise the server's default.
+    /// The server may grant a different TTL, returned in [Lease::ttl].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u64>,
+}
+
+/// A lease for a namespace, as returned when listing origins.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct OriginEntry {
+    pub namespace: String,
+
+    #[serde(flatten)]
+    pub lease: Lease,
+}
+
+/// Options when listing origins.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Default, Debug)]
+pub struct ListOptions {
+    /// Only list namespaces starting with this prefix.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+
+    /// Continue after this namespace, from [OriginList::next].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+
+    /// The maximum number of origins to return, capped by the server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+/// A page of origins, sorted by namespace.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct OriginList {
+    pub origins: Vec<OriginEntry>,
+
+    /// Pass this as [ListOptions::after] to get the next page, or None if this is the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+}
+
+/// The kind of change to a namespace's origin.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum OriginEventKind {
+    /// The namespace was leased, including when the same origin takes over its lease.
+    Created,
+
+    /// The lease was refreshed, possibly with new metadata.
+    Refreshed,
+
+    /// The namespace was leased by a different origin.
+    Moved,
+
+    /// The lease expired or was released.
+    Expired,
+}
+
+impl OriginEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Refreshed => "refreshed",
+            Self::Moved => "moved",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// A change to a namespace's origin, as emitted by the change feed.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct OriginEvent {
+    pub kind: OriginEventKind,
+    pub namespace: String,
+
+    /// The current lease, or None if it expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease: Option<Lease>,
+}
+
+/// Options when watching for changes.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Default, Debug)]
+pub struct WatchOptions {
+    /// Only watch namespaces starting with this prefix.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
 }
@@ -1,18 +1,30 @@
-use std::net;
+use std::{io, net, path::PathBuf, sync::Arc, time};
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    extract::{FromRef, Path, Query, Request, State},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::get,
     Json, Router,
 };
 
 use clap::Parser;
+use futures::{Stream, StreamExt};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+};
+use tower::ServiceExt;
 
-use redis::{aio::ConnectionManager, AsyncCommands};
-
-use moq_api::{ApiError, Origin};
+use crate::{
+    auth::{AuthConfig, ClientCert, Scopes},
+    feed::{Feed, SWEEP_INTERVAL},
+    tls, ApiError, Lease, LeaseOptions, LeaseRefresh, LeaseToken, ListOptions, MemoryStore, Origin,
+    OriginEvent, OriginList, OriginStore, RedisStore, WatchOptions,
+};
 
 /// Runs a HTTP API to create/get origins for broadcasts.
 #[derive(Parser, Debug)]
@@ -22,9 +34,128 @@ pub struct ServerConfig {
     #[arg(long, default_value = "[::]:80")]
     pub bind: net::SocketAddr,
 
-    /// Connect to the given redis instance
+    /// Store origins in the given redis instance, otherwise they're stored in memory
     #[arg(long)]
-    pub redis: url::Url,
+    pub redis: Option<url::Url>,
+
+    /// Persist the in-memory origins to the given file, reloading them on startup
+    #[arg(long, conflicts_with = "redis")]
+    pub store_file: Option<PathBuf>,
+
+    /// Serve HTTPS using the certificate chain at this path, encoded as PEM
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// The private key for --tls-cert, encoded as PEM
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// Verify client certificates against the roots at this path, encoded as PEM, for mTLS
+    #[arg(long, requires = "tls_cert")]
+    pub tls_client_root: Option<PathBuf>,
+
+    /// Require the credentials listed in this JSON file, each scoped to namespace prefixes
+    #[arg(long)]
+    pub auth: Option<PathBuf>,
+
+    /// Leases expire after this many seconds unless refreshed, if the origin doesn't request a TTL
+    #[arg(long, default_value = "600")]
+    pub lease_ttl: u64,
+
+    /// The minimum TTL in seconds that an origin may request
+    #[arg(long, default_value = "10")]
+    pub lease_ttl_min: u64,
+
+    /// The maximum TTL in seconds that an origin may request
+    #[arg(long, default_value = "3600")]
+    pub lease_ttl_max: u64,
+
+    /// Scan the store every this many seconds, to notify watchers of expired leases and changes made by other instances
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u64).range(1..))]
+    pub sweep_interval: u64,
+}
+
+/// The TTLs granted to leases, in seconds.
+#[derive(Clone, Copy, Debug)]
+struct Ttl {
+    default: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Ttl {
+    // Grant the requested TTL, limited to the allowed range.
+    fn grant(&self, requested: Option<u64>) -> time::Duration {
+        let ttl = requested.unwrap_or(self.default).clamp(self.min, self.max);
+        time::Duration::from_secs(ttl)
+    }
+}
+
+impl Default for Ttl {
+    fn default() -> Self {
+        // Leases expire after 10 minutes; the origin needs to keep refreshing it.
+        Self {
+            default: 600,
+            min: 10,
+            max: 3600,
+        }
+    }
+}
+
+// The number of origins returned when listing, unless the client asks for fewer.
+const LIST_LIMIT: usize = 100;
+const LIST_LIMIT_MAX: usize = 1000;
+
+type Store = Arc<dyn OriginStore>;
+
+#[derive(Clone)]
+struct AppState {
+    store: Store,
+
+    // None if authentication is disabled.
+    auth: Option<Arc<AuthConfig>>,
+
+    feed: Feed,
+    ttl: Ttl,
+}
+
+impl AppState {
+    // Create the state, scanning the store for changes in the background.
+    fn new(store: Store, auth: Option<Arc<AuthConfig>>, ttl: Ttl, sweep: time::Duration) -> Self {
+        let feed = Feed::new();
+        tokio::spawn(feed.clone().sweep(store.clone(), sweep));
+
+        Self {
+            store,
+            auth,
+            feed,
+            ttl,
+        }
+    }
+}
+
+impl FromRef<AppState> for Store {
+    fn from_ref(state: &AppState) -> Self {
+        state.store.clone()
+    }
+}
+
+impl FromRef<AppState> for Option<Arc<AuthConfig>> {
+    fn from_ref(state: &AppState) -> Self {
+        state.auth.clone()
+    }
+}
+
+impl FromRef<AppState> for Feed {
+    fn from_ref(state: &AppState) -> Self {
+        state.feed.clone()
+    }
+}
+
+impl FromRef<AppState> for Ttl {
+    fn from_ref(state: &AppState) -> Self {
+        state.ttl
+    }
 }
 
 pub struct Server {
@@ -37,155 +168,498 @@ impl Server {
     }
 
     pub async fn run(self) -> Result<(), ApiError> {
-        log::info!("connecting to redis: url={}", self.config.redis);
-
-        // Create the redis client.
-        let redis = redis::Client::open(self.config.redis)?;
-        let redis = redis.get_connection_manager().await?;
-
-        let app = Router::new()
-            .route(
-                "/origin/*namespace",
-                get(get_origin)
-                    .post(set_origin)
-                    .delete(delete_origin)
-                    .patch(patch_origin),
-            )
-            .with_state(redis);
+        let config = self.config;
+
+        if config.lease_ttl_min > config.lease_ttl_max {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--lease-ttl-min exceeds --lease-ttl-max",
+            );
+            return Err(err.into());
+        }
 
-        log::info!("serving requests: bind={}", self.config.bind);
+        let ttl = Ttl {
+            default: config.lease_ttl,
+            min: config.lease_ttl_min,
+            max: config.lease_ttl_max,
+        };
+
+        let store: Store = match (config.redis, config.store_file) {
+            (Some(url), _) => {
+                log::info!("connecting to redis: url={}", url);
+                Arc::new(RedisStore::connect(url).await?)
+            }
+            (None, Some(path)) => {
+                log::info!("using in-memory store: path={}", path.display());
+                Arc::new(MemoryStore::open(path)?)
+            }
+            (None, None) => {
+                log::info!("using in-memory store");
+                Arc::new(MemoryStore::new())
+            }
+        };
+
+        let auth = match &config.auth {
+            Some(path) => {
+                log::info!("requiring authentication: path={}", path.display());
+                Some(Arc::new(AuthConfig::load(path)?))
+            }
+            None => None,
+        };
+
+        let tls = match (&config.tls_cert, &config.tls_key) {
+            (Some(cert), Some(key)) => Some(tls::server_config(
+                cert,
+                key,
+                config.tls_client_root.as_deref(),
+            )?),
+            _ => None,
+        };
+
+        log::info!(
+            "serving requests: bind={} tls={}",
+            config.bind,
+            tls.is_some()
+        );
+
+        let sweep = time::Duration::from_secs(config.sweep_interval);
+        let app = router(AppState::new(store, auth, ttl, sweep));
+        let listener = tokio::net::TcpListener::bind(&config.bind).await?;
+
+        match tls {
+            Some(tls) => serve_tls(listener, app, tls).await,
+            None => {
+                axum::serve(listener, app.into_make_service()).await?;
+                Ok(())
+            }
+        }
+    }
 
-        let listener = tokio::net::TcpListener::bind(&self.config.bind).await?;
+    /// Serve plain HTTP requests on the listener using the given store, without authentication.
+    /// Useful for tests, ex. with a [MemoryStore].
+    pub async fn serve(
+        listener: tokio::net::TcpListener,
+        store: Arc<dyn OriginStore>,
+    ) -> Result<(), ApiError> {
+        let app = router(AppState::new(store, None, Ttl::default(), SWEEP_INTERVAL));
         axum::serve(listener, app.into_make_service()).await?;
 
         Ok(())
     }
 }
 
-async fn get_origin(
-    Path(namespace): Path<String>,
-    State(mut redis): State<ConnectionManager>,
-) -> Result<Json<Origin>, AppError> {
-    let key = origin_key(&namespace);
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/origins", get(list_origins))
+        .route("/events", get(watch_origins))
+        .route(
+            "/origin/*namespace",
+            get(get_origin)
+                .post(set_origin)
+                .delete(delete_origin)
+                .patch(patch_origin),
+        )
+        .with_state(state)
+}
 
-    let payload: Option<String> = redis.get(&key).await?;
-    let payload = payload.ok_or(AppError::NotFound)?;
-    let origin: Origin = serde_json::from_str(&payload)?;
+// axum::serve doesn't support TLS, so accept and serve each connection ourselves.
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    config: rustls::ServerConfig,
+) -> Result<(), ApiError> {
+    let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(config));
+
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::debug!("TLS handshake failed: addr={} err={}", addr, err);
+                    return;
+                }
+            };
+
+            // Identify the client by its certificate, if any, for every request on the connection.
+            let cert = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| ClientCert::new(cert));
+
+            let service = hyper::service::service_fn(move |mut req: Request<_>| {
+                if let Some(cert) = &cert {
+                    req.extensions_mut().insert(cert.clone());
+                }
+                app.clone().oneshot(req)
+            });
+
+            if let Err(err) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::debug!("connection failed: addr={} err={}", addr, err);
+            }
+        });
+    }
+}
 
-    Ok(Json(origin))
+async fn get_origin(
+    Path(namespace): Path<String>,
+    State(store): State<Store>,
+    _scopes: Scopes,
+) -> Result<Json<Lease>, AppError> {
+    let lease = store.get(&namespace).await?.ok_or(AppError::NotFound)?;
+    Ok(Json(lease))
 }
 
 async fn set_origin(
-    State(mut redis): State<ConnectionManager>,
+    State(store): State<Store>,
+    State(feed): State<Feed>,
+    State(ttl): State<Ttl>,
     Path(namespace): Path<String>,
+    Query(options): Query<LeaseOptions>,
+    scopes: Scopes,
     Json(origin): Json<Origin>,
-) -> Result<(), AppError> {
-    // TODO validate origin
+) -> Result<Json<Lease>, AppError> {
+    scopes.check(&namespace)?;
 
-    let key = origin_key(&namespace);
-
-    // Convert the input back to JSON after validating it add adding any fields (TODO)
-    let payload = serde_json::to_string(&origin)?;
-
-    // Attempt to get the current value for the key
-    let current: Option<String> = redis::cmd("GET").arg(&key).query_async(&mut redis).await?;
+    // TODO validate origin
 
-    if let Some(current) = &current {
-        if current.eq(&payload) {
-            // The value is the same, so we're done.
-            return Ok(());
-        } else {
-            return Err(AppError::Duplicate);
-        }
-    }
+    let ttl = ttl.grant(options.ttl);
+    let lease = store.create(&namespace, origin, options, ttl).await?;
 
-    let res: Option<String> = redis::cmd("SET")
-        .arg(key)
-        .arg(payload)
-        .arg("NX")
-        .arg("EX")
-        .arg(600) // Set the key to expire in 10 minutes; the origin needs to keep refreshing it.
-        .query_async(&mut redis)
-        .await?;
+    log::info!(
+        "leased origin: namespace={} url={} token={} ttl={} takeover={}",
+        namespace,
+        lease.origin.url,
+        lease.token,
+        lease.ttl,
+        options.takeover
+    );
 
-    if res.is_none() {
-        return Err(AppError::Duplicate);
-    }
+    feed.update(&namespace, Some(lease.clone()), false);
 
-    Ok(())
+    Ok(Json(lease))
 }
 
 async fn delete_origin(
     Path(namespace): Path<String>,
-    State(mut redis): State<ConnectionManager>,
+    State(store): State<Store>,
+    State(feed): State<Feed>,
+    Query(lease): Query<LeaseToken>,
+    scopes: Scopes,
 ) -> Result<(), AppError> {
-    let key = origin_key(&namespace);
-    match redis.del(key).await? {
-        0 => Err(AppError::NotFound),
-        _ => Ok(()),
-    }
+    scopes.check(&namespace)?;
+    store.delete(&namespace, lease.token).await?;
+    feed.update(&namespace, None, false);
+    Ok(())
 }
 
-// Update the expiration deadline.
+// Update the expiration deadline, and the metadata if provided.
 async fn patch_origin(
     Path(namespace): Path<String>,
-    State(mut redis): State<ConnectionManager>,
-    Json(origin): Json<Origin>,
+    State(store): State<Store>,
+    State(feed): State<Feed>,
+    State(ttl): State<Ttl>,
+    scopes: Scopes,
+    Json(refresh): Json<LeaseRefresh>,
 ) -> Result<(), AppError> {
-    let key = origin_key(&namespace);
+    scopes.check(&namespace)?;
 
-    // Make sure the contents haven't changed
-    // TODO make a LUA script to do this all in one operation.
-    let payload: Option<String> = redis.get(&key).await?;
-    let payload = payload.ok_or(AppError::NotFound)?;
-    let expected: Origin = serde_json::from_str(&payload)?;
+    let ttl = ttl.grant(refresh.ttl);
+    store
+        .refresh(&namespace, refresh.token, refresh.origin, ttl)
+        .await?;
 
-    if expected != origin {
-        return Err(AppError::Duplicate);
+    // Get the lease to include any new metadata in the event.
+    if let Some(lease) = store.get(&namespace).await? {
+        feed.update(&namespace, Some(lease), true);
     }
+    Ok(())
+}
 
-    // Reset the timeout to 10 minutes.
-    match redis.expire(key, 600).await? {
-        0 => Err(AppError::NotFound),
-        _ => Ok(()),
-    }
+async fn list_origins(
+    State(store): State<Store>,
+    Query(options): Query<ListOptions>,
+    _scopes: Scopes,
+) -> Result<Json<OriginList>, AppError> {
+    let limit = options.limit.unwrap_or(LIST_LIMIT).clamp(1, LIST_LIMIT_MAX);
+
+    // Get an extra origin to find out if there's another page.
+    let mut origins = store
+        .list(&options.prefix, options.after.as_deref(), limit + 1)
+        .await?;
+
+    let next = match origins.len() > limit {
+        true => {
+            origins.truncate(limit);
+            origins.last().map(|entry| entry.namespace.clone())
+        }
+        false => None,
+    };
+
+    Ok(Json(OriginList { origins, next }))
 }
 
-fn origin_key(namespace: &str) -> String {
-    format!("origin.{namespace}")
+// Stream changes to origins using server-sent events, starting with the current origins.
+// The stream ends if the client falls behind, so it can reconnect and start over.
+async fn watch_origins(
+    State(feed): State<Feed>,
+    Query(options): Query<WatchOptions>,
+    _scopes: Scopes,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (current, events) = feed.watch(&options.prefix);
+    let prefix = options.prefix;
+
+    let changes = futures::stream::unfold(events, move |mut events| {
+        let prefix = prefix.clone();
+        async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) if event.namespace.starts_with(&prefix) => {
+                        return Some((event, events))
+                    }
+                    Ok(_) => continue,
+                    Err(_) => return None,
+                }
+            }
+        }
+    });
+
+    let stream = futures::stream::iter(current)
+        .chain(changes)
+        .map(|event: OriginEvent| Event::default().event(event.kind.as_str()).json_data(event));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
 #[derive(thiserror::Error, Debug)]
-enum AppError {
-    #[error("redis error")]
-    Redis(#[from] redis::RedisError),
-
-    #[error("json error")]
-    Json(#[from] serde_json::Error),
+pub(crate) enum AppError {
+    #[error("store error")]
+    Store(ApiError),
 
     #[error("not found")]
     NotFound,
 
+    /// The namespace is leased by another origin, or the fencing token is stale.
     #[error("duplicate ID")]
     Duplicate,
+
+    /// Authentication is required, and the credentials were missing or invalid.
+    #[error("unauthorized")]
+    Unauthorized,
+
+    /// The credentials don't grant access to the namespace.
+    #[error("forbidden")]
+    Forbidden,
+}
+
+impl From<ApiError> for AppError {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::NotFound => AppError::NotFound,
+            ApiError::Conflict => AppError::Duplicate,
+            err => AppError::Store(err),
+        }
+    }
 }
 
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         match self {
-            AppError::Redis(e) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("redis error: {e}"),
-            )
-                .into_response(),
-            AppError::Json(e) => (
+            AppError::Store(e) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                format!("json error: {e}"),
+                format!("store error: {e}"),
             )
                 .into_response(),
             AppError::NotFound => StatusCode::NOT_FOUND.into_response(),
             AppError::Duplicate => StatusCode::CONFLICT.into_response(),
+            AppError::Unauthorized => (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+            )
+                .into_response(),
+            AppError::Forbidden => StatusCode::FORBIDDEN.into_response(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{Client, ClientConfig};
+
+    fn testdata(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("testdata")
+            .join(name)
+    }
+
+    fn origin() -> Origin {
+        Origin::new("https://relay.example".parse().unwrap())
+    }
+
+    // The token may lease live/ and the test client certificate may lease relay/.
+    fn auth() -> AuthConfig {
+        let pem = std::fs::read(testdata("client.pem")).unwrap();
+        let cert = rustls_pemfile::certs(&mut pem.as_slice())
+            .next()
+            .unwrap()
+            .unwrap();
+
+        AuthConfig {
+            tokens: HashMap::from([("secret".to_string(), vec!["live/".to_string()])]),
+            certificates: HashMap::from([(
+                ClientCert::new(&cert).fingerprint,
+                vec!["relay/".to_string()],
+            )]),
+        }
+    }
+
+    // Serve plain HTTP on a random port, like [Server::serve] but with the given credentials.
+    async fn serve(auth: AuthConfig) -> url::Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let state = AppState::new(
+            Arc::new(MemoryStore::new()),
+            Some(Arc::new(auth)),
+            Ttl::default(),
+            SWEEP_INTERVAL,
+        );
+        let app = router(state);
+        tokio::spawn(async move { axum::serve(listener, app.into_make_service()).await });
+
+        url.parse().unwrap()
+    }
+
+    // Serve HTTPS on a random port, verifying client certificates against the test CA.
+    async fn serve_https(auth: AuthConfig) -> url::Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("https://{}/", listener.local_addr().unwrap());
+
+        let config = tls::server_config(
+            &testdata("server.pem"),
+            &testdata("server.key"),
+            Some(&testdata("ca.pem")),
+        )
+        .unwrap();
+
+        let state = AppState::new(
+            Arc::new(MemoryStore::new()),
+            Some(Arc::new(auth)),
+            Ttl::default(),
+            SWEEP_INTERVAL,
+        );
+        tokio::spawn(serve_tls(listener, router(state), config));
+
+        url.parse().unwrap()
+    }
+
+    fn client(url: &url::Url, token: Option<&str>, cert: bool) -> Client {
+        let identity = cert.then(|| {
+            let mut identity = std::fs::read(testdata("client.pem")).unwrap();
+            identity.extend(std::fs::read(testdata("client.key")).unwrap());
+            identity
+        });
+
+        let config = ClientConfig {
+            token: token.map(str::to_string),
+            identity,
+            roots: vec![std::fs::read(testdata("ca.pem")).unwrap()],
+        };
+
+        Client::with_config(url.clone(), config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unauthenticated() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url: url::Url = format!("http://{}/", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        tokio::spawn(Server::serve(listener, Arc::new(MemoryStore::new())));
+
+        // Anybody can lease any namespace.
+        let client = Client::new(url);
+        let lease = client.set_origin("foo", origin()).await.unwrap();
+        assert_eq!(client.get_origin("foo").await.unwrap(), Some(origin()));
+        client.delete_origin("foo", lease.token).await.unwrap();
+        assert_eq!(client.get_origin("foo").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn token() {
+        let url = serve(auth()).await;
+
+        for client in [
+            client(&url, None, false),
+            client(&url, Some("wrong"), false),
+        ] {
+            let res = client.set_origin("live/foo", origin()).await;
+            assert!(matches!(res, Err(ApiError::Unauthorized)), "{res:?}");
+
+            let res = client.get_origin("live/foo").await;
+            assert!(matches!(res, Err(ApiError::Unauthorized)), "{res:?}");
         }
+
+        let client = client(&url, Some("secret"), false);
+        let lease = client.set_origin("live/foo", origin()).await.unwrap();
+        client
+            .refresh_origin("live/foo", lease.token)
+            .await
+            .unwrap();
+
+        let res = client.set_origin("other/foo", origin()).await;
+        assert!(matches!(res, Err(ApiError::Forbidden)), "{res:?}");
+        let res = client.refresh_origin("other/foo", lease.token).await;
+        assert!(matches!(res, Err(ApiError::Forbidden)), "{res:?}");
+        let res = client.delete_origin("other/foo", lease.token).await;
+        assert!(matches!(res, Err(ApiError::Forbidden)), "{res:?}");
+        let res = client.set_origin("lively/foo", origin()).await;
+        assert!(matches!(res, Err(ApiError::Forbidden)), "{res:?}");
+
+        // Any valid credential may look up origins outside its scopes.
+        assert_eq!(client.get_origin("other/foo").await.unwrap(), None);
+        assert_eq!(client.get_origin("live/foo").await.unwrap(), Some(origin()));
+    }
+
+    #[tokio::test]
+    async fn certificate() {
+        let url = serve_https(auth()).await;
+
+        // The scopes are looked up using the fingerprint of the client certificate.
+        let relay = client(&url, None, true);
+        relay.set_origin("relay/foo", origin()).await.unwrap();
+        let res = relay.set_origin("live/foo", origin()).await;
+        assert!(matches!(res, Err(ApiError::Forbidden)), "{res:?}");
+
+        // A certificate is optional, so a token still works.
+        let token = client(&url, Some("secret"), false);
+        token.set_origin("live/foo", origin()).await.unwrap();
+
+        let anonymous = client(&url, None, false);
+        let res = anonymous.get_origin("relay/foo").await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)), "{res:?}");
+
+        // An invalid token is rejected, even with a valid certificate.
+        let invalid = client(&url, Some("wrong"), true);
+        let res = invalid.get_origin("relay/foo").await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)), "{res:?}");
+
+        // A certificate signed by a trusted root must still be listed.
+        let url = serve_https(AuthConfig::default()).await;
+        let unknown = client(&url, None, true);
+        let res = unknown.get_origin("relay/foo").await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)), "{res:?}");
     }
 }
@@ -0,0 +1,255 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    ops::Bound,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time,
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use super::{unix_millis, OriginStore};
+use crate::{ApiError, Lease, LeaseOptions, Origin, OriginEntry};
+
+/// Stores leases in memory, for development and testing without Redis.
+///
+/// The leases are optionally written to a file after every change and loaded on startup,
+/// so they survive a restart. Expired leases are removed lazily.
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    state: Arc<Mutex<State>>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct State {
+    // The last fencing token handed out.
+    token: u64,
+
+    leases: BTreeMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    lease: Lease,
+
+    // Wall clock time, so it's still meaningful after a restart.
+    expires: time::SystemTime,
+}
+
+impl State {
+    // Get the lease, removing it if it expired.
+    fn get(&mut self, namespace: &str, now: time::SystemTime) -> Option<&mut Entry> {
+        if self.leases.get(namespace)?.expires <= now {
+            self.leases.remove(namespace);
+            return None;
+        }
+
+        self.leases.get_mut(namespace)
+    }
+
+    // Get the lease, making sure the token matches.
+    fn get_token(
+        &mut self,
+        namespace: &str,
+        token: u64,
+        now: time::SystemTime,
+    ) -> Result<&mut Entry, ApiError> {
+        let entry = self.get(namespace, now).ok_or(ApiError::NotFound)?;
+        match entry.lease.token == token {
+            true => Ok(entry),
+            false => Err(ApiError::Conflict),
+        }
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Persist leases to the given file, loading any existing leases from it.
+    pub fn open(path: PathBuf) -> Result<Self, ApiError> {
+        let state = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            path: Some(path),
+        })
+    }
+
+    // Write the state to a temporary file and then rename it, so a crash can't corrupt it.
+    fn save(&self, state: &mut State, now: time::SystemTime) -> Result<(), ApiError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        state.leases.retain(|_, entry| entry.expires > now);
+
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec(state)?)?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OriginStore for MemoryStore {
+    async fn get(&self, namespace: &str) -> Result<Option<Lease>, ApiError> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.get(namespace, time::SystemTime::now());
+
+        Ok(entry.map(|entry| entry.lease.clone()))
+    }
+
+    async fn create(
+        &self,
+        namespace: &str,
+        origin: Origin,
+        options: LeaseOptions,
+        ttl: time::Duration,
+    ) -> Result<Lease, ApiError> {
+        let now = time::SystemTime::now();
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(entry) = state.get(namespace, now) {
+            if !options.takeover || entry.lease.origin.url != origin.url {
+                return Err(ApiError::Conflict);
+            }
+        }
+
+        state.token += 1;
+        let lease = Lease {
+            origin,
+            token: state.token,
+            registered: unix_millis(),
+            ttl: ttl.as_secs(),
+        };
+
+        state.leases.insert(
+            namespace.to_string(),
+            Entry {
+                lease: lease.clone(),
+                expires: now + ttl,
+            },
+        );
+
+        self.save(&mut state, now)?;
+
+        Ok(lease)
+    }
+
+    async fn refresh(
+        &self,
+        namespace: &str,
+        token: u64,
+        origin: Option<Origin>,
+        ttl: time::Duration,
+    ) -> Result<(), ApiError> {
+        let now = time::SystemTime::now();
+        let mut state = self.state.lock().unwrap();
+
+        let entry = state.get_token(namespace, token, now)?;
+        if let Some(origin) = origin {
+            if origin.url != entry.lease.origin.url {
+                return Err(ApiError::Conflict);
+            }
+            entry.lease.origin = origin;
+        }
+        entry.lease.ttl = ttl.as_secs();
+        entry.expires = now + ttl;
+
+        self.save(&mut state, now)
+    }
+
+    async fn delete(&self, namespace: &str, token: u64) -> Result<(), ApiError> {
+        let now = time::SystemTime::now();
+        let mut state = self.state.lock().unwrap();
+
+        state.get_token(namespace, token, now)?;
+        state.leases.remove(namespace);
+        self.save(&mut state, now)
+    }
+
+    async fn list(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<OriginEntry>, ApiError> {
+        let now = time::SystemTime::now();
+        let state = self.state.lock().unwrap();
+
+        let start = match after {
+            Some(after) if after >= prefix => Bound::Excluded(after.to_string()),
+            _ => Bound::Included(prefix.to_string()),
+        };
+
+        let entries = state
+            .leases
+            .range((start, Bound::Unbounded))
+            .take_while(|(namespace, _)| namespace.starts_with(prefix))
+            .filter(|(_, entry)| entry.expires > now)
+            .take(limit)
+            .map(|(namespace, entry)| OriginEntry {
+                namespace: namespace.clone(),
+                lease: entry.lease.clone(),
+            })
+            .collect();
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: time::Duration = time::Duration::from_secs(60);
+
+    fn origin(url: &str) -> Origin {
+        Origin::new(url.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn persist() {
+        let path = std::env::temp_dir().join(format!("moq-api-{}.json", std::process::id()));
+
+        let store = MemoryStore::open(path.clone()).unwrap();
+        let lease = store
+            .create(
+                "foo",
+                origin("https://a.example"),
+                LeaseOptions::default(),
+                TTL,
+            )
+            .await
+            .unwrap();
+
+        // Reload the leases, and make sure tokens aren't reused.
+        let store = MemoryStore::open(path.clone()).unwrap();
+        assert_eq!(store.get("foo").await.unwrap(), Some(lease.clone()));
+        store.delete("foo", lease.token).await.unwrap();
+
+        let next = store
+            .create(
+                "foo",
+                origin("https://a.example"),
+                LeaseOptions::default(),
+                TTL,
+            )
+            .await
+            .unwrap();
+        assert!(next.token > lease.token);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+mod memory;
+mod redis;
+
+pub use self::memory::*;
+pub use self::redis::*;
+
+use std::time;
+
+use async_trait::async_trait;
+
+use crate::{ApiError, Lease, LeaseOptions, Origin, OriginEntry};
+
+/// Storage for origin leases.
+///
+/// Each namespace is leased by at most one origin, which expires after the TTL unless refreshed.
+/// Every new lease gets a larger fencing token than any previous lease of the same namespace.
+#[async_trait]
+pub trait OriginStore: Send + Sync {
+    /// Get the current lease for the namespace, if any.
+    async fn get(&self, namespace: &str) -> Result<Option<Lease>, ApiError>;
+
+    /// Lease the namespace, returning [ApiError::Conflict] if it's leased by another origin.
+    ///
+    /// An existing lease with the same URL is replaced when [LeaseOptions::takeover] is set.
+    async fn create(
+        &self,
+        namespace: &str,
+        origin: Origin,
+        options: LeaseOptions,
+        ttl: time::Duration,
+    ) -> Result<Lease, ApiError>;
+
+    /// Reset the expiration of the lease, and replace the origin metadata if provided.
+    ///
+    /// Returns [ApiError::NotFound] if there's no lease, or [ApiError::Conflict] if the token is stale
+    /// or the origin URL doesn't match.
+    async fn refresh(
+        &self,
+        namespace: &str,
+        token: u64,
+        origin: Option<Origin>,
+        ttl: time::Duration,
+    ) -> Result<(), ApiError>;
+
+    /// Release the lease, with the same errors as [OriginStore::refresh].
+    async fn delete(&self, namespace: &str, token: u64) -> Result<(), ApiError>;
+
+    /// List up to `limit` leases with a namespace starting with the prefix, sorted by namespace.
+    /// Only namespaces after `after` are returned, for pagination.
+    async fn list(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<OriginEntry>, ApiError>;
+}
+
+// The current time in milliseconds since the UNIX epoch, used as the registration time.
+fn unix_millis() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// The same rules are checked for every store, using the trait.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: time::Duration = time::Duration::from_secs(60);
+
+    fn origin(url: &str) -> Origin {
+        Origin::new(url.parse().unwrap())
+    }
+
+    // Run the tests against every store, using a unique prefix so a shared Redis can be reused.
+    async fn check(store: &dyn OriginStore) {
+        let prefix = format!("test-{}-{}/", std::process::id(), unix_millis());
+
+        lease(store, &prefix).await;
+        update(store, &prefix).await;
+        list(store, &prefix).await;
+    }
+
+    async fn lease(store: &dyn OriginStore, prefix: &str) {
+        let foo = &format!("{prefix}lease");
+        let a = origin("https://a.example");
+        let b = origin("https://b.example");
+
+        let lease = store
+            .create(foo, a.clone(), LeaseOptions::default(), TTL)
+            .await
+            .unwrap();
+        assert_eq!(store.get(foo).await.unwrap(), Some(lease.clone()));
+        assert_eq!(lease.ttl, TTL.as_secs());
+
+        // Only a takeover replaces an existing lease, even for the same origin.
+        let res = store
+            .create(foo, a.clone(), LeaseOptions::default(), TTL)
+            .await;
+        assert!(matches!(res, Err(ApiError::Conflict)));
+
+        // Another origin can't lease or take over the namespace.
+        let takeover = LeaseOptions {
+            takeover: true,
+            ..Default::default()
+        };
+        let res = store.create(foo, b.clone(), takeover, TTL).await;
+        assert!(matches!(res, Err(ApiError::Conflict)));
+
+        // The same origin can take it over, fencing the old token.
+        let next = store.create(foo, a, takeover, TTL).await.unwrap();
+        assert!(next.token > lease.token);
+
+        let res = store.refresh(foo, lease.token, None, TTL).await;
+        assert!(matches!(res, Err(ApiError::Conflict)));
+        let res = store.delete(foo, lease.token).await;
+        assert!(matches!(res, Err(ApiError::Conflict)));
+        store.refresh(foo, next.token, None, TTL).await.unwrap();
+
+        store.delete(foo, next.token).await.unwrap();
+        assert_eq!(store.get(foo).await.unwrap(), None);
+        let res = store.refresh(foo, next.token, None, TTL).await;
+        assert!(matches!(res, Err(ApiError::NotFound)));
+        let res = store.delete(foo, next.token).await;
+        assert!(matches!(res, Err(ApiError::NotFound)));
+
+        // A takeover creates the lease if there isn't one, and tokens are never reused.
+        let taken = store.create(foo, b.clone(), takeover, TTL).await.unwrap();
+        assert!(taken.token > next.token);
+        store.delete(foo, taken.token).await.unwrap();
+
+        // Leases expire unless refreshed.
+        store
+            .create(foo, b, LeaseOptions::default(), time::Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(store.get(foo).await.unwrap(), None);
+    }
+
+    async fn update(store: &dyn OriginStore, prefix: &str) {
+        let foo = &format!("{prefix}update");
+
+        let lease = store
+            .create(
+                foo,
+                origin("https://a.example"),
+                LeaseOptions::default(),
+                TTL,
+            )
+            .await
+            .unwrap();
+        assert!(lease.registered > 0);
+
+        let mut updated = origin("https://a.example");
+        updated.load = Some(10);
+        store
+            .refresh(foo, lease.token, Some(updated.clone()), TTL * 2)
+            .await
+            .unwrap();
+
+        let current = store.get(foo).await.unwrap().unwrap();
+        assert_eq!(current.origin, updated);
+        assert_eq!(current.token, lease.token);
+        assert_eq!(current.registered, lease.registered);
+        assert_eq!(current.ttl, TTL.as_secs() * 2);
+
+        // The URL can't change without a new lease.
+        let res = store
+            .refresh(foo, lease.token, Some(origin("https://b.example")), TTL)
+            .await;
+        assert!(matches!(res, Err(ApiError::Conflict)));
+        assert_eq!(store.get(foo).await.unwrap().unwrap().origin, updated);
+
+        store.delete(foo, lease.token).await.unwrap();
+    }
+
+    async fn list(store: &dyn OriginStore, prefix: &str) {
+        let prefix = &format!("{prefix}list/");
+        for namespace in ["foo", "foo/bar", "food", "bar"] {
+            store
+                .create(
+                    &format!("{prefix}{namespace}"),
+                    origin("https://a.example"),
+                    LeaseOptions::default(),
+                    TTL,
+                )
+                .await
+                .unwrap();
+        }
+
+        let namespaces = |entries: Vec<OriginEntry>| -> Vec<String> {
+            entries
+                .into_iter()
+                .map(|entry| entry.namespace.strip_prefix(prefix).unwrap().to_string())
+                .collect()
+        };
+
+        let foo = &format!("{prefix}foo");
+        let entries = store.list(foo, None, 10).await.unwrap();
+        assert_eq!(namespaces(entries), ["foo", "foo/bar", "food"]);
+
+        let entries = store.list(foo, None, 2).await.unwrap();
+        assert_eq!(namespaces(entries), ["foo", "foo/bar"]);
+
+        let after = format!("{prefix}foo/bar");
+        let entries = store.list(foo, Some(&after), 2).await.unwrap();
+        assert_eq!(namespaces(entries), ["food"]);
+
+        let entries = store.list(prefix, None, 10).await.unwrap();
+        assert_eq!(namespaces(entries), ["bar", "foo", "foo/bar", "food"]);
+    }
+
+    #[tokio::test]
+    async fn memory() {
+        check(&MemoryStore::new()).await;
+    }
+
+    // Checks the Lua scripts, ex. MOQ_API_TEST_REDIS=redis://127.0.0.1 cargo test -- --ignored
+    #[tokio::test]
+    #[ignore = "requires a Redis server at MOQ_API_TEST_REDIS"]
+    async fn redis() {
+        let url = std::env::var("MOQ_API_TEST_REDIS").expect("MOQ_API_TEST_REDIS is not set");
+        let store = RedisStore::connect(url.parse().unwrap()).await.unwrap();
+        check(&store).await;
+    }
+}
@@ -0,0 +1,277 @@
+use std::time;
+
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands, ScanOptions};
+
+use super::{unix_millis, OriginStore};
+use crate::{ApiError, Lease, LeaseOptions, Origin, OriginEntry};
+
+// The number of keys requested per SCAN, and the number of leases read per round trip when listing.
+const SCAN_COUNT: usize = 1000;
+const LIST_BATCH: usize = 100;
+
+// Create a lease unless one exists, or replace a lease held by the same URL when taking over.
+// KEYS: lease, token counter for the namespace. ARGV: url, origin, ttl, takeover, registered.
+// Returns the new fencing token, or 0 if the namespace is already leased.
+const CREATE_SCRIPT: &str = r#"
+local current = redis.call('HGET', KEYS[1], 'url')
+if current and (ARGV[4] ~= '1' or current ~= ARGV[1]) then
+    return 0
+end
+
+local token = redis.call('INCR', KEYS[2])
+redis.call('DEL', KEYS[1])
+redis.call('HSET', KEYS[1], 'url', ARGV[1], 'origin', ARGV[2], 'token', token, 'registered', ARGV[5], 'ttl', ARGV[3])
+redis.call('EXPIRE', KEYS[1], ARGV[3])
+return token
+"#;
+
+// Reset the expiration if the token matches, and replace the origin if provided.
+// KEYS: lease. ARGV: token, ttl, [url, origin].
+// Returns 1 on success, 0 if the token is stale or the URL changed, or -1 if there's no lease.
+const REFRESH_SCRIPT: &str = r#"
+local token = redis.call('HGET', KEYS[1], 'token')
+if not token then
+    return -1
+end
+if token ~= ARGV[1] then
+    return 0
+end
+
+if ARGV[3] then
+    if redis.call('HGET', KEYS[1], 'url') ~= ARGV[3] then
+        return 0
+    end
+    redis.call('HSET', KEYS[1], 'origin', ARGV[4])
+end
+
+redis.call('HSET', KEYS[1], 'ttl', ARGV[2])
+
+redis.call('EXPIRE', KEYS[1], ARGV[2])
+return 1
+"#;
+
+// Delete the lease if the token matches.
+// KEYS: lease. ARGV: token.
+// Returns 1 on success, 0 if the token is stale, or -1 if there's no lease.
+const RELEASE_SCRIPT: &str = r#"
+local token = redis.call('HGET', KEYS[1], 'token')
+if not token then
+    return -1
+end
+if token ~= ARGV[1] then
+    return 0
+end
+
+redis.call('DEL', KEYS[1])
+return 1
+"#;
+
+/// Stores leases in Redis, so multiple moq-api instances can share them.
+///
+/// Each lease is a hash with an expiration, and is modified atomically using Lua scripts.
+/// Each namespace has its own token counter, and both keys use the namespace as the hash tag,
+/// so they're in the same slot when using Redis Cluster and scripts may access both.
+#[derive(Clone)]
+pub struct RedisStore {
+    redis: ConnectionManager,
+}
+
+impl RedisStore {
+    pub async fn connect(url: url::Url) -> Result<Self, ApiError> {
+        let redis = redis::Client::open(url)?;
+        let redis = redis.get_connection_manager().await?;
+
+        Ok(Self { redis })
+    }
+
+    async fn get_key(&self, key: &str) -> Result<Option<Lease>, ApiError> {
+        let fields: LeaseFields = self.redis.clone().hget(key, &LEASE_FIELDS).await?;
+        parse_lease(fields)
+    }
+}
+
+// The hash fields read for a lease, in the order of [LeaseFields].
+const LEASE_FIELDS: [&str; 4] = ["origin", "token", "registered", "ttl"];
+type LeaseFields = (Option<String>, Option<u64>, Option<u64>, Option<u64>);
+
+// Returns None if the lease doesn't exist, ex. it expired.
+fn parse_lease((origin, token, registered, ttl): LeaseFields) -> Result<Option<Lease>, ApiError> {
+    let (origin, token) = match origin.zip(token) {
+        Some(lease) => lease,
+        None => return Ok(None),
+    };
+    let origin: Origin = serde_json::from_str(&origin)?;
+
+    Ok(Some(Lease {
+        origin,
+        token,
+        registered: registered.unwrap_or_default(),
+        ttl: ttl.unwrap_or_default(),
+    }))
+}
+
+#[async_trait]
+impl OriginStore for RedisStore {
+    async fn get(&self, namespace: &str) -> Result<Option<Lease>, ApiError> {
+        self.get_key(&lease_key(namespace)).await
+    }
+
+    async fn create(
+        &self,
+        namespace: &str,
+        origin: Origin,
+        options: LeaseOptions,
+        ttl: time::Duration,
+    ) -> Result<Lease, ApiError> {
+        let payload = serde_json::to_string(&origin)?;
+        let registered = unix_millis();
+
+        let token: u64 = redis::Script::new(CREATE_SCRIPT)
+            .key(lease_key(namespace))
+            .key(token_key(namespace))
+            .arg(origin.url.as_str())
+            .arg(payload)
+            .arg(ttl.as_secs())
+            .arg(options.takeover as u8)
+            .arg(registered)
+            .invoke_async(&mut self.redis.clone())
+            .await?;
+
+        match token {
+            0 => Err(ApiError::Conflict),
+            token => Ok(Lease {
+                origin,
+                token,
+                registered,
+                ttl: ttl.as_secs(),
+            }),
+        }
+    }
+
+    async fn refresh(
+        &self,
+        namespace: &str,
+        token: u64,
+        origin: Option<Origin>,
+        ttl: time::Duration,
+    ) -> Result<(), ApiError> {
+        let script = redis::Script::new(REFRESH_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(lease_key(namespace))
+            .arg(token)
+            .arg(ttl.as_secs());
+
+        if let Some(origin) = origin {
+            invocation
+                .arg(origin.url.as_str())
+                .arg(serde_json::to_string(&origin)?);
+        }
+
+        let res: i64 = invocation.invoke_async(&mut self.redis.clone()).await?;
+
+        script_result(res)
+    }
+
+    async fn delete(&self, namespace: &str, token: u64) -> Result<(), ApiError> {
+        let res: i64 = redis::Script::new(RELEASE_SCRIPT)
+            .key(lease_key(namespace))
+            .arg(token)
+            .invoke_async(&mut self.redis.clone())
+            .await?;
+
+        script_result(res)
+    }
+
+    // SCAN visits every matching key, so this is only suitable for a moderate number of leases.
+    // The leases are then read in pipelined batches, rather than one round trip each.
+    async fn list(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<OriginEntry>, ApiError> {
+        let pattern = format!("{}{}*", LEASE_PREFIX, escape_glob(prefix));
+
+        let mut keys: Vec<String> = Vec::new();
+        let mut redis = self.redis.clone();
+        let options = ScanOptions::default()
+            .with_pattern(pattern)
+            .with_count(SCAN_COUNT);
+        let mut iter = redis.scan_options::<String>(options).await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        drop(iter);
+
+        // SCAN may return duplicates, and isn't sorted.
+        let mut namespaces: Vec<String> = keys
+            .iter()
+            .filter_map(|key| key.strip_prefix(LEASE_PREFIX)?.strip_suffix('}'))
+            .filter(|namespace| after.is_none_or(|after| *namespace > after))
+            .map(str::to_string)
+            .collect();
+        namespaces.sort();
+        namespaces.dedup();
+
+        let mut entries = Vec::new();
+        for batch in namespaces.chunks(LIST_BATCH) {
+            if entries.len() >= limit {
+                break;
+            }
+
+            let mut pipe = redis::pipe();
+            for namespace in batch {
+                pipe.hget(lease_key(namespace), &LEASE_FIELDS);
+            }
+            let leases: Vec<LeaseFields> = pipe.query_async(&mut redis).await?;
+
+            for (namespace, fields) in batch.iter().zip(leases) {
+                // The lease may have expired since the scan.
+                if let Some(lease) = parse_lease(fields)? {
+                    entries.push(OriginEntry {
+                        namespace: namespace.clone(),
+                        lease,
+                    });
+                }
+            }
+        }
+
+        entries.truncate(limit);
+        Ok(entries)
+    }
+}
+
+fn script_result(res: i64) -> Result<(), ApiError> {
+    match res {
+        1 => Ok(()),
+        0 => Err(ApiError::Conflict),
+        _ => Err(ApiError::NotFound),
+    }
+}
+
+// Keys are hash tagged with the namespace, ex. `lease:{live/foo}`, so a lease and its token counter
+// are stored in the same slot, while leases are still spread across a cluster.
+const LEASE_PREFIX: &str = "lease:{";
+
+fn lease_key(namespace: &str) -> String {
+    format!("{LEASE_PREFIX}{namespace}}}")
+}
+
+// The token counter never expires, so tokens are never reused for the namespace.
+fn token_key(namespace: &str) -> String {
+    format!("lease-token:{{{namespace}}}")
+}
+
+// Escape the special characters in a SCAN MATCH pattern.
+fn escape_glob(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
@@ -0,0 +1,58 @@
+use std::{fs, io, path::Path, sync::Arc};
+
+use rustls::{pki_types::CertificateDer, server::WebPkiClientVerifier, RootCertStore};
+
+use crate::ApiError;
+
+/// Load the TLS configuration used to serve HTTPS.
+///
+/// If client roots are provided, client certificates are verified against them but remain optional,
+/// so clients may authenticate using a bearer token instead.
+pub(crate) fn server_config(
+    cert: &Path,
+    key: &Path,
+    client_roots: Option<&Path>,
+) -> Result<rustls::ServerConfig, ApiError> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let chain = load_certs(cert)?;
+    let key = rustls_pemfile::private_key(&mut io::BufReader::new(fs::File::open(key)?))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing private key"))?;
+
+    let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let builder = match client_roots {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots.add(cert)?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .allow_unauthenticated()
+                .build()
+                .map_err(|err| rustls::Error::General(err.to_string()))?;
+
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder.with_single_cert(chain, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(config)
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, ApiError> {
+    let mut file = io::BufReader::new(fs::File::open(path)?);
+    let certs: Vec<_> = rustls_pemfile::certs(&mut file).collect::<Result<_, _>>()?;
+
+    if certs.is_empty() {
+        let err = io::Error::new(io::ErrorKind::InvalidData, "no certificates found");
+        return Err(err.into());
+    }
+
+    Ok(certs)
+}
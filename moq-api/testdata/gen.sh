@@ -0,0 +1,27 @@
+#!/bin/bash
+# Generates the certificates used by the moq-api tests: a CA, a server certificate for localhost, and a client certificate.
+set -euo pipefail
+cd "$(dirname "$0")"
+
+DAYS=36500
+TMP=$(mktemp -d)
+trap 'rm -rf "$TMP"' EXIT
+
+openssl req -x509 -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes -days $DAYS \
+	-subj "/CN=moq-api test CA" -keyout "$TMP/ca.key" -out ca.pem \
+	-addext "basicConstraints=critical,CA:TRUE" -addext "keyUsage=critical,keyCertSign"
+
+issue() {
+	local name=$1 cn=$2 ext=$3
+	openssl req -newkey ec -pkeyopt ec_paramgen_curve:P-256 -nodes \
+		-subj "/CN=$cn" -keyout "$name.key" -out "$TMP/$name.csr"
+	openssl x509 -req -in "$TMP/$name.csr" -CA ca.pem -CAkey "$TMP/ca.key" -CAcreateserial \
+		-CAserial "$TMP/ca.srl" -days $DAYS -out "$name.pem" -extfile <(printf "%s" "$ext")
+}
+
+issue server localhost "basicConstraints=critical,CA:FALSE
+subjectAltName=DNS:localhost,IP:127.0.0.1
+extendedKeyUsage=serverAuth"
+
+issue client relay "basicConstraints=critical,CA:FALSE
+extendedKeyUsage=clientAuth"
@@ -1,11 +1,19 @@
 //! This module contains the structs and functions for the MoQ catalog format
 /// The catalog format is a JSON file that describes the tracks available in a broadcast.
 ///
-/// The current version of the catalog format is draft-01.
+/// The structs follow the draft-01 layout, while [Root::parse] also accepts the WARP (MSF) layout.
 /// https://www.ietf.org/archive/id/draft-ietf-moq-catalogformat-01.html
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+pub mod loc;
+mod patch;
+mod validate;
+mod warp;
+
+pub use patch::*;
+pub use validate::*;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Root {
     pub version: u16,
 
@@ -24,7 +32,7 @@ pub struct Root {
     pub tracks: Vec<Track>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Track {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
@@ -60,8 +68,8 @@ pub struct Track {
 }
 
 impl Track {
-    #[allow(dead_code)] // TODO use
-    fn with_common(&mut self, common: &CommonTrackFields) {
+    /// Fill in any fields that are only provided by the common track fields.
+    pub fn with_common(&mut self, common: &CommonTrackFields) {
         if self.namespace.is_none() {
             self.namespace.clone_from(&common.namespace);
         }
@@ -87,7 +95,7 @@ pub enum TrackPackaging {
     Loc,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct SelectionParam {
     pub codec: Option<String>,
 
@@ -123,7 +131,7 @@ pub struct SelectionParam {
     pub language: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct CommonTrackFields {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
@@ -174,13 +182,13 @@ impl CommonTrackFields {
             if common.namespace.is_some() {
                 track.namespace = None;
             }
-            if track.packaging.is_some() {
+            if common.packaging.is_some() {
                 track.packaging = None;
             }
-            if track.render_group.is_some() {
+            if common.render_group.is_some() {
                 track.render_group = None;
             }
-            if track.alt_group.is_some() {
+            if common.alt_group.is_some() {
                 track.alt_group = None;
             }
         }
@@ -0,0 +1,22 @@
+//! Object extension headers used by the Low Overhead Container (LOC) packaging.
+//!
+//! With LOC, each object is a single encoded frame, ie: the payload of a WebCodecs EncodedVideoChunk
+//! or EncodedAudioChunk, with the frame metadata carried in the object extension headers.
+//! https://www.ietf.org/archive/id/draft-ietf-moq-loc-01.html
+
+/// The presentation timestamp of the frame in microseconds, encoded as a varint.
+pub const TIMESTAMP: u64 = 0x02;
+
+/// Video frame marking, encoded as a varint. Unused for now.
+pub const VIDEO_FRAME_MARKING: u64 = 0x04;
+
+/// The audio level of the frame, encoded as a varint. Unused for now.
+pub const AUDIO_LEVEL: u64 = 0x06;
+
+/// The decode timestamp of the frame in microseconds, encoded as a varint.
+/// Not part of the LOC draft, and only sent when it differs from the presentation timestamp, ie: for B-frames.
+pub const DECODE_TIMESTAMP: u64 = 0x08;
+
+/// The codec specific decoder configuration, ie: the contents of the avcC box.
+/// Sent with the first frame of each group, which is always a keyframe, so decoding can start at any group.
+pub const VIDEO_CONFIG: u64 = 0x0d;
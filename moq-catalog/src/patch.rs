@@ -0,0 +1,480 @@
+//! Catalog delta updates, encoded as JSON Patch (RFC 6902).
+//!
+//! A catalog group starts with a full catalog object, and each following object in the same group is a
+//! patch relative to the previous catalog.  [Root::delta] produces a patch between two catalogs and
+//! [Root::apply] applies one, while [Merger] decodes the objects of a catalog track into the live catalog.
+//! https://www.rfc-editor.org/rfc/rfc6902
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{CatalogError, Root};
+
+#[derive(thiserror::Error, Debug)]
+pub enum PatchError {
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
+
+    #[error("path not found: {0}")]
+    NotFound(String),
+
+    #[error("test failed: {0}")]
+    TestFailed(String),
+
+    #[error("delta update before the full catalog")]
+    MissingCatalog,
+
+    #[error("invalid catalog: {0}")]
+    Catalog(#[from] CatalogError),
+}
+
+/// A single JSON Patch operation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// A JSON Patch document, ie: a catalog delta update.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Patch(pub Vec<Operation>);
+
+impl Patch {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Apply the patch to a JSON document.
+    ///
+    /// The operations are applied in order, and the document is left unchanged if any of them fail.
+    pub fn apply(&self, doc: &mut Value) -> Result<(), PatchError> {
+        let mut patched = doc.clone();
+        for op in &self.0 {
+            apply(&mut patched, op)?;
+        }
+
+        *doc = patched;
+        Ok(())
+    }
+}
+
+impl Root {
+    /// Produce the patch that turns this catalog into the next one.
+    ///
+    /// Tracks are matched by name, so adding or removing a track doesn't touch the others.
+    pub fn delta(&self, next: &Root) -> Result<Patch, PatchError> {
+        let before = serde_json::to_value(self)?;
+        let after = serde_json::to_value(next)?;
+
+        let mut ops = Vec::new();
+        diff("", &before, &after, &mut ops);
+
+        Ok(Patch(ops))
+    }
+
+    /// Apply a delta update to this catalog.
+    pub fn apply(&mut self, patch: &Patch) -> Result<(), PatchError> {
+        let mut doc = serde_json::to_value(&*self)?;
+        patch.apply(&mut doc)?;
+        *self = serde_json::from_value(doc)?;
+
+        Ok(())
+    }
+}
+
+/// Merges the objects of a catalog track into the current catalog.
+///
+/// The first object of each group is a full catalog, and the rest are delta updates.
+/// Patches are applied to the catalog as published, before the common track fields are resolved,
+/// and the result is parsed and validated with [Root::from_value].
+#[derive(Debug, Default)]
+pub struct Merger {
+    document: Option<Value>,
+    current: Option<Root>,
+}
+
+impl Merger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the given object of the current group, returning the updated catalog.
+    ///
+    /// The current catalog is left unchanged if the object is invalid.
+    pub fn push(&mut self, object_id: u64, payload: &[u8]) -> Result<&Root, PatchError> {
+        let document = match (object_id, &self.document) {
+            (0, _) => serde_json::from_slice(payload)?,
+            (_, Some(document)) => {
+                let patch: Patch = serde_json::from_slice(payload)?;
+                let mut document = document.clone();
+                patch.apply(&mut document)?;
+                document
+            }
+            (_, None) => return Err(PatchError::MissingCatalog),
+        };
+
+        let catalog = Root::from_value(document.clone())?;
+        self.document = Some(document);
+
+        Ok(self.current.insert(catalog))
+    }
+
+    /// The current catalog, if a full catalog has been received.
+    pub fn current(&self) -> Option<&Root> {
+        self.current.as_ref()
+    }
+}
+
+// Append the operations that turn `before` into `after`.
+fn diff(path: &str, before: &Value, after: &Value, ops: &mut Vec<Operation>) {
+    if before == after {
+        return;
+    }
+
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            for (key, value) in after {
+                let path = format!("{}/{}", path, escape(key));
+                match before.get(key) {
+                    Some(previous) => diff(&path, previous, value, ops),
+                    None => ops.push(Operation::Add {
+                        path,
+                        value: value.clone(),
+                    }),
+                }
+            }
+
+            for key in before.keys().filter(|key| !after.contains_key(*key)) {
+                let path = format!("{}/{}", path, escape(key));
+                ops.push(Operation::Remove { path });
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            if !diff_named(path, before, after, ops) {
+                ops.push(Operation::Replace {
+                    path: path.to_string(),
+                    value: Value::Array(after.clone()),
+                });
+            }
+        }
+        _ => ops.push(Operation::Replace {
+            path: path.to_string(),
+            value: after.clone(),
+        }),
+    }
+}
+
+// Diff arrays of named objects (ie: tracks) by name, returning false if they can't be matched up.
+fn diff_named(path: &str, before: &[Value], after: &[Value], ops: &mut Vec<Operation>) -> bool {
+    let name = |value: &Value| {
+        value
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+
+    let before_names: Option<Vec<_>> = before.iter().map(name).collect();
+    let after_names: Option<Vec<_>> = after.iter().map(name).collect();
+    let (before_names, after_names) = match (before_names, after_names) {
+        (Some(before), Some(after)) => (before, after),
+        _ => return false,
+    };
+
+    // The remaining elements must keep their order, with any new elements appended at the end.
+    let remaining: Vec<_> = before_names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| after_names.contains(name))
+        .collect();
+    let kept = remaining.len();
+    if kept > after_names.len()
+        || remaining
+            .iter()
+            .zip(&after_names)
+            .any(|((_, before), after)| *before != after)
+    {
+        return false;
+    }
+
+    // Remove in reverse order so the indexes stay valid.
+    for index in (0..before.len()).rev() {
+        if !after_names.contains(&before_names[index]) {
+            ops.push(Operation::Remove {
+                path: format!("{path}/{index}"),
+            });
+        }
+    }
+
+    for (index, (previous, _)) in remaining.into_iter().enumerate() {
+        diff(
+            &format!("{path}/{index}"),
+            &before[previous],
+            &after[index],
+            ops,
+        );
+    }
+
+    for value in &after[kept..] {
+        ops.push(Operation::Add {
+            path: format!("{path}/-"),
+            value: value.clone(),
+        });
+    }
+
+    true
+}
+
+fn apply(doc: &mut Value, op: &Operation) -> Result<(), PatchError> {
+    match op {
+        Operation::Add { path, value } => add(doc, path, value.clone()),
+        Operation::Remove { path } => remove(doc, path).map(|_| ()),
+        Operation::Replace { path, value } => {
+            *pointer(doc, path)? = value.clone();
+            Ok(())
+        }
+        Operation::Move { from, path } => {
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        Operation::Copy { from, path } => {
+            let value = pointer(doc, from)?.clone();
+            add(doc, path, value)
+        }
+        Operation::Test { path, value } => match pointer(doc, path)? == value {
+            true => Ok(()),
+            false => Err(PatchError::TestFailed(path.clone())),
+        },
+    }
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), PatchError> {
+    let (parent, key) = match split(path)? {
+        Some(split) => split,
+        None => {
+            *doc = value;
+            return Ok(());
+        }
+    };
+
+    match pointer(doc, parent)? {
+        Value::Object(object) => {
+            object.insert(key, value);
+        }
+        Value::Array(array) => {
+            let index = match key.as_str() {
+                "-" => array.len(),
+                key => index(key, array.len() + 1, path)?,
+            };
+            array.insert(index, value);
+        }
+        _ => return Err(PatchError::NotFound(path.to_string())),
+    }
+
+    Ok(())
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, PatchError> {
+    let (parent, key) = split(path)?.ok_or_else(|| PatchError::InvalidPath(path.to_string()))?;
+
+    let removed = match pointer(doc, parent)? {
+        Value::Object(object) => object.remove(&key),
+        Value::Array(array) => {
+            let index = index(&key, array.len(), path)?;
+            Some(array.remove(index))
+        }
+        _ => None,
+    };
+
+    removed.ok_or_else(|| PatchError::NotFound(path.to_string()))
+}
+
+// Resolve a JSON pointer (RFC 6901) to a mutable value.
+fn pointer<'a>(doc: &'a mut Value, path: &str) -> Result<&'a mut Value, PatchError> {
+    if !path.is_empty() && !path.starts_with('/') {
+        return Err(PatchError::InvalidPath(path.to_string()));
+    }
+
+    doc.pointer_mut(path)
+        .ok_or_else(|| PatchError::NotFound(path.to_string()))
+}
+
+// Split a JSON pointer into the parent pointer and the unescaped last token, or None for the root.
+fn split(path: &str) -> Result<Option<(&str, String)>, PatchError> {
+    if path.is_empty() {
+        return Ok(None);
+    }
+
+    let (parent, key) = path
+        .rsplit_once('/')
+        .ok_or_else(|| PatchError::InvalidPath(path.to_string()))?;
+
+    Ok(Some((parent, key.replace("~1", "/").replace("~0", "~"))))
+}
+
+fn index(key: &str, len: usize, path: &str) -> Result<usize, PatchError> {
+    match key.parse::<usize>() {
+        Ok(index) if index < len && (key == "0" || !key.starts_with('0')) => Ok(index),
+        _ => Err(PatchError::NotFound(path.to_string())),
+    }
+}
+
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CommonTrackFields, Track};
+    use serde_json::json;
+
+    fn track(name: &str) -> Track {
+        Track {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn catalog(tracks: Vec<Track>) -> Root {
+        Root {
+            version: 1,
+            streaming_format: 1,
+            streaming_format_version: "0.2".to_string(),
+            streaming_delta_updates: true,
+            common_track_fields: CommonTrackFields::default(),
+            tracks,
+        }
+    }
+
+    #[test]
+    fn selection_params_delta() {
+        let mut before = track("1.m4s");
+        before.selection_params.width = Some(1920);
+        before.selection_params.bitrate = Some(1_500_000);
+
+        let mut after = before.clone();
+        after.selection_params.framerate = Some(30);
+        after.selection_params.bitrate = Some(4_000_000);
+
+        let before = catalog(vec![before]);
+        let after = catalog(vec![after]);
+
+        // The order of the operations depends on the order of the keys.
+        let patch = before.delta(&after).unwrap();
+        assert_eq!(patch.0.len(), 2);
+        assert!(patch.0.contains(&Operation::Replace {
+            path: "/tracks/0/selectionParams/bitrate".to_string(),
+            value: json!(4_000_000),
+        }));
+        assert!(patch.0.contains(&Operation::Add {
+            path: "/tracks/0/selectionParams/framerate".to_string(),
+            value: json!(30),
+        }));
+
+        assert!(before.delta(&before).unwrap().is_empty());
+    }
+
+    #[test]
+    fn add_and_remove_tracks() {
+        let before = catalog(vec![track("0.m4s"), track("1.m4s"), track("2.m4s")]);
+        let after = catalog(vec![track("0.m4s"), track("2.m4s"), track("3.m4s")]);
+
+        let patch = before.delta(&after).unwrap();
+        assert_eq!(
+            patch.0,
+            vec![
+                Operation::Remove {
+                    path: "/tracks/1".to_string()
+                },
+                Operation::Add {
+                    path: "/tracks/-".to_string(),
+                    value: serde_json::to_value(track("3.m4s")).unwrap(),
+                },
+            ]
+        );
+
+        let mut merged = before;
+        merged.apply(&patch).unwrap();
+        let names: Vec<_> = merged
+            .tracks
+            .iter()
+            .map(|track| track.name.as_str())
+            .collect();
+        assert_eq!(names, ["0.m4s", "2.m4s", "3.m4s"]);
+    }
+
+    #[test]
+    fn apply_operations() {
+        let mut doc = json!({ "a": { "b": [1, 2] }, "c~d": 3 });
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "test", "path": "/c~0d", "value": 3 },
+            { "op": "add", "path": "/a/b/1", "value": 5 },
+            { "op": "copy", "from": "/a/b", "path": "/e" },
+            { "op": "move", "from": "/c~0d", "path": "/a/f" },
+            { "op": "replace", "path": "/a/b/0", "value": 0 },
+        ]))
+        .unwrap();
+
+        patch.apply(&mut doc).unwrap();
+        assert_eq!(
+            doc,
+            json!({ "a": { "b": [0, 5, 2], "f": 3 }, "e": [1, 5, 2] })
+        );
+
+        // A failed operation leaves the document untouched.
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "remove", "path": "/e/0" },
+            { "op": "test", "path": "/a/f", "value": 4 },
+        ]))
+        .unwrap();
+        assert!(matches!(
+            patch.apply(&mut doc),
+            Err(PatchError::TestFailed(_))
+        ));
+        assert_eq!(doc["e"], json!([1, 5, 2]));
+    }
+
+    #[test]
+    fn merge_objects() {
+        let mut merger = Merger::new();
+        assert!(matches!(
+            merger.push(1, b"[]"),
+            Err(PatchError::MissingCatalog)
+        ));
+
+        let mut first = track("0.loc");
+        first.selection_params.codec = Some("opus".to_string());
+        let mut full = catalog(vec![first]);
+        full.common_track_fields.packaging = Some(crate::TrackPackaging::Loc);
+
+        let full = serde_json::to_vec(&full).unwrap();
+        assert_eq!(merger.push(0, &full).unwrap().tracks.len(), 1);
+
+        // The common packaging is resolved into the added track.
+        let delta = serde_json::to_vec(&json!([
+            { "op": "add", "path": "/tracks/-", "value": { "name": "1.loc", "selectionParams": { "codec": "opus" } } },
+        ]))
+        .unwrap();
+        let merged = merger.push(1, &delta).unwrap();
+        assert_eq!(merged.tracks.len(), 2);
+        assert_eq!(merged.tracks[1].packaging, Some(crate::TrackPackaging::Loc));
+
+        // An invalid update is rejected without losing the current catalog.
+        let invalid = serde_json::to_vec(&json!([
+            { "op": "replace", "path": "/tracks/1/name", "value": "0.loc" },
+        ]))
+        .unwrap();
+        assert!(matches!(
+            merger.push(2, &invalid),
+            Err(PatchError::Catalog(CatalogError::DuplicateTrack(_)))
+        ));
+        assert_eq!(merger.current().unwrap().tracks[1].name, "1.loc");
+    }
+}
@@ -0,0 +1,355 @@
+//! Parsing and validation of catalogs in any supported layout.
+//!
+//! [Root::parse] detects the layout, resolves the common track fields into each track and validates
+//! the result, so consumers never have to look at the common track fields themselves.
+use std::collections::HashSet;
+
+use serde_json::Value;
+
+use crate::{warp, Root, TrackPackaging};
+
+#[derive(thiserror::Error, Debug)]
+pub enum CatalogError {
+    #[error("invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("unsupported catalog version: {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("unsupported streaming format: {0}")]
+    UnsupportedFormat(u16),
+
+    #[error("track has no name")]
+    MissingName,
+
+    #[error("track {track}: missing {field}")]
+    MissingField { track: String, field: &'static str },
+
+    #[error("duplicate track: {0}")]
+    DuplicateTrack(String),
+
+    #[error("track {track}: invalid codec: {codec}")]
+    InvalidCodec { track: String, codec: String },
+
+    #[error("track {track}: unknown dependency: {depends}")]
+    UnknownDependency { track: String, depends: String },
+}
+
+/// The layout of a catalog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// draft-ietf-moq-catalogformat-01, with common track fields and nested selection params.
+    Draft01,
+
+    /// The WARP (MSF) layout, with the selection params flattened into each track.
+    Warp,
+}
+
+impl Format {
+    // Only draft-01 has a streaming format, which is required.
+    fn detect(value: &Value) -> Self {
+        match value.get("streamingFormat") {
+            Some(_) => Self::Draft01,
+            None => Self::Warp,
+        }
+    }
+}
+
+impl Root {
+    /// Parse a catalog in any supported layout, resolving the common track fields into each track.
+    pub fn parse(buf: &[u8]) -> Result<Self, CatalogError> {
+        let value: Value = serde_json::from_slice(buf)?;
+        Self::from_value(value)
+    }
+
+    /// Parse a catalog from a JSON value, see [Root::parse].
+    pub fn from_value(value: Value) -> Result<Self, CatalogError> {
+        let mut root = match Format::detect(&value) {
+            Format::Draft01 => {
+                let root: Root = serde_json::from_value(value)?;
+                if root.version != 1 {
+                    return Err(CatalogError::UnsupportedVersion(root.version));
+                }
+                if root.streaming_format != 1 {
+                    return Err(CatalogError::UnsupportedFormat(root.streaming_format));
+                }
+                root
+            }
+            Format::Warp => {
+                let catalog: warp::Catalog = serde_json::from_value(value)?;
+                if catalog.version != warp::VERSION {
+                    return Err(CatalogError::UnsupportedVersion(catalog.version));
+                }
+                catalog.into()
+            }
+        };
+
+        for track in &mut root.tracks {
+            track.with_common(&root.common_track_fields);
+        }
+
+        root.validate()?;
+
+        Ok(root)
+    }
+
+    /// Serialize the catalog using the given layout.
+    pub fn to_json(&self, format: Format) -> Result<String, CatalogError> {
+        let json = match format {
+            Format::Draft01 => serde_json::to_string_pretty(self)?,
+            Format::Warp => serde_json::to_string_pretty(&warp::Catalog::from(self))?,
+        };
+
+        Ok(json)
+    }
+
+    /// Validate the required fields, codec strings and dependencies of each track.
+    pub fn validate(&self) -> Result<(), CatalogError> {
+        let mut names = HashSet::new();
+
+        for track in &self.tracks {
+            if track.name.is_empty() {
+                return Err(CatalogError::MissingName);
+            }
+
+            let missing = |field| CatalogError::MissingField {
+                track: track.name.clone(),
+                field,
+            };
+
+            let namespace = track
+                .namespace
+                .as_ref()
+                .or(self.common_track_fields.namespace.as_ref());
+            if !names.insert((namespace, &track.name)) {
+                return Err(CatalogError::DuplicateTrack(track.name.clone()));
+            }
+
+            let packaging = track
+                .packaging
+                .as_ref()
+                .or(self.common_track_fields.packaging.as_ref())
+                .ok_or_else(|| missing("packaging"))?;
+
+            // CMAF tracks can't be decoded without the initialization segment.
+            if packaging == &TrackPackaging::Cmaf
+                && track.init_track.is_none()
+                && track.init_data.is_none()
+            {
+                return Err(missing("initTrack"));
+            }
+
+            let codec = track
+                .selection_params
+                .codec
+                .as_ref()
+                .ok_or_else(|| missing("codec"))?;
+            if !valid_codec(codec) {
+                return Err(CatalogError::InvalidCodec {
+                    track: track.name.clone(),
+                    codec: codec.clone(),
+                });
+            }
+        }
+
+        for track in &self.tracks {
+            for depends in track.depends.iter().flatten() {
+                if depends == &track.name || !self.tracks.iter().any(|t| &t.name == depends) {
+                    return Err(CatalogError::UnknownDependency {
+                        track: track.name.clone(),
+                        depends: depends.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Validate a RFC 6381 codec string, strictly for the codecs we know.
+fn valid_codec(codec: &str) -> bool {
+    let mut parts = codec.split('.');
+    let fourcc = parts.next().unwrap_or_default();
+    let parts: Vec<_> = parts.collect();
+
+    let hex =
+        |part: &str, len: usize| part.len() == len && part.chars().all(|c| c.is_ascii_hexdigit());
+    let digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+
+    match fourcc {
+        // avc1.PPCCLL
+        "avc1" | "avc3" => matches!(parts.as_slice(), [config] if hex(config, 6)),
+        // hev1.P.C.TL[.CC]*
+        "hev1" | "hvc1" => match parts.as_slice() {
+            [profile, compat, tier, constraints @ ..] => {
+                let profile = profile.strip_prefix(['A', 'B', 'C']).unwrap_or(profile);
+                let level = tier.strip_prefix(['L', 'H']);
+                digits(profile)
+                    && !compat.is_empty()
+                    && compat.len() <= 8
+                    && compat.chars().all(|c| c.is_ascii_hexdigit())
+                    && level.is_some_and(digits)
+                    && constraints.len() <= 6
+                    && constraints.iter().all(|c| !c.is_empty() && c.len() <= 2)
+            }
+            _ => false,
+        },
+        // av01.P.LLT.DD[.M.CCC.cp.tc.mc.F]
+        "av01" => match parts.as_slice() {
+            [profile, level, depth, ..] => {
+                let tier = level.strip_suffix(['M', 'H']);
+                digits(profile) && tier.is_some_and(digits) && digits(depth)
+            }
+            _ => false,
+        },
+        // vp09.PP.LL.DD[...]
+        "vp09" => parts.len() >= 3 && parts.iter().all(|part| digits(part)),
+        // mp4a.OO[.A]
+        "mp4a" => match parts.as_slice() {
+            [oti] => hex(oti, 2),
+            [oti, aot] => hex(oti, 2) && digits(aot),
+            _ => false,
+        },
+        "opus" | "flac" | "ac-3" | "ec-3" | "vp8" => parts.is_empty(),
+        // Unknown codecs only need to look like a codec string.
+        _ => {
+            !fourcc.is_empty()
+                && codec.split('.').all(|part| {
+                    !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn draft01() -> Value {
+        json!({
+            "version": 1,
+            "streamingFormat": 1,
+            "streamingFormatVersion": "0.2",
+            "supportsDeltaUpdates": true,
+            "commonTrackFields": { "namespace": "bbb", "packaging": "cmaf", "renderGroup": 1 },
+            "tracks": [
+                {
+                    "name": "1.m4s",
+                    "initTrack": "0.mp4",
+                    "selectionParams": { "codec": "avc1.64001f", "width": 1280, "height": 720 },
+                },
+                {
+                    "name": "2.m4s",
+                    "initTrack": "0.mp4",
+                    "selectionParams": { "codec": "mp4a.40.2", "samplerate": 48000 },
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn resolve_common_fields() {
+        let root = Root::from_value(draft01()).unwrap();
+        for track in &root.tracks {
+            assert_eq!(track.namespace.as_deref(), Some("bbb"));
+            assert_eq!(track.packaging, Some(TrackPackaging::Cmaf));
+            assert_eq!(track.render_group, Some(1));
+        }
+    }
+
+    #[test]
+    fn warp_layout() {
+        let root = Root::from_value(draft01()).unwrap();
+        let warp = root.to_json(Format::Warp).unwrap();
+
+        let value: Value = serde_json::from_str(&warp).unwrap();
+        assert_eq!(value["tracks"][0]["codec"], "avc1.64001f");
+        assert_eq!(value["tracks"][1]["packaging"], "cmaf");
+
+        let parsed = Root::parse(warp.as_bytes()).unwrap();
+        assert_eq!(parsed.streaming_format, root.streaming_format);
+        assert_eq!(
+            parsed.streaming_format_version,
+            root.streaming_format_version
+        );
+        assert_eq!(parsed.tracks.len(), 2);
+        assert_eq!(parsed.tracks[0].selection_params.width, Some(1280));
+        assert_eq!(parsed.tracks[1].namespace.as_deref(), Some("bbb"));
+    }
+
+    #[test]
+    fn validation_errors() {
+        let mut value = draft01();
+        value["version"] = json!(2);
+        assert!(matches!(
+            Root::from_value(value),
+            Err(CatalogError::UnsupportedVersion(2))
+        ));
+
+        let mut value = draft01();
+        value["tracks"][1]["name"] = json!("1.m4s");
+        assert!(matches!(
+            Root::from_value(value),
+            Err(CatalogError::DuplicateTrack(name)) if name == "1.m4s"
+        ));
+
+        let mut value = draft01();
+        value["tracks"][0]["selectionParams"]["codec"] = json!("avc1.64");
+        assert!(matches!(
+            Root::from_value(value),
+            Err(CatalogError::InvalidCodec { codec, .. }) if codec == "avc1.64"
+        ));
+
+        let mut value = draft01();
+        value["tracks"][1]
+            .as_object_mut()
+            .unwrap()
+            .remove("initTrack");
+        assert!(matches!(
+            Root::from_value(value),
+            Err(CatalogError::MissingField {
+                field: "initTrack",
+                ..
+            })
+        ));
+
+        let mut value = draft01();
+        value["tracks"][1]["depends"] = json!(["3.m4s"]);
+        assert!(matches!(
+            Root::from_value(value),
+            Err(CatalogError::UnknownDependency { depends, .. }) if depends == "3.m4s"
+        ));
+    }
+
+    #[test]
+    fn codec_strings() {
+        for codec in [
+            "avc1.64001f",
+            "hev1.1.6.L93.B0",
+            "hvc1.2.4.H120.90",
+            "av01.0.08M.08",
+            "vp09.00.31.08",
+            "mp4a.40.2",
+            "mp4a.6B",
+            "opus",
+            "ec-3",
+        ] {
+            assert!(valid_codec(codec), "{codec}");
+        }
+
+        for codec in [
+            "",
+            "avc1",
+            "avc1.64001g",
+            "hev1.1.6",
+            "av01.0.08X.08",
+            "mp4a.40.x",
+            "opus.2",
+            "a b",
+        ] {
+            assert!(!valid_codec(codec), "{codec}");
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! The WARP (MSF) catalog layout, where the selection params are flattened into each track.
+//!
+//! There are no common track fields, so every track carries its own namespace and packaging.
+//! https://www.ietf.org/archive/id/draft-ietf-moq-warp-00.html
+use serde::{Deserialize, Serialize};
+
+use crate::{CommonTrackFields, Root, SelectionParam, Track, TrackPackaging};
+
+/// The catalog version used by the WARP layout.
+pub const VERSION: u16 = 1;
+
+/// The streaming format and version that WARP is advertised as in the draft-01 layout.
+///
+/// The WARP layout doesn't carry these, so they're filled in when converting to a [Root].
+pub const STREAMING_FORMAT: u16 = 1;
+pub const STREAMING_FORMAT_VERSION: &str = "0.2";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct Catalog {
+    pub version: u16,
+
+    #[serde(rename = "generatedAt", skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<u64>,
+
+    #[serde(rename = "isComplete", skip_serializing_if = "Option::is_none")]
+    pub is_complete: Option<bool>,
+
+    pub tracks: Vec<WarpTrack>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub(crate) struct WarpTrack {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packaging: Option<TrackPackaging>,
+
+    #[serde(rename = "initTrack", skip_serializing_if = "Option::is_none")]
+    pub init_track: Option<String>,
+
+    #[serde(rename = "initData", skip_serializing_if = "Option::is_none")]
+    pub init_data: Option<String>,
+
+    #[serde(rename = "renderGroup", skip_serializing_if = "Option::is_none")]
+    pub render_group: Option<u16>,
+
+    #[serde(rename = "altGroup", skip_serializing_if = "Option::is_none")]
+    pub alt_group: Option<u16>,
+
+    #[serde(rename = "temporalId", skip_serializing_if = "Option::is_none")]
+    pub temporal_id: Option<u32>,
+
+    #[serde(rename = "spatialId", skip_serializing_if = "Option::is_none")]
+    pub spatial_id: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depends: Option<Vec<String>>,
+
+    #[serde(flatten)]
+    pub selection_params: SelectionParam,
+}
+
+impl From<Catalog> for Root {
+    fn from(catalog: Catalog) -> Self {
+        let tracks = catalog
+            .tracks
+            .into_iter()
+            .map(|track| Track {
+                namespace: track.namespace,
+                name: track.name,
+                init_track: track.init_track,
+                init_data: track.init_data,
+                packaging: track.packaging,
+                render_group: track.render_group,
+                alt_group: track.alt_group,
+                selection_params: track.selection_params,
+                temporal_id: track.temporal_id,
+                spatial_id: track.spatial_id,
+                depends: track.depends,
+            })
+            .collect();
+
+        Root {
+            version: catalog.version,
+            streaming_format: STREAMING_FORMAT,
+            streaming_format_version: STREAMING_FORMAT_VERSION.to_string(),
+            streaming_delta_updates: true,
+            common_track_fields: CommonTrackFields::default(),
+            tracks,
+        }
+    }
+}
+
+impl From<&Root> for Catalog {
+    fn from(root: &Root) -> Self {
+        let tracks = root
+            .tracks
+            .iter()
+            .map(|track| {
+                let mut track = track.clone();
+                track.with_common(&root.common_track_fields);
+
+                WarpTrack {
+                    namespace: track.namespace,
+                    name: track.name,
+                    packaging: track.packaging,
+                    init_track: track.init_track,
+                    init_data: track.init_data,
+                    render_group: track.render_group,
+                    alt_group: track.alt_group,
+                    temporal_id: track.temporal_id,
+                    spatial_id: track.spatial_id,
+                    depends: track.depends,
+                    selection_params: track.selection_params,
+                }
+            })
+            .collect();
+
+        Catalog {
+            version: VERSION,
+            generated_at: None,
+            is_complete: None,
+            tracks,
+        }
+    }
+}
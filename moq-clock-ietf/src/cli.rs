@@ -16,6 +16,10 @@ pub struct Cli {
     #[command(flatten)]
     pub tls: moq_native_ietf::tls::Args,
 
+    /// The mlog configuration.
+    #[command(flatten)]
+    pub mlog: moq_native_ietf::mlog::Args,
+
     /// Publish the current time to the relay, otherwise only subscribe.
     #[arg(long)]
     pub publish: bool,
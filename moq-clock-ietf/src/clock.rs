@@ -1,7 +1,7 @@
 use anyhow::Context;
 use moq_transport::serve::{
-    Datagram, DatagramsReader, DatagramsWriter, StreamReader, Subgroup, SubgroupWriter,
-    SubgroupsReader, SubgroupsWriter, TrackReader, TrackReaderMode,
+    Datagram, DatagramsReader, DatagramsWriter, Subgroup, SubgroupWriter, SubgroupsReader,
+    SubgroupsWriter, TrackReader, TrackReaderMode,
 };
 
 use chrono::prelude::*;
@@ -45,6 +45,7 @@ impl Publisher {
                         group_id: next_group_id as u64,
                         subgroup_id: 0,
                         priority: 0,
+                        end_of_group: true,
                     })
                     .context("failed to create minute segment")?;
 
@@ -143,22 +144,16 @@ impl Subscriber {
             .await
             .context("failed to get mode")?
         {
-            TrackReaderMode::Stream(stream) => Self::recv_stream(stream).await,
             TrackReaderMode::Subgroups(subgroups) => Self::recv_subgroups(subgroups).await,
             TrackReaderMode::Datagrams(datagrams) => Self::recv_datagrams(datagrams).await,
-        }
-    }
-
-    /// Receives time updates from a stream and prints them to stdout.
-    async fn recv_stream(mut stream_reader: StreamReader) -> anyhow::Result<()> {
-        while let Some(mut stream_group_reader) = stream_reader.next().await? {
-            while let Some(object) = stream_group_reader.read_next().await? {
-                let str = String::from_utf8_lossy(&object);
-                println!("{str}");
+            TrackReaderMode::Mixed(mixed) => {
+                tokio::try_join!(
+                    Self::recv_subgroups(mixed.subgroups),
+                    Self::recv_datagrams(mixed.datagrams),
+                )?;
+                Ok(())
             }
         }
-
-        Ok(())
     }
 
     /// Receives time updates from subgroups and prints them to stdout.
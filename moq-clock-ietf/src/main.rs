@@ -8,11 +8,7 @@ mod clock;
 use clap::Parser;
 use cli::Cli;
 
-use moq_transport::{
-    coding::TrackNamespace,
-    serve,
-    session::{Publisher, Subscriber},
-};
+use moq_transport::{coding::TrackNamespace, mlog::VantagePoint, serve, session::Session};
 
 /// The main entry point for the MoQ Clock IETF example.
 #[tokio::main]
@@ -27,6 +23,7 @@ async fn main() -> anyhow::Result<()> {
 
     let config = Cli::parse();
     let tls = config.tls.load()?;
+    config.mlog.validate()?;
 
     // Create the QUIC endpoint
     let quic = quic::Endpoint::new(quic::Config {
@@ -38,20 +35,25 @@ async fn main() -> anyhow::Result<()> {
     log::info!("connecting to server: url={}", config.url);
 
     // Connect to the server
-    let (session, connection_id) = quic.client.connect(&config.url).await?;
+    let (session, connection_id, transport) = quic.client.connect(&config.url).await?;
 
     log::info!(
         "connected with CID: {} (use this to look up qlog/mlog on server)",
         connection_id
     );
 
-    // Depending on whether we are publishing or subscribing, create the appropriate session
-    if config.publish {
-        // Create the publisher session
-        let (session, mut publisher) = Publisher::connect(session)
-            .await
-            .context("failed to create MoQ Transport session")?;
+    // Create the mlog for this connection ID if mlog directory is configured
+    let mlog = config
+        .mlog
+        .create(&connection_id, "moq-clock", VantagePoint::Client)?;
+
+    // Create the MoQ session, which can both publish and subscribe
+    let (session, mut publisher, mut subscriber) = Session::connect(session, transport, mlog)
+        .await
+        .context("failed to create MoQ Transport session")?;
 
+    // Depending on whether we are publishing or subscribing, use the appropriate role
+    if config.publish {
         if config.datagrams {
             log::info!("publishing clock via datagrams");
 
@@ -86,11 +88,6 @@ async fn main() -> anyhow::Result<()> {
             }
         }
     } else {
-        // Create the subscriber session
-        let (session, mut subscriber) = Subscriber::connect(session)
-            .await
-            .context("failed to create MoQ Transport session")?;
-
         let track_namespace = TrackNamespace::from_utf8_path(&config.namespace);
 
         if config.track_status {
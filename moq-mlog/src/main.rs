@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use serde::Serialize;
+
+mod stats;
+mod timeline;
+mod trace;
+
+use stats::{Delay, Summary, TrackStats};
+use trace::Trace;
+
+/// Analyze MoQ Transport logs (mlog), reporting per-track statistics.
+///
+/// When logs from both ends of a connection are provided, ex. a client and the relay,
+/// the delay between objects being created and parsed is also reported.
+#[derive(Parser, Clone)]
+pub struct Cli {
+    /// The mlog files to analyze, including any rotated files.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+
+    /// Output the report as JSON instead of text.
+    #[arg(long)]
+    pub json: bool,
+
+    /// The maximum number of missing object IDs to print per group.
+    #[arg(long, default_value = "10")]
+    pub max_missing: usize,
+}
+
+#[derive(Serialize)]
+struct Report {
+    traces: Vec<TraceReport>,
+    delays: Vec<Delay>,
+}
+
+#[derive(Serialize)]
+struct TraceReport {
+    name: String,
+    events: usize,
+    skipped: usize,
+    tracks: Vec<TrackStats>,
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let cli = Cli::parse();
+    let traces = Trace::load(&cli.files)?;
+
+    let timelines: Vec<_> = traces
+        .iter()
+        .map(|trace| (trace, timeline::tracks(trace)))
+        .collect();
+
+    for trace in traces.iter().filter(|t| t.reference_time.is_none()) {
+        log::warn!(
+            "{} has no reference time, so it can't be compared with other logs",
+            trace.name()
+        );
+    }
+
+    let report = Report {
+        traces: timelines
+            .iter()
+            .map(|(trace, tracks)| TraceReport {
+                name: trace.name(),
+                events: trace.events.len(),
+                skipped: trace.skipped,
+                tracks: tracks.iter().map(TrackStats::new).collect(),
+            })
+            .collect(),
+        delays: stats::delays(&timelines),
+    };
+
+    match cli.json {
+        true => println!("{}", serde_json::to_string_pretty(&report)?),
+        false => print(&report, cli.max_missing),
+    }
+
+    Ok(())
+}
+
+fn print(report: &Report, max_missing: usize) {
+    for trace in &report.traces {
+        println!("{}: {} events", trace.name, trace.events);
+        if trace.skipped > 0 {
+            println!("  skipped {} unparsable records", trace.skipped);
+        }
+
+        for track in &trace.tracks {
+            println!(
+                "  {} ({}, alias {})",
+                track.name,
+                track.direction.as_str(),
+                track.alias
+            );
+            println!("    objects: {} ({} bytes)", track.objects, track.bytes);
+            println!(
+                "    groups: {} ({} complete)",
+                track.groups, track.complete_groups
+            );
+
+            for (group_id, missing) in &track.missing {
+                let mut ids: Vec<_> = missing
+                    .iter()
+                    .take(max_missing)
+                    .map(u64::to_string)
+                    .collect();
+                if missing.len() > max_missing {
+                    ids.push(format!("... {} more", missing.len() - max_missing));
+                }
+                println!("      group {} missing: {}", group_id, ids.join(", "));
+            }
+
+            if let Some(interval) = &track.interval {
+                println!("    interval: {}", summary(interval));
+            }
+            if let Some(jitter) = track.jitter {
+                println!("    jitter: {:.2}ms", jitter);
+            }
+            if let Some(first_object) = track.first_object {
+                println!("    subscribe to first object: {:.2}ms", first_object);
+            }
+        }
+    }
+
+    if !report.delays.is_empty() {
+        println!("end-to-end delay:");
+    }
+
+    for delay in &report.delays {
+        println!("  {}: {} -> {}", delay.track, delay.sender, delay.receiver);
+        println!(
+            "    {} objects: {}",
+            delay.delay.count,
+            summary(&delay.delay)
+        );
+    }
+}
+
+fn summary(summary: &Summary) -> String {
+    format!(
+        "min {:.2}ms, mean {:.2}ms, p50 {:.2}ms, p95 {:.2}ms, max {:.2}ms",
+        summary.min, summary.mean, summary.p50, summary.p95, summary.max
+    )
+}
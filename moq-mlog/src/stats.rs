@@ -0,0 +1,350 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::Serialize;
+
+use crate::timeline::{Direction, Track};
+use crate::trace::Trace;
+
+/// Statistics for a single track in one direction.
+#[derive(Debug, Serialize)]
+pub struct TrackStats {
+    pub name: String,
+    pub alias: u64,
+    pub direction: Direction,
+
+    /// The number of objects with a payload, and the total payload size.
+    pub objects: usize,
+    pub bytes: u64,
+
+    pub groups: usize,
+    pub complete_groups: usize,
+
+    /// The object IDs missing from each incomplete group.
+    pub missing: BTreeMap<u64, Vec<u64>>,
+
+    /// The time between consecutive objects, in milliseconds.
+    pub interval: Option<Summary>,
+
+    /// The mean difference between consecutive intervals, in milliseconds (RFC 3550 style).
+    pub jitter: Option<f64>,
+
+    /// The time from the SUBSCRIBE or PUBLISH to the first object, in milliseconds.
+    pub first_object: Option<f64>,
+}
+
+/// A summary of a distribution, in milliseconds.
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub count: usize,
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    pub fn new(mut values: Vec<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_by(f64::total_cmp);
+        let percentile = |p: f64| values[((values.len() - 1) as f64 * p).round() as usize];
+
+        Some(Self {
+            count: values.len(),
+            min: values[0],
+            mean: values.iter().sum::<f64>() / values.len() as f64,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            max: values[values.len() - 1],
+        })
+    }
+}
+
+#[derive(Default)]
+struct Group {
+    ids: BTreeSet<u64>,
+
+    // The object ID of the EndOfGroup marker, if received.
+    end: Option<u64>,
+}
+
+impl TrackStats {
+    pub fn new(track: &Track) -> Self {
+        let payloads: Vec<_> = track
+            .objects
+            .iter()
+            .filter(|object| object.status.is_none())
+            .collect();
+
+        let mut groups: BTreeMap<u64, Group> = BTreeMap::new();
+        for object in &track.objects {
+            let group = groups.entry(object.group_id).or_default();
+            match object.status.as_deref() {
+                Some("EndOfGroup") | Some("EndOfTrack") => group.end = Some(object.object_id),
+                _ => {
+                    group.ids.insert(object.object_id);
+                }
+            }
+        }
+
+        // The subscription may start in the middle of the first group, so only count gaps after the first object.
+        let first_group = groups.keys().next().copied();
+        let missing: BTreeMap<u64, Vec<u64>> = groups
+            .iter()
+            .filter_map(|(&group_id, group)| {
+                let first = match Some(group_id) == first_group {
+                    true => *group.ids.first()?,
+                    false => 0,
+                };
+                let last = match group.end {
+                    Some(end) => end.checked_sub(1)?,
+                    None => *group.ids.last()?,
+                };
+
+                let missing: Vec<_> = (first..=last)
+                    .filter(|id| !group.ids.contains(id))
+                    .collect();
+                (!missing.is_empty()).then_some((group_id, missing))
+            })
+            .collect();
+
+        let intervals: Vec<f64> = payloads.windows(2).map(|w| w[1].time - w[0].time).collect();
+        let jitter = match intervals.len() {
+            0 | 1 => None,
+            n => Some(
+                intervals
+                    .windows(2)
+                    .map(|w| (w[1] - w[0]).abs())
+                    .sum::<f64>()
+                    / (n - 1) as f64,
+            ),
+        };
+
+        Self {
+            name: track.name.clone(),
+            alias: track.alias,
+            direction: track.direction,
+            objects: payloads.len(),
+            bytes: payloads.iter().map(|object| object.size).sum(),
+            groups: groups.len(),
+            complete_groups: groups.len() - missing.len(),
+            missing,
+            interval: Summary::new(intervals),
+            jitter,
+            first_object: track
+                .subscribed
+                .zip(payloads.first())
+                .map(|(subscribed, object)| object.time - subscribed),
+        }
+    }
+}
+
+/// The delay between objects being created by one trace and parsed by another.
+#[derive(Debug, Serialize)]
+pub struct Delay {
+    pub track: String,
+
+    /// The names of the sending and receiving traces.
+    pub sender: String,
+    pub receiver: String,
+
+    pub delay: Summary,
+}
+
+/// Match objects sent in one trace with the same objects received in another, by track name, group and object ID.
+///
+/// Times are compared using the reference time of each trace, so clocks must be synchronized.
+pub fn delays(traces: &[(&Trace, Vec<Track>)]) -> Vec<Delay> {
+    let mut out = Vec::new();
+
+    for (sender, sent) in traces {
+        for (receiver, received) in traces {
+            if std::ptr::eq(*sender, *receiver) {
+                continue;
+            }
+
+            for track in sent.iter().filter(|t| t.direction == Direction::Sent) {
+                let created: HashMap<(u64, u64), f64> = track
+                    .objects
+                    .iter()
+                    .filter_map(|o| Some(((o.group_id, o.object_id), sender.absolute(o.time)?)))
+                    .collect();
+
+                let delays: Vec<f64> = received
+                    .iter()
+                    .filter(|t| t.direction == Direction::Received && t.name == track.name)
+                    .flat_map(|t| &t.objects)
+                    .filter_map(|o| {
+                        let created = created.get(&(o.group_id, o.object_id))?;
+                        Some(receiver.absolute(o.time)? - created)
+                    })
+                    .collect();
+
+                if let Some(delay) = Summary::new(delays) {
+                    out.push(Delay {
+                        track: track.name.clone(),
+                        sender: sender.name(),
+                        receiver: receiver.name(),
+                        delay,
+                    });
+                }
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::timeline;
+
+    fn trace(vantage_point: &str, reference_time: f64, events: &[serde_json::Value]) -> Trace {
+        let header = json!({
+            "qlog_version": "0.3",
+            "title": "test",
+            "trace": {
+                "vantage_point": { "type": vantage_point },
+                "common_fields": { "reference_time": reference_time },
+            },
+        });
+
+        let mut contents = header.to_string();
+        for event in events {
+            contents.push('\n');
+            contents.push_str(&event.to_string());
+        }
+
+        Trace::parse(Path::new(vantage_point), &contents).unwrap()
+    }
+
+    fn control(
+        time: f64,
+        name: &str,
+        message_type: &str,
+        fields: serde_json::Value,
+    ) -> serde_json::Value {
+        let mut data = json!({
+            "event_type": name,
+            "stream_id": 0,
+            "message_type": message_type,
+        });
+        data.as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+
+        json!({ "time": time, "name": format!("moqt:{name}"), "data": data })
+    }
+
+    fn object(time: f64, name: &str, group_id: u64, object_id: u64) -> serde_json::Value {
+        json!({
+            "time": time,
+            "name": format!("moqt:{name}"),
+            "data": {
+                "event_type": name,
+                "stream_id": 2,
+                "group_id": group_id,
+                "subgroup_id": 0,
+                "object_id": object_id,
+                "object_payload_length": 100,
+            },
+        })
+    }
+
+    fn header(time: f64, name: &str) -> serde_json::Value {
+        json!({
+            "time": time,
+            "name": format!("moqt:{name}"),
+            "data": { "event_type": name, "stream_id": 2, "track_alias": 7, "group_id": 0 },
+        })
+    }
+
+    fn subscriber() -> Trace {
+        let subscribe = json!({ "request_id": 0, "track_namespace": "clock", "track_name": "now" });
+        trace(
+            "client",
+            1000.0,
+            &[
+                control(10.0, "control_message_created", "subscribe", subscribe),
+                control(
+                    20.0,
+                    "control_message_parsed",
+                    "subscribe_ok",
+                    json!({ "request_id": 0, "track_alias": 7 }),
+                ),
+                header(25.0, "subgroup_header_parsed"),
+                object(30.0, "subgroup_object_parsed", 0, 1),
+                object(40.0, "subgroup_object_parsed", 0, 2),
+                object(52.0, "subgroup_object_parsed", 0, 4),
+                object(60.0, "subgroup_object_parsed", 1, 0),
+                object(70.0, "subgroup_object_parsed", 1, 1),
+            ],
+        )
+    }
+
+    #[test]
+    fn track_stats() {
+        let trace = subscriber();
+        let tracks = timeline::tracks(&trace);
+        assert_eq!(tracks.len(), 1);
+
+        let stats = TrackStats::new(&tracks[0]);
+        assert_eq!(stats.name, "clock/now");
+        assert_eq!(stats.direction, Direction::Received);
+        assert_eq!(stats.objects, 5);
+        assert_eq!(stats.bytes, 500);
+        assert_eq!(stats.groups, 2);
+        assert_eq!(stats.complete_groups, 1);
+        // The first group started at object 1, so only object 3 is missing.
+        assert_eq!(stats.missing, BTreeMap::from([(0, vec![3])]));
+        assert_eq!(stats.first_object, Some(20.0));
+
+        // Intervals of 10, 12, 8 and 10ms.
+        let interval = stats.interval.unwrap();
+        assert_eq!(interval.max, 12.0);
+        assert_eq!(stats.jitter, Some(8.0 / 3.0));
+    }
+
+    #[test]
+    fn end_to_end() {
+        let subscribe = json!({ "request_id": 1, "track_namespace": "clock", "track_name": "now" });
+        let publisher = trace(
+            "server",
+            1005.0,
+            &[
+                control(0.0, "control_message_parsed", "subscribe", subscribe),
+                control(
+                    1.0,
+                    "control_message_created",
+                    "subscribe_ok",
+                    json!({ "request_id": 1, "track_alias": 7 }),
+                ),
+                header(2.0, "subgroup_header_created"),
+                object(20.0, "subgroup_object_created", 0, 1),
+                object(30.0, "subgroup_object_created", 0, 2),
+            ],
+        );
+        let subscriber = subscriber();
+
+        let traces = [
+            (&publisher, timeline::tracks(&publisher)),
+            (&subscriber, timeline::tracks(&subscriber)),
+        ];
+        let delays = delays(&traces);
+        assert_eq!(delays.len(), 1);
+
+        // Created at 1025 and 1035, parsed at 1030 and 1040.
+        let delay = &delays[0];
+        assert_eq!(delay.track, "clock/now");
+        assert_eq!(delay.delay.count, 2);
+        assert_eq!(delay.delay.mean, 5.0);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use moq_transport::mlog::EventData;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::trace::Trace;
+
+/// Whether objects were sent or received by the endpoint that wrote the trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sent => "sent",
+            Self::Received => "received",
+        }
+    }
+}
+
+/// An object sent or received on a stream or datagram.
+#[derive(Debug, Clone)]
+pub struct Object {
+    pub time: f64,
+    pub group_id: u64,
+    pub object_id: u64,
+    pub size: u64,
+
+    /// The object status, ex. `EndOfGroup`, or None for a normal object.
+    pub status: Option<String>,
+}
+
+/// The objects for a single track in one direction, in the order they were logged.
+#[derive(Debug, Clone)]
+pub struct Track {
+    /// The full track name, ex. `clock/now`, or the alias if the subscription wasn't logged.
+    pub name: String,
+    pub alias: u64,
+    pub direction: Direction,
+
+    /// When the SUBSCRIBE or PUBLISH was sent or received.
+    pub subscribed: Option<f64>,
+
+    pub objects: Vec<Object>,
+}
+
+// A SUBSCRIBE or PUBLISH, keyed by request ID.
+struct Subscription {
+    // The full track name.
+    name: String,
+    time: f64,
+    direction: Direction,
+}
+
+/// Reconstruct the per-track timelines from a trace.
+///
+/// Objects on subgroup streams are associated with a track using the stream ID of the header,
+/// and track aliases are resolved to names using the SUBSCRIBE/SUBSCRIBE_OK or PUBLISH messages.
+pub fn tracks(trace: &Trace) -> Vec<Track> {
+    let mut timeline = Timeline::default();
+
+    for event in &trace.events {
+        let time = event.time;
+
+        match &event.data {
+            EventData::ControlMessageCreated(msg) => {
+                timeline.control(time, Direction::Sent, &msg.message_type, &msg.message)
+            }
+            EventData::ControlMessageParsed(msg) => {
+                timeline.control(time, Direction::Received, &msg.message_type, &msg.message)
+            }
+            EventData::SubgroupHeaderCreated(header) => {
+                timeline.header(header.stream_id, Direction::Sent, &header.header)
+            }
+            EventData::SubgroupHeaderParsed(header) => {
+                timeline.header(header.stream_id, Direction::Received, &header.header)
+            }
+            EventData::SubgroupObjectCreated(object) => {
+                timeline.stream_object(time, object.stream_id, &object.object)
+            }
+            EventData::SubgroupObjectParsed(object) => {
+                timeline.stream_object(time, object.stream_id, &object.object)
+            }
+            EventData::ObjectDatagramCreated(datagram) => {
+                timeline.datagram(time, Direction::Sent, &datagram.object)
+            }
+            EventData::ObjectDatagramStatusCreated(datagram) => {
+                timeline.datagram(time, Direction::Sent, &datagram.object)
+            }
+            EventData::ObjectDatagramParsed(datagram) => {
+                timeline.datagram(time, Direction::Received, &datagram.object)
+            }
+            EventData::ObjectDatagramStatusParsed(datagram) => {
+                timeline.datagram(time, Direction::Received, &datagram.object)
+            }
+            _ => {}
+        }
+    }
+
+    timeline.finish()
+}
+
+#[derive(Default)]
+struct Timeline {
+    subscriptions: HashMap<u64, Subscription>,
+
+    // The request ID for each track alias, in each direction.
+    aliases: HashMap<(Direction, u64), u64>,
+
+    // The track alias for each subgroup stream.
+    streams: HashMap<u64, (Direction, u64)>,
+
+    objects: HashMap<(Direction, u64), Vec<Object>>,
+}
+
+impl Timeline {
+    // The direction is whether the message was sent, not the direction of the objects.
+    fn control(&mut self, time: f64, sent: Direction, message_type: &str, msg: &Value) {
+        let Some(request_id) = msg["request_id"].as_u64() else {
+            return;
+        };
+
+        match message_type {
+            // Objects flow in the opposite direction of the SUBSCRIBE.
+            "subscribe" => {
+                let direction = match sent {
+                    Direction::Sent => Direction::Received,
+                    Direction::Received => Direction::Sent,
+                };
+                self.subscribe(request_id, time, direction, msg);
+            }
+            "subscribe_ok" => {
+                if let (Some(subscription), Some(alias)) = (
+                    self.subscriptions.get(&request_id),
+                    msg["track_alias"].as_u64(),
+                ) {
+                    self.aliases
+                        .insert((subscription.direction, alias), request_id);
+                }
+            }
+            // Objects flow in the same direction as the PUBLISH.
+            "publish" => {
+                self.subscribe(request_id, time, sent, msg);
+                if let Some(alias) = msg["track_alias"].as_u64() {
+                    self.aliases.insert((sent, alias), request_id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn subscribe(&mut self, request_id: u64, time: f64, direction: Direction, msg: &Value) {
+        let namespace = msg["track_namespace"].as_str().unwrap_or_default();
+        let name = msg["track_name"].as_str().unwrap_or_default();
+
+        self.subscriptions.insert(
+            request_id,
+            Subscription {
+                name: format!("{namespace}/{name}"),
+                time,
+                direction,
+            },
+        );
+    }
+
+    fn header(&mut self, stream_id: u64, direction: Direction, header: &Value) {
+        if let Some(alias) = header["track_alias"].as_u64() {
+            self.streams.insert(stream_id, (direction, alias));
+        }
+    }
+
+    fn stream_object(&mut self, time: f64, stream_id: u64, object: &Value) {
+        match self.streams.get(&stream_id) {
+            Some(&track) => self.object(time, track, object, "object_payload_length"),
+            None => log::debug!("object on unknown stream: {}", stream_id),
+        }
+    }
+
+    fn datagram(&mut self, time: f64, direction: Direction, datagram: &Value) {
+        if let Some(alias) = datagram["track_alias"].as_u64() {
+            self.object(time, (direction, alias), datagram, "payload_length");
+        }
+    }
+
+    fn object(&mut self, time: f64, track: (Direction, u64), object: &Value, size: &str) {
+        let (Some(group_id), Some(object_id)) =
+            (object["group_id"].as_u64(), object["object_id"].as_u64())
+        else {
+            return;
+        };
+
+        self.objects.entry(track).or_default().push(Object {
+            time,
+            group_id,
+            object_id,
+            size: object[size].as_u64().unwrap_or_default(),
+            status: object["object_status"]
+                .as_str()
+                .filter(|status| *status != "NormalObject")
+                .map(str::to_string),
+        });
+    }
+
+    // Resolve the track names, now that every SUBSCRIBE_OK has been seen.
+    fn finish(self) -> Vec<Track> {
+        let mut tracks: Vec<_> = self
+            .objects
+            .into_iter()
+            .map(|((direction, alias), objects)| {
+                let subscription = self
+                    .aliases
+                    .get(&(direction, alias))
+                    .and_then(|request_id| self.subscriptions.get(request_id));
+
+                Track {
+                    name: match subscription {
+                        Some(subscription) => subscription.name.clone(),
+                        None => format!("alias {alias}"),
+                    },
+                    alias,
+                    direction,
+                    subscribed: subscription.map(|subscription| subscription.time),
+                    objects,
+                }
+            })
+            .collect();
+
+        tracks
+            .sort_by(|a, b| (&a.name, a.direction.as_str()).cmp(&(&b.name, b.direction.as_str())));
+        tracks
+    }
+}
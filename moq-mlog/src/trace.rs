@@ -0,0 +1,113 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use moq_transport::mlog::{Event, VantagePoint};
+
+/// The events from a single connection, possibly spread across rotated files.
+pub struct Trace {
+    /// The first file, used to identify the trace.
+    pub path: PathBuf,
+
+    /// The application that wrote the log, ex. `moq-relay`.
+    pub title: String,
+    pub vantage_point: Option<VantagePoint>,
+
+    /// The wall clock time that event times are relative to, in milliseconds since the UNIX epoch.
+    /// Older logs don't include this, so they can't be compared with other traces.
+    pub reference_time: Option<f64>,
+
+    pub events: Vec<Event>,
+
+    /// The number of records that couldn't be parsed.
+    pub skipped: usize,
+}
+
+impl Trace {
+    /// Parse a JSON-SEQ mlog file, starting with the header.
+    pub fn parse(path: &Path, contents: &str) -> anyhow::Result<Self> {
+        let mut lines = contents.lines().filter(|line| !line.trim().is_empty());
+
+        let header = lines.next().context("empty mlog")?;
+        let header: serde_json::Value = serde_json::from_str(header.trim_start_matches('\x1e'))
+            .context("failed to parse mlog header")?;
+        anyhow::ensure!(header.get("qlog_version").is_some(), "missing mlog header");
+
+        let trace = &header["trace"];
+        let mut out = Self {
+            path: path.to_path_buf(),
+            title: header["title"].as_str().unwrap_or_default().to_string(),
+            vantage_point: serde_json::from_value(trace["vantage_point"]["type"].clone()).ok(),
+            reference_time: trace["common_fields"]["reference_time"].as_f64(),
+            events: Vec::new(),
+            skipped: 0,
+        };
+
+        for line in lines {
+            match serde_json::from_str(line.trim_start_matches('\x1e')) {
+                Ok(event) => out.events.push(event),
+                Err(err) => {
+                    log::debug!("skipping record in {}: {}", path.display(), err);
+                    out.skipped += 1;
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Load the given files, merging files rotated from the same connection into a single trace.
+    pub fn load(paths: &[PathBuf]) -> anyhow::Result<Vec<Self>> {
+        let mut traces: Vec<Self> = Vec::new();
+
+        for path in paths {
+            let contents = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let trace = Self::parse(path, &contents)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+
+            // Rotated files repeat the header, including the reference time.
+            match traces.iter_mut().find(|t| t.same_connection(&trace)) {
+                Some(existing) => existing.merge(trace),
+                None => traces.push(trace),
+            }
+        }
+
+        for trace in &mut traces {
+            trace.events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+
+        Ok(traces)
+    }
+
+    fn same_connection(&self, other: &Self) -> bool {
+        self.reference_time.is_some()
+            && self.reference_time == other.reference_time
+            && self.title == other.title
+            && self.vantage_point == other.vantage_point
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.events.extend(other.events);
+        self.skipped += other.skipped;
+    }
+
+    /// A short description of the trace, ex. `moq-relay (server) abc_server.mlog`.
+    pub fn name(&self) -> String {
+        let vantage_point = match self.vantage_point {
+            Some(vantage_point) => vantage_point.as_str(),
+            None => "unknown",
+        };
+        let file = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+
+        format!("{} ({}) {}", self.title, vantage_point, file)
+    }
+
+    /// Convert a time relative to the trace into wall clock time, if known.
+    pub fn absolute(&self, time: f64) -> Option<f64> {
+        self.reference_time.map(|reference| reference + time)
+    }
+}
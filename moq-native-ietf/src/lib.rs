@@ -1,2 +1,3 @@
+pub mod mlog;
 pub mod quic;
 pub mod tls;
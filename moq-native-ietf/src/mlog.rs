@@ -0,0 +1,112 @@
+use std::{path::PathBuf, sync::OnceLock, time};
+
+use anyhow::Context;
+use clap::Parser;
+use moq_transport::mlog::{
+    FileSink, Header, MlogThread, MlogWriter, Rotation, Sampler, VantagePoint,
+};
+
+// The number of events buffered before they're dropped, if the disk can't keep up.
+const CHANNEL_CAPACITY: usize = 16 * 1024;
+
+// A single thread writes the mlogs for every connection.
+fn thread() -> std::io::Result<&'static MlogThread> {
+    static THREAD: OnceLock<MlogThread> = OnceLock::new();
+
+    if let Some(thread) = THREAD.get() {
+        return Ok(thread);
+    }
+
+    // If two connections race, the losing thread exits as soon as its handle is dropped.
+    let thread = MlogThread::spawn(CHANNEL_CAPACITY)?;
+    Ok(THREAD.get_or_init(|| thread))
+}
+
+#[derive(Parser, Clone, Default)]
+pub struct Args {
+    /// Directory to write mlog files (one per connection)
+    #[arg(long)]
+    pub mlog_dir: Option<PathBuf>,
+
+    /// Start a new mlog file once the current one reaches this many bytes
+    #[arg(long, requires = "mlog_dir")]
+    pub mlog_max_size: Option<u64>,
+
+    /// Start a new mlog file once the current one is this many seconds old
+    #[arg(long, requires = "mlog_dir")]
+    pub mlog_max_age: Option<u64>,
+
+    /// Only log one in every N events of a type, ex. `moqt:subgroup_object_parsed=100`
+    /// A rate of 0 disables the event type. May be repeated.
+    #[arg(long, value_parser = parse_sample, requires = "mlog_dir")]
+    pub mlog_sample: Vec<(String, u64)>,
+}
+
+fn parse_sample(s: &str) -> anyhow::Result<(String, u64)> {
+    let (name, every) = s.split_once('=').context("expected NAME=N")?;
+    let every = every.parse().context("invalid sample rate")?;
+    Ok((name.to_string(), every))
+}
+
+impl Args {
+    /// Make sure the mlog directory exists, if configured.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(dir) = &self.mlog_dir {
+            anyhow::ensure!(
+                dir.exists(),
+                "mlog directory does not exist: {}",
+                dir.display()
+            );
+            anyhow::ensure!(
+                dir.is_dir(),
+                "mlog path is not a directory: {}",
+                dir.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Create the mlog for a connection, if enabled, named `{cid}_{vantage_point}.mlog`.
+    ///
+    /// Events are sampled and then queued to a background thread shared by all connections.
+    pub fn create(
+        &self,
+        connection_id: &str,
+        title: &str,
+        vantage_point: VantagePoint,
+    ) -> anyhow::Result<Option<MlogWriter>> {
+        let dir = match &self.mlog_dir {
+            Some(dir) => dir,
+            None => return Ok(None),
+        };
+
+        let path = dir.join(format!("{}_{}.mlog", connection_id, vantage_point.as_str()));
+        let header = Header::new(title, vantage_point);
+        let rotation = Rotation {
+            max_size: self.mlog_max_size,
+            max_age: self.mlog_max_age.map(time::Duration::from_secs),
+        };
+
+        let file = FileSink::create(&path, header, rotation)
+            .with_context(|| format!("failed to create mlog: {}", path.display()))?;
+
+        let sink = thread()?.open(file)?;
+
+        // Sample before queuing, so dropped events don't fill the channel.
+        let sampler = self
+            .mlog_sample
+            .iter()
+            .fold(Sampler::default(), |sampler, (name, every)| {
+                sampler.rate(name, *every)
+            });
+
+        log::debug!(
+            "mlog enabled: cid={} path={}",
+            connection_id,
+            path.display()
+        );
+
+        Ok(Some(MlogWriter::new(sink).sampler(sampler)))
+    }
+}
@@ -16,6 +16,7 @@ use crate::tls;
 use futures::future::BoxFuture;
 use futures::stream::{FuturesUnordered, StreamExt};
 use futures::FutureExt;
+use moq_transport::session::Transport;
 
 /// Build a TransportConfig with our standard settings
 ///
@@ -136,13 +137,15 @@ impl Endpoint {
 
 pub struct Server {
     quic: quinn::Endpoint,
-    accept: FuturesUnordered<BoxFuture<'static, anyhow::Result<(web_transport::Session, String)>>>,
+    accept: FuturesUnordered<
+        BoxFuture<'static, anyhow::Result<(web_transport::Session, String, Transport)>>,
+    >,
     qlog_dir: Option<Arc<PathBuf>>,
     base_server_config: Arc<quinn::ServerConfig>,
 }
 
 impl Server {
-    pub async fn accept(&mut self) -> Option<(web_transport::Session, String)> {
+    pub async fn accept(&mut self) -> Option<(web_transport::Session, String, Transport)> {
         loop {
             tokio::select! {
                 res = self.quic.accept() => {
@@ -165,7 +168,7 @@ impl Server {
         conn: quinn::Incoming,
         qlog_dir: Option<Arc<PathBuf>>,
         base_server_config: Arc<quinn::ServerConfig>,
-    ) -> anyhow::Result<(web_transport::Session, String)> {
+    ) -> anyhow::Result<(web_transport::Session, String, Transport)> {
         // Capture the original destination connection ID BEFORE accepting
         // This is the actual QUIC CID that can be used for qlog/mlog correlation
         let orig_dst_cid = conn.orig_dst_cid();
@@ -234,7 +237,7 @@ impl Server {
             server_name,
         );
 
-        let session = match alpn.as_bytes() {
+        let (session, transport) = match alpn.as_bytes() {
             web_transport_quinn::ALPN => {
                 // Wait for the CONNECT request.
                 let request = web_transport_quinn::accept(conn)
@@ -242,17 +245,19 @@ impl Server {
                     .context("failed to receive WebTransport request")?;
 
                 // Accept the CONNECT request.
-                request
+                let session = request
                     .ok()
                     .await
-                    .context("failed to respond to WebTransport request")?
+                    .context("failed to respond to WebTransport request")?;
+
+                (session, Transport::WebTransport)
             }
             // A bit of a hack to pretend like we're a WebTransport session
-            moq_transport::setup::ALPN => conn.into(),
+            moq_transport::setup::ALPN => (conn.into(), Transport::Quic),
             _ => anyhow::bail!("unsupported ALPN: {}", alpn),
         };
 
-        Ok((session.into(), connection_id_hex))
+        Ok((session.into(), connection_id_hex, transport))
     }
 
     pub fn local_addr(&self) -> anyhow::Result<net::SocketAddr> {
@@ -270,7 +275,10 @@ pub struct Client {
 }
 
 impl Client {
-    pub async fn connect(&self, url: &Url) -> anyhow::Result<(web_transport::Session, String)> {
+    pub async fn connect(
+        &self,
+        url: &Url,
+    ) -> anyhow::Result<(web_transport::Session, String, Transport)> {
         let mut config = self.config.clone();
 
         // TODO support connecting to both ALPNs at the same time
@@ -320,12 +328,15 @@ impl Client {
             .context("CID not captured")?
             .to_string();
 
-        let session = match url.scheme() {
-            "https" => web_transport_quinn::connect_with(connection, url).await?,
-            "moqt" => connection.into(),
+        let (session, transport) = match url.scheme() {
+            "https" => (
+                web_transport_quinn::connect_with(connection, url).await?,
+                Transport::WebTransport,
+            ),
+            "moqt" => (connection.into(), Transport::Quic),
             _ => unreachable!(),
         };
 
-        Ok((session.into(), connection_id_hex))
+        Ok((session.into(), connection_id_hex, transport))
     }
 }
@@ -0,0 +1,380 @@
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+// Refuse atoms larger than this, instead of buffering forever.
+const MAX_SIZE: u64 = 1 << 30;
+
+/// A complete ISO-BMFF box, including the header.
+#[derive(Debug, Clone)]
+pub struct Atom {
+    /// The box type, ex. `moof`.
+    pub name: [u8; 4],
+
+    /// The offset of the box within the stream.
+    pub offset: u64,
+
+    /// The entire box, including the header.
+    pub raw: Bytes,
+}
+
+impl Atom {
+    /// The box type as a string, for logging.
+    pub fn kind(&self) -> String {
+        kind(&self.name)
+    }
+}
+
+/// The result of reading from the buffer.
+#[derive(Debug)]
+pub enum Next {
+    Atom(Atom),
+
+    /// At least this many more bytes are needed to read the next atom.
+    Needed(usize),
+
+    /// The atom extends to the end of the stream, see [AtomReader::finish].
+    UntilEof,
+}
+
+/// Incrementally reads boxes from a stream, regardless of how it's split into chunks.
+///
+/// Bytes are only consumed from the buffer once they're part of the header or a complete box,
+/// except for boxes that extend to the end of the stream, which are buffered until [AtomReader::finish].
+#[derive(Debug)]
+pub struct AtomReader {
+    // The partial header, or the box that extends to the end of the stream.
+    pending: BytesMut,
+
+    // The offset of the pending box within the stream.
+    offset: u64,
+
+    // The maximum size of any box, including those that extend to the end of the stream.
+    max_size: u64,
+}
+
+impl Default for AtomReader {
+    fn default() -> Self {
+        Self {
+            pending: BytesMut::new(),
+            offset: 0,
+            max_size: MAX_SIZE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    name: [u8; 4],
+
+    // The size of the entire box, or None if it extends to the end of the stream.
+    size: Option<u64>,
+}
+
+impl AtomReader {
+    /// Read the next complete box from the buffer.
+    pub fn next<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<Next> {
+        let header = match self.header(buf)? {
+            Ok(header) => header,
+            Err(needed) => return Ok(Next::Needed(needed)),
+        };
+
+        let size = match header.size {
+            Some(size) => size as usize,
+            None => {
+                // There's no way to know where the box ends, so buffer everything.
+                self.take_until_eof(buf, header.name)?;
+                return Ok(Next::UntilEof);
+            }
+        };
+
+        let needed = size - self.pending.len();
+        if buf.remaining() < needed {
+            return Ok(Next::Needed(needed - buf.remaining()));
+        }
+
+        // Avoid a copy when none of the box was buffered.
+        let raw = match self.pending.is_empty() {
+            true => buf.copy_to_bytes(size),
+            false => {
+                self.take(buf, needed);
+                self.pending.split().freeze()
+            }
+        };
+
+        Ok(Next::Atom(self.atom(header.name, raw)))
+    }
+
+    /// Called at the end of the stream, returning the box that extends to the end of the stream, if any.
+    ///
+    /// Returns an error if the stream ended in the middle of a box.
+    pub fn finish<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<Option<Atom>> {
+        if self.pending.is_empty() && !buf.has_remaining() {
+            return Ok(None);
+        }
+
+        let header = self.header(buf)?;
+        if let Ok(Header { name, size: None }) = header {
+            self.take_until_eof(buf, name)?;
+        } else {
+            self.take(buf, buf.remaining());
+        }
+
+        let offset = self.offset;
+        let received = self.pending.len();
+
+        match header {
+            Ok(Header { name, size: None }) => {
+                let raw = self.pending.split().freeze();
+                Ok(Some(self.atom(name, raw)))
+            }
+            Ok(Header {
+                name,
+                size: Some(size),
+            }) => anyhow::bail!(
+                "truncated {} atom at offset {offset}: expected {size} bytes, received {received}",
+                kind(&name)
+            ),
+            Err(_) => {
+                anyhow::bail!("truncated atom header at offset {offset}: received {received} bytes")
+            }
+        }
+    }
+
+    // Parse the header, returning the number of bytes needed if it's incomplete.
+    fn header<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<Result<Header, usize>> {
+        // Parse the header in place when it's contiguous, so the box isn't copied.
+        if self.pending.is_empty() {
+            if let Ok(header) = parse_header(buf.chunk(), self.offset, self.max_size)? {
+                return Ok(Ok(header));
+            }
+        }
+
+        // Otherwise buffer the header, which may be split across chunks.
+        let mut len = 8;
+        loop {
+            if let Err(needed) = self.fill(buf, len) {
+                return Ok(Err(needed));
+            }
+
+            match parse_header(&self.pending, self.offset, self.max_size)? {
+                Ok(header) => return Ok(Ok(header)),
+                Err(required) => len = required,
+            }
+        }
+    }
+
+    // Buffer until there are at least `len` bytes pending, returning the number of bytes still needed.
+    fn fill<B: Buf>(&mut self, buf: &mut B, len: usize) -> Result<(), usize> {
+        let missing = len.saturating_sub(self.pending.len());
+        self.take(buf, missing.min(buf.remaining()));
+
+        match len.saturating_sub(self.pending.len()) {
+            0 => Ok(()),
+            needed => Err(needed),
+        }
+    }
+
+    // Buffer the rest of a box that extends to the end of the stream, as long as it's not too large.
+    fn take_until_eof<B: Buf>(&mut self, buf: &mut B, name: [u8; 4]) -> anyhow::Result<()> {
+        let size = self.pending.len() as u64 + buf.remaining() as u64;
+        anyhow::ensure!(
+            size <= self.max_size,
+            "invalid {} atom at offset {}: size {size} exceeds the maximum of {}",
+            kind(&name),
+            self.offset,
+            self.max_size
+        );
+
+        self.take(buf, buf.remaining());
+        Ok(())
+    }
+
+    // Move bytes from the buffer to the pending box, across any number of chunks.
+    fn take<B: Buf>(&mut self, buf: &mut B, len: usize) {
+        self.pending.put(buf.take(len));
+    }
+
+    fn atom(&mut self, name: [u8; 4], raw: Bytes) -> Atom {
+        let offset = self.offset;
+        self.offset += raw.len() as u64;
+
+        Atom { name, offset, raw }
+    }
+}
+
+// Parse the header at the start of the buffer, returning the required length if it's too short.
+fn parse_header(buf: &[u8], offset: u64, max_size: u64) -> anyhow::Result<Result<Header, usize>> {
+    if buf.len() < 8 {
+        return Ok(Err(8));
+    }
+
+    let name: [u8; 4] = buf[4..8].try_into().unwrap();
+    let size = u32::from_be_bytes(buf[..4].try_into().unwrap());
+
+    let (len, size) = match size {
+        0 => (8, None),
+        1 => match buf.get(8..16) {
+            Some(size) => (16, Some(u64::from_be_bytes(size.try_into().unwrap()))),
+            None => return Ok(Err(16)),
+        },
+        size => (8, Some(size as u64)),
+    };
+
+    if let Some(size) = size {
+        let kind = kind(&name);
+        anyhow::ensure!(
+            size >= len as u64,
+            "invalid {kind} atom at offset {offset}: size {size} is smaller than the header"
+        );
+        anyhow::ensure!(
+            size <= max_size,
+            "invalid {kind} atom at offset {offset}: size {size} exceeds the maximum of {max_size}"
+        );
+    }
+
+    Ok(Ok(Header { name, size }))
+}
+
+fn kind(name: &[u8; 4]) -> String {
+    String::from_utf8_lossy(name).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn atom(name: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+        out.extend_from_slice(name);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    fn names(atoms: &[Atom]) -> Vec<String> {
+        atoms.iter().map(Atom::kind).collect()
+    }
+
+    #[test]
+    fn split_chunks() {
+        let mut stream = atom(b"styp", b"msdh");
+        // A 64-bit size.
+        stream.extend_from_slice(&1u32.to_be_bytes());
+        stream.extend_from_slice(b"moof");
+        stream.extend_from_slice(&20u64.to_be_bytes());
+        stream.extend_from_slice(b"abcd");
+        stream.extend(atom(b"mdat", &[0; 32]));
+
+        // Every chunk is a single byte, so the headers are never contiguous.
+        let chunks: Vec<_> = stream
+            .iter()
+            .map(|b| Bytes::copy_from_slice(&[*b]))
+            .collect();
+        let mut reader = AtomReader::default();
+        let mut atoms = Vec::new();
+        let mut buf: Box<dyn Buf> = Box::new(Bytes::new());
+
+        for chunk in chunks {
+            buf = Box::new(buf.chain(chunk));
+            loop {
+                match reader.next(&mut buf).unwrap() {
+                    Next::Atom(atom) => atoms.push(atom),
+                    Next::Needed(needed) => {
+                        assert!(needed > 0);
+                        break;
+                    }
+                    Next::UntilEof => unreachable!(),
+                }
+            }
+        }
+
+        assert_eq!(names(&atoms), ["styp", "moof", "mdat"]);
+        assert_eq!(atoms[1].offset, 12);
+        assert_eq!(atoms[1].raw.len(), 20);
+        assert_eq!(atoms[2].raw.len(), 40);
+        assert!(reader.finish(&mut buf).unwrap().is_none());
+    }
+
+    #[test]
+    fn needed_bytes() {
+        let stream = atom(b"mdat", &[0; 100]);
+        let mut reader = AtomReader::default();
+
+        let mut buf = Bytes::copy_from_slice(&stream[..4]);
+        assert!(matches!(reader.next(&mut buf).unwrap(), Next::Needed(4)));
+
+        let mut buf = Bytes::copy_from_slice(&stream[4..50]);
+        assert!(matches!(reader.next(&mut buf).unwrap(), Next::Needed(58)));
+        assert_eq!(buf.len(), 42);
+
+        // The stream ends in the middle of the box.
+        let err = reader.finish(&mut buf).unwrap_err();
+        assert!(err.to_string().contains("truncated mdat atom"), "{err}");
+    }
+
+    #[test]
+    fn until_eof() {
+        let mut stream = atom(b"prft", &[0; 16]);
+        stream.extend_from_slice(&0u32.to_be_bytes());
+        stream.extend_from_slice(b"mdat");
+        stream.extend_from_slice(&[1; 10]);
+
+        let mut reader = AtomReader::default();
+        let mut buf = Bytes::from(stream);
+        assert!(
+            matches!(reader.next(&mut buf).unwrap(), Next::Atom(atom) if &atom.name == b"prft")
+        );
+        assert!(matches!(reader.next(&mut buf).unwrap(), Next::UntilEof));
+
+        let atom = reader.finish(&mut buf).unwrap().unwrap();
+        assert_eq!(atom.kind(), "mdat");
+        assert_eq!(atom.offset, 24);
+        assert_eq!(atom.raw.len(), 18);
+    }
+
+    #[test]
+    fn until_eof_limit() {
+        let mut reader = AtomReader {
+            max_size: 32,
+            ..Default::default()
+        };
+
+        let mut stream = 0u32.to_be_bytes().to_vec();
+        stream.extend_from_slice(b"mdat");
+        stream.extend_from_slice(&[1; 16]);
+        let mut buf = Bytes::from(stream);
+        assert!(matches!(reader.next(&mut buf).unwrap(), Next::UntilEof));
+
+        // The pending box grows past the limit as more of the stream arrives.
+        let mut buf = Bytes::from_static(&[1; 16]);
+        let err = reader.next(&mut buf).unwrap_err();
+        assert!(
+            err.to_string().contains("exceeds the maximum of 32"),
+            "{err}"
+        );
+
+        // The same applies to whatever is left at the end of the stream.
+        let mut reader = AtomReader {
+            max_size: 32,
+            ..Default::default()
+        };
+        let mut stream = 0u32.to_be_bytes().to_vec();
+        stream.extend_from_slice(b"mdat");
+        stream.extend_from_slice(&[1; 32]);
+        let err = reader.finish(&mut Bytes::from(stream)).unwrap_err();
+        assert!(
+            err.to_string().contains("exceeds the maximum of 32"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn invalid_size() {
+        let mut reader = AtomReader::default();
+        let mut buf = Bytes::from_static(&[0, 0, 0, 4, b'f', b'r', b'e', b'e']);
+        let err = reader.next(&mut buf).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid free atom at offset 0"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+use anyhow::Context;
+use moq_transport::serve::{SubgroupWriter, SubgroupsWriter, TrackWriter, TracksWriter};
+use std::sync::{Arc, Mutex};
+
+use crate::estimate::Estimate;
+use crate::Media;
+
+// The alternate groups used for the ABR ladder, so players can switch between renditions.
+const VIDEO_ALT_GROUP: u16 = 1;
+const AUDIO_ALT_GROUP: u16 = 2;
+
+// Start a new catalog group once this many delta updates have been appended, so new subscribers
+// don't have to apply every update since the start of the broadcast.
+const MAX_DELTAS: usize = 64;
+
+/// The tracks and catalog shared by every rendition of a broadcast.
+///
+/// Each rendition is an independent fMP4 input of the same content, ie: one rung of an ABR ladder.
+/// The catalog is published once every rendition has been set up (or has ended), grouping the video (and audio)
+/// tracks of each rendition into an alternate group.  Any later change, such as a rendition being
+/// added or removed or a measured frame rate and bitrate, is published as a delta update appended
+/// to the catalog group as a JSON Patch object.
+#[derive(Clone)]
+pub struct Broadcast {
+    state: Arc<Mutex<BroadcastState>>,
+}
+
+struct BroadcastState {
+    // The full broadcast of tracks
+    tracks: TracksWriter,
+
+    // The catalog track
+    catalog: SubgroupsWriter,
+
+    // The state of each rendition, including its catalog tracks once it has been set up.
+    renditions: Vec<Rendition>,
+
+    // The current catalog group and the catalog it currently describes, once published.
+    published: Option<Published>,
+}
+
+#[derive(Clone)]
+enum Rendition {
+    // The input hasn't been parsed up to the moov yet.
+    Pending,
+    Ready(Vec<moq_catalog::Track>),
+    // The input has ended, so the rendition isn't waited on.
+    Removed,
+}
+
+struct Published {
+    group: SubgroupWriter,
+    catalog: moq_catalog::Root,
+
+    // The number of delta updates in the group.
+    deltas: usize,
+}
+
+impl Broadcast {
+    /// Create a broadcast consisting of the given number of renditions.
+    pub fn new(mut tracks: TracksWriter, renditions: usize) -> anyhow::Result<Self> {
+        anyhow::ensure!(renditions > 0, "at least one rendition is required");
+
+        let catalog = tracks
+            .create(".catalog")
+            .context("broadcast closed")?
+            .subgroups()?;
+
+        let state = BroadcastState {
+            tracks,
+            catalog,
+            renditions: vec![Rendition::Pending; renditions],
+            published: None,
+        };
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+
+    /// Create the media parser for the rendition with the given index.
+    pub fn rendition(&self, index: usize) -> anyhow::Result<Media> {
+        let renditions = self.state.lock().unwrap().renditions.len();
+        anyhow::ensure!(index < renditions, "unknown rendition: {}", index);
+
+        Media::rendition(self.clone(), index)
+    }
+
+    /// The namespace of the broadcast, as advertised in the catalog.
+    pub fn namespace(&self) -> String {
+        self.state.lock().unwrap().tracks.namespace.to_utf8_path()
+    }
+
+    /// The name of the init track for the given rendition.
+    pub fn init_name(&self, index: usize) -> String {
+        format!("{index}.mp4")
+    }
+
+    /// The name of the media track for the given rendition and MP4 track ID.
+    /// The first rendition keeps the single input names, so existing subscribers keep working.
+    pub fn track_name(&self, index: usize, track_id: u32) -> String {
+        match index {
+            0 => format!("{track_id}.m4s"),
+            index => format!("{index}-{track_id}.m4s"),
+        }
+    }
+
+    /// Create a new track in the broadcast.
+    pub fn create(&self, name: &str) -> anyhow::Result<TrackWriter> {
+        self.state
+            .lock()
+            .unwrap()
+            .tracks
+            .create(name)
+            .context("broadcast closed")
+    }
+
+    /// Add the catalog tracks of a rendition, publishing the catalog once every rendition is known.
+    ///
+    /// A rendition that is added after the catalog was published, ie: a restarted input, is
+    /// published as a delta update instead.
+    pub fn publish(&self, index: usize, tracks: Vec<moq_catalog::Track>) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let rendition = state
+            .renditions
+            .get_mut(index)
+            .context("unknown rendition")?;
+        anyhow::ensure!(
+            !matches!(rendition, Rendition::Ready(_)),
+            "duplicate rendition: {}",
+            index
+        );
+        *rendition = Rendition::Ready(tracks);
+
+        state.flush_ready()
+    }
+
+    /// Remove the tracks of a rendition from the catalog, ie: once its input has ended.
+    ///
+    /// A rendition that ended before it was set up is no longer waited on, so the catalog is
+    /// published if every other rendition is ready.
+    pub fn remove(&self, index: usize) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let rendition = state
+            .renditions
+            .get_mut(index)
+            .context("unknown rendition")?;
+        if matches!(
+            std::mem::replace(rendition, Rendition::Removed),
+            Rendition::Removed
+        ) {
+            return Ok(());
+        }
+
+        state.flush_ready()
+    }
+
+    /// Update the measured properties of a track, publishing a catalog delta if they changed.
+    pub(crate) fn update(&self, name: &str, estimate: Estimate) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        let track = state
+            .renditions
+            .iter_mut()
+            .flat_map(Rendition::tracks_mut)
+            .find(|track| track.name == name)
+            .context("unknown track")?;
+
+        if estimate.framerate.is_some() {
+            track.selection_params.framerate = estimate.framerate;
+        }
+        if estimate.bitrate.is_some() {
+            track.selection_params.bitrate = estimate.bitrate;
+        }
+
+        state.flush()
+    }
+}
+
+impl Rendition {
+    fn tracks_mut(&mut self) -> &mut [moq_catalog::Track] {
+        match self {
+            Rendition::Ready(tracks) => tracks,
+            _ => &mut [],
+        }
+    }
+}
+
+impl BroadcastState {
+    // The tracks of every rendition, grouped into alternates.
+    fn catalog_tracks(&self) -> Vec<moq_catalog::Track> {
+        let mut tracks: Vec<_> = self
+            .renditions
+            .iter()
+            .filter_map(|rendition| match rendition {
+                Rendition::Ready(tracks) => Some(tracks),
+                _ => None,
+            })
+            .flatten()
+            .cloned()
+            .collect();
+
+        // Only group the tracks into alternates when there's something to switch between.
+        let video = tracks.iter().filter(|track| is_video(track)).count();
+        let audio = tracks.len() - video;
+
+        for track in &mut tracks {
+            track.alt_group = match is_video(track) {
+                true if video > 1 => Some(VIDEO_ALT_GROUP),
+                false if audio > 1 => Some(AUDIO_ALT_GROUP),
+                _ => None,
+            };
+        }
+
+        tracks
+    }
+
+    fn catalog(&self) -> moq_catalog::Root {
+        let mut tracks = self.catalog_tracks();
+
+        moq_catalog::Root {
+            version: 1,
+            streaming_format: 1,
+            streaming_format_version: "0.2".to_string(),
+            streaming_delta_updates: true,
+            common_track_fields: moq_catalog::CommonTrackFields::from_tracks(&mut tracks),
+            tracks,
+        }
+    }
+
+    // Publish the current catalog, unless it hasn't been published yet and a rendition is pending.
+    fn flush_ready(&mut self) -> anyhow::Result<()> {
+        let pending = self
+            .renditions
+            .iter()
+            .any(|rendition| matches!(rendition, Rendition::Pending));
+        if self.published.is_none() && pending {
+            return Ok(());
+        }
+
+        self.flush()
+    }
+
+    // Publish the current catalog, as a delta update if possible.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let catalog = self.catalog();
+        catalog.validate().context("invalid catalog")?;
+
+        let published = match self.published.as_mut() {
+            Some(published) if published.deltas < MAX_DELTAS => published,
+            _ => return self.publish_full(catalog),
+        };
+
+        let patch = published.catalog.delta(&catalog)?;
+        if patch.is_empty() {
+            return Ok(());
+        }
+
+        let patch_str = serde_json::to_string(&patch)?;
+        log::info!("catalog delta: {}", patch_str);
+
+        published.group.write(patch_str.into())?;
+        published.catalog = catalog;
+        published.deltas += 1;
+
+        Ok(())
+    }
+
+    // Start a new catalog group with the full catalog.
+    fn publish_full(&mut self, catalog: moq_catalog::Root) -> anyhow::Result<()> {
+        let catalog_str = serde_json::to_string_pretty(&catalog)?;
+
+        log::info!("catalog: {}", catalog_str);
+
+        // The catalog is the first object of the group, followed by any delta updates.
+        let mut group = self.catalog.append(0)?;
+        group.write(catalog_str.into())?;
+
+        self.published = Some(Published {
+            group,
+            catalog,
+            deltas: 0,
+        });
+
+        Ok(())
+    }
+}
+
+fn is_video(track: &moq_catalog::Track) -> bool {
+    track.selection_params.width.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use moq_transport::{coding::TrackNamespace, serve::Tracks};
+
+    fn video(name: &str) -> moq_catalog::Track {
+        moq_catalog::Track {
+            name: name.to_string(),
+            init_track: Some("0.mp4".to_string()),
+            packaging: Some(moq_catalog::TrackPackaging::Cmaf),
+            selection_params: moq_catalog::SelectionParam {
+                codec: Some("avc1.64001f".to_string()),
+                width: Some(1280),
+                height: Some(720),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn published(broadcast: &Broadcast) -> Option<Vec<String>> {
+        let state = broadcast.state.lock().unwrap();
+        let published = state.published.as_ref()?;
+        Some(
+            published
+                .catalog
+                .tracks
+                .iter()
+                .map(|track| track.name.clone())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn removed_before_ready() {
+        let (writer, _request, _reader) =
+            Tracks::new(TrackNamespace::from_utf8_path("test")).produce();
+        let broadcast = Broadcast::new(writer, 3).unwrap();
+
+        broadcast.publish(0, vec![video("1.m4s")]).unwrap();
+        assert_eq!(published(&broadcast), None);
+
+        // The input of a rendition ends before its moov, so it is no longer waited on.
+        broadcast.remove(1).unwrap();
+        assert_eq!(published(&broadcast), None);
+
+        broadcast.publish(2, vec![video("2-1.m4s")]).unwrap();
+        assert_eq!(
+            published(&broadcast),
+            Some(vec!["1.m4s".to_string(), "2-1.m4s".to_string()])
+        );
+    }
+
+    #[test]
+    fn last_pending_removed() {
+        let (writer, _request, _reader) =
+            Tracks::new(TrackNamespace::from_utf8_path("test")).produce();
+        let broadcast = Broadcast::new(writer, 2).unwrap();
+
+        broadcast.publish(0, vec![video("1.m4s")]).unwrap();
+        broadcast.remove(1).unwrap();
+        assert_eq!(published(&broadcast), Some(vec!["1.m4s".to_string()]));
+
+        // Removing it again doesn't change anything.
+        broadcast.remove(1).unwrap();
+        assert_eq!(published(&broadcast), Some(vec!["1.m4s".to_string()]));
+    }
+}
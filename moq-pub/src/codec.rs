@@ -0,0 +1,564 @@
+//! RFC 6381 codec strings for sample entries that the mp4 crate doesn't fully parse.
+//!
+//! The mp4 crate only exposes avc1 and mp4a configuration, so hev1/hvc1, av01, vp09, Opus, ac-3,
+//! ec-3 and fLaC sample entries are located by walking the raw moov atom and decoding their
+//! configuration box directly.
+use anyhow::Context;
+use bytes::Buf;
+use std::cmp;
+
+/// The codec and dimensions of a video track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoCodec {
+    pub codec: String,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// The codec and format of an audio track.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioCodec {
+    pub codec: String,
+    pub samplerate: u32,
+    pub channel_count: u16,
+    pub bitrate: Option<u32>,
+}
+
+// The fixed fields of a VisualSampleEntry before any child boxes.
+const VISUAL_SAMPLE_ENTRY_SIZE: usize = 78;
+
+// The fixed fields of an AudioSampleEntry before any child boxes.
+const AUDIO_SAMPLE_ENTRY_SIZE: usize = 28;
+
+/// Find the hev1/hvc1, av01 or vp09 sample entry for the given track in a raw moov atom.
+/// Returns None if the track uses a different sample entry.
+pub fn video(moov: &[u8], track_id: u32) -> anyhow::Result<Option<VideoCodec>> {
+    let (name, entry) = find_sample_entry(moov, track_id)?;
+    video_entry(&name, entry)
+}
+
+/// Find the Opus, ac-3, ec-3 or fLaC sample entry for the given track in a raw moov atom.
+/// Returns None if the track uses a different sample entry.
+pub fn audio(moov: &[u8], track_id: u32) -> anyhow::Result<Option<AudioCodec>> {
+    let (name, entry) = find_sample_entry(moov, track_id)?;
+    audio_entry(&name, entry)
+}
+
+/// Return the codec specific decoder configuration of the given track, ie: the payload of the avcC box.
+/// This is the `description` used by WebCodecs, and is sent alongside keyframes when using LOC.
+pub fn decoder_config(moov: &[u8], track_id: u32) -> anyhow::Result<Option<Vec<u8>>> {
+    let (name, entry) = find_sample_entry(moov, track_id)?;
+
+    let (fixed, config): (usize, &[u8; 4]) = match &name {
+        b"avc1" | b"avc3" => (VISUAL_SAMPLE_ENTRY_SIZE, b"avcC"),
+        b"hev1" | b"hvc1" => (VISUAL_SAMPLE_ENTRY_SIZE, b"hvcC"),
+        b"av01" => (VISUAL_SAMPLE_ENTRY_SIZE, b"av1C"),
+        b"vp09" => (VISUAL_SAMPLE_ENTRY_SIZE, b"vpcC"),
+        b"mp4a" => (AUDIO_SAMPLE_ENTRY_SIZE, b"esds"),
+        b"Opus" => (AUDIO_SAMPLE_ENTRY_SIZE, b"dOps"),
+        b"ac-3" => (AUDIO_SAMPLE_ENTRY_SIZE, b"dac3"),
+        b"ec-3" => (AUDIO_SAMPLE_ENTRY_SIZE, b"dec3"),
+        b"fLaC" => (AUDIO_SAMPLE_ENTRY_SIZE, b"dfLa"),
+        _ => return Ok(None),
+    };
+
+    let children = entry.get(fixed..).context("sample entry too small")?;
+    Ok(child(children, config).map(|config| config.to_vec()))
+}
+
+// Return the name and payload of the first sample entry of the given track.
+fn find_sample_entry(moov: &[u8], track_id: u32) -> anyhow::Result<([u8; 4], &[u8])> {
+    let (_, moov, _) = next_box(moov)?.context("empty moov atom")?;
+
+    for (name, trak) in boxes(moov) {
+        if &name != b"trak" {
+            continue;
+        }
+
+        let tkhd = child(trak, b"tkhd").context("missing tkhd")?;
+        if tkhd_track_id(tkhd)? != track_id {
+            continue;
+        }
+
+        let stsd = [b"mdia", b"minf", b"stbl", b"stsd"]
+            .iter()
+            .try_fold(trak, |parent, name| child(parent, name))
+            .context("missing stsd")?;
+
+        // Skip the version, flags and entry_count
+        let entries = stsd.get(8..).context("stsd too small")?;
+        let (name, entry, _) = next_box(entries)?.context("missing sample entry")?;
+
+        return Ok((name, entry));
+    }
+
+    anyhow::bail!("missing trak for track: {}", track_id)
+}
+
+fn video_entry(name: &[u8; 4], entry: &[u8]) -> anyhow::Result<Option<VideoCodec>> {
+    let config: &[u8; 4] = match name {
+        b"hev1" | b"hvc1" => b"hvcC",
+        b"av01" => b"av1C",
+        b"vp09" => b"vpcC",
+        _ => return Ok(None),
+    };
+
+    anyhow::ensure!(
+        entry.len() >= VISUAL_SAMPLE_ENTRY_SIZE,
+        "visual sample entry too small"
+    );
+
+    // Skip reserved, data_reference_index and pre_defined fields.
+    let mut fixed = &entry[24..28];
+    let width = fixed.get_u16();
+    let height = fixed.get_u16();
+
+    let children = &entry[VISUAL_SAMPLE_ENTRY_SIZE..];
+    let config = child(children, config)
+        .with_context(|| format!("missing {} box", String::from_utf8_lossy(config)))?;
+
+    let codec = match name {
+        b"av01" => av1(config)?,
+        b"vp09" => vp9(config)?,
+        _ => hevc(name, config)?,
+    };
+
+    Ok(Some(VideoCodec {
+        codec,
+        width,
+        height,
+    }))
+}
+
+fn audio_entry(name: &[u8; 4], entry: &[u8]) -> anyhow::Result<Option<AudioCodec>> {
+    let config: &[u8; 4] = match name {
+        b"Opus" => b"dOps",
+        b"ac-3" => b"dac3",
+        b"ec-3" => b"dec3",
+        b"fLaC" => b"dfLa",
+        _ => return Ok(None),
+    };
+
+    anyhow::ensure!(
+        entry.len() >= AUDIO_SAMPLE_ENTRY_SIZE,
+        "audio sample entry too small"
+    );
+
+    // Skip reserved and data_reference_index fields.
+    let mut fixed = &entry[16..AUDIO_SAMPLE_ENTRY_SIZE];
+    let channel_count = fixed.get_u16();
+    let _sample_size = fixed.get_u16();
+    fixed.advance(4); // pre_defined and reserved
+    let samplerate = fixed.get_u32() >> 16; // 16.16 fixed point
+
+    let children = &entry[AUDIO_SAMPLE_ENTRY_SIZE..];
+    let config = child(children, config)
+        .with_context(|| format!("missing {} box", String::from_utf8_lossy(config)))?;
+
+    let mut audio = AudioCodec {
+        codec: String::new(),
+        samplerate,
+        channel_count,
+        bitrate: None,
+    };
+
+    match name {
+        b"Opus" => opus(config, &mut audio)?,
+        b"ac-3" => ac3(config, &mut audio)?,
+        b"ec-3" => eac3(config, &mut audio)?,
+        _ => flac(config, &mut audio)?,
+    };
+
+    // Prefer the bitrate box when present, since it covers every codec.
+    if let Some(mut btrt) = child(children, b"btrt").filter(|btrt| btrt.len() >= 12) {
+        let _buffer_size = btrt.get_u32();
+        let max_bitrate = btrt.get_u32();
+        let avg_bitrate = btrt.get_u32();
+
+        let bitrate = cmp::max(max_bitrate, avg_bitrate);
+        if bitrate > 0 {
+            audio.bitrate = Some(bitrate);
+        }
+    }
+
+    Ok(Some(audio))
+}
+
+// See https://opus-codec.org/docs/opus_in_isobmff.html
+fn opus(mut dops: &[u8], audio: &mut AudioCodec) -> anyhow::Result<()> {
+    anyhow::ensure!(dops.len() >= 11, "dOps box too small");
+
+    let _version = dops.get_u8();
+    let channel_count = dops.get_u8();
+    let _pre_skip = dops.get_u16();
+    let _input_samplerate = dops.get_u32();
+
+    // Opus is always decoded at 48kHz, regardless of the input sample rate.
+    audio.codec = "opus".to_string();
+    audio.samplerate = 48000;
+    audio.channel_count = channel_count.into();
+
+    Ok(())
+}
+
+// See ETSI TS 102 366 Annex F
+const AC3_SAMPLERATES: [u32; 3] = [48000, 44100, 32000];
+const AC3_CHANNELS: [u16; 8] = [2, 1, 2, 3, 3, 4, 4, 5];
+const AC3_BITRATES: [u32; 19] = [
+    32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 576, 640,
+];
+
+fn ac3(dac3: &[u8], audio: &mut AudioCodec) -> anyhow::Result<()> {
+    anyhow::ensure!(dac3.len() >= 3, "dac3 box too small");
+
+    // fscod(2) bsid(5) bsmod(3) acmod(3) lfeon(1) bit_rate_code(5) reserved(5)
+    let bits = u32::from_be_bytes([0, dac3[0], dac3[1], dac3[2]]);
+    let fscod = (bits >> 22) & 0x3;
+    let acmod = (bits >> 11) & 0x7;
+    let lfeon = (bits >> 10) & 0x1;
+    let bit_rate_code = (bits >> 5) & 0x1f;
+
+    audio.codec = "ac-3".to_string();
+    if let Some(samplerate) = AC3_SAMPLERATES.get(fscod as usize) {
+        audio.samplerate = *samplerate;
+    }
+    audio.channel_count = AC3_CHANNELS[acmod as usize] + lfeon as u16;
+    audio.bitrate = AC3_BITRATES
+        .get(bit_rate_code as usize)
+        .map(|kbps| kbps * 1000);
+
+    Ok(())
+}
+
+fn eac3(mut dec3: &[u8], audio: &mut AudioCodec) -> anyhow::Result<()> {
+    anyhow::ensure!(dec3.len() >= 5, "dec3 box too small");
+
+    // data_rate(13) num_ind_sub(3)
+    let header = dec3.get_u16();
+    let data_rate = (header >> 3) as u32;
+
+    // Use the first independent substream: fscod(2) bsid(5) reserved(1) asvc(1) bsmod(3) acmod(3) lfeon(1)
+    let bits = dec3.get_u16();
+    let fscod = bits >> 14;
+    let acmod = (bits >> 1) & 0x7;
+    let lfeon = bits & 0x1;
+
+    audio.codec = "ec-3".to_string();
+    if let Some(samplerate) = AC3_SAMPLERATES.get(fscod as usize) {
+        audio.samplerate = *samplerate;
+    }
+    audio.channel_count = AC3_CHANNELS[acmod as usize] + lfeon;
+    if data_rate > 0 {
+        audio.bitrate = Some(data_rate * 1000);
+    }
+
+    Ok(())
+}
+
+// See https://github.com/xiph/flac/blob/master/doc/isoflac.txt
+fn flac(mut dfla: &[u8], audio: &mut AudioCodec) -> anyhow::Result<()> {
+    anyhow::ensure!(dfla.len() >= 4 + 4 + 18, "dfLa box too small");
+
+    let _version_flags = dfla.get_u32();
+
+    // The first metadata block must be STREAMINFO.
+    let block_type = dfla.get_u8() & 0x7f;
+    anyhow::ensure!(block_type == 0, "missing FLAC STREAMINFO block");
+    dfla.advance(3); // length
+
+    // min/max block size and min/max frame size
+    dfla.advance(10);
+
+    // samplerate(20) channels-1(3) bits_per_sample-1(5) total_samples(36)
+    let bits = dfla.get_u32();
+    let samplerate = bits >> 12;
+    let channels = ((bits >> 9) & 0x7) + 1;
+
+    audio.codec = "flac".to_string();
+    audio.samplerate = samplerate;
+    audio.channel_count = channels as u16;
+
+    Ok(())
+}
+
+// hev1.[profile_space][profile_idc].[compatibility].[tier][level].[constraints]
+// See ISO/IEC 14496-15 Annex E.3
+fn hevc(name: &[u8; 4], mut hvcc: &[u8]) -> anyhow::Result<String> {
+    anyhow::ensure!(hvcc.len() >= 13, "hvcC box too small");
+
+    let _version = hvcc.get_u8();
+    let profile = hvcc.get_u8();
+    let compatibility = hvcc.get_u32();
+    let constraints = &hvcc[..6];
+    let level = hvcc[6];
+
+    let profile_space = match profile >> 6 {
+        0 => "",
+        1 => "A",
+        2 => "B",
+        _ => "C",
+    };
+    let tier = match (profile >> 5) & 0x1 {
+        0 => 'L',
+        _ => 'H',
+    };
+    let profile_idc = profile & 0x1f;
+
+    let mut codec = format!(
+        "{}.{}{}.{:X}.{}{}",
+        std::str::from_utf8(name)?,
+        profile_space,
+        profile_idc,
+        compatibility.reverse_bits(),
+        tier,
+        level
+    );
+
+    // Trailing bytes of the constraint flags are omitted when zero.
+    let used = constraints
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |i| i + 1);
+    for byte in &constraints[..used] {
+        codec.push_str(&format!(".{:X}", byte));
+    }
+
+    Ok(codec)
+}
+
+// av01.[profile].[level][tier].[bit_depth]
+// See https://aomediacodec.github.io/av1-isobmff/#codecsparam
+fn av1(av1c: &[u8]) -> anyhow::Result<String> {
+    anyhow::ensure!(av1c.len() >= 4, "av1C box too small");
+
+    let profile = av1c[1] >> 5;
+    let level = av1c[1] & 0x1f;
+    let tier = match av1c[2] >> 7 {
+        0 => 'M',
+        _ => 'H',
+    };
+
+    let high_bitdepth = (av1c[2] >> 6) & 0x1 == 1;
+    let twelve_bit = (av1c[2] >> 5) & 0x1 == 1;
+    let bit_depth = match (high_bitdepth, twelve_bit) {
+        (false, _) => 8,
+        (true, false) => 10,
+        (true, true) => 12,
+    };
+
+    Ok(format!(
+        "av01.{}.{:02}{}.{:02}",
+        profile, level, tier, bit_depth
+    ))
+}
+
+// vp09.[profile].[level].[bit_depth]
+// See https://www.webmproject.org/vp9/mp4/#codecs-parameter-string
+fn vp9(mut vpcc: &[u8]) -> anyhow::Result<String> {
+    anyhow::ensure!(vpcc.len() >= 7, "vpcC box too small");
+
+    let _version_flags = vpcc.get_u32();
+    let profile = vpcc.get_u8();
+    let level = vpcc.get_u8();
+    let bit_depth = vpcc.get_u8() >> 4;
+
+    Ok(format!("vp09.{:02}.{:02}.{:02}", profile, level, bit_depth))
+}
+
+fn tkhd_track_id(tkhd: &[u8]) -> anyhow::Result<u32> {
+    let version = *tkhd.first().context("empty tkhd")?;
+
+    // Skip the version, flags, creation_time and modification_time.
+    let offset = match version {
+        1 => 4 + 8 + 8,
+        _ => 4 + 4 + 4,
+    };
+
+    let mut track_id = tkhd.get(offset..offset + 4).context("tkhd too small")?;
+    Ok(track_id.get_u32())
+}
+
+// Find the payload of the first child box with the given name.
+fn child<'a>(parent: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(parent)
+        .find(|(n, _)| n == name)
+        .map(|(_, payload)| payload)
+}
+
+// Iterate over the (name, payload) of each box, stopping at the first malformed box.
+fn boxes(mut buf: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let (name, payload, size) = next_box(buf).ok()??;
+        buf = &buf[size..];
+        Some((name, payload))
+    })
+}
+
+// The name, payload and total size of a box.
+type RawBox<'a> = ([u8; 4], &'a [u8], usize);
+
+// Parse the box at the start of the buffer.
+fn next_box(buf: &[u8]) -> anyhow::Result<Option<RawBox<'_>>> {
+    if buf.len() < 8 {
+        return Ok(None);
+    }
+
+    let mut header = buf;
+    let size = header.get_u32() as usize;
+    let mut name = [0u8; 4];
+    header.copy_to_slice(&mut name);
+
+    let (header_size, size) = match size {
+        // Runs until the end of the buffer.
+        0 => (8, buf.len()),
+
+        // The next 8 bytes are the extended size to be used instead.
+        1 => {
+            anyhow::ensure!(header.len() >= 8, "truncated extended box size");
+            (16, header.get_u64() as usize)
+        }
+
+        size => (8, size),
+    };
+
+    anyhow::ensure!(size >= header_size, "impossible box size: {}", size);
+    anyhow::ensure!(size <= buf.len(), "truncated box: {:?}", name);
+
+    Ok(Some((name, &buf[header_size..size], size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hevc_codec_string() {
+        // Main profile, level 3.1, progressive source flag set.
+        let hvcc = [
+            0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5d,
+        ];
+        assert_eq!(hevc(b"hvc1", &hvcc).unwrap(), "hvc1.1.6.L93.90");
+
+        // Main 10 profile, high tier
+        let hvcc = [
+            0x01, 0x22, 0x20, 0x00, 0x00, 0x00, 0xb0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x78,
+        ];
+        assert_eq!(hevc(b"hev1", &hvcc).unwrap(), "hev1.2.4.H120.B0");
+    }
+
+    #[test]
+    fn av1_codec_string() {
+        // Main profile, level 4.0 (8), main tier, 8 bit
+        assert_eq!(av1(&[0x81, 0x08, 0x0c, 0x00]).unwrap(), "av01.0.08M.08");
+
+        // Main profile, level 5.1 (13), high tier, 10 bit
+        assert_eq!(av1(&[0x81, 0x0d, 0xcc, 0x00]).unwrap(), "av01.0.13H.10");
+    }
+
+    #[test]
+    fn vp9_codec_string() {
+        let vpcc = [0x01, 0x00, 0x00, 0x00, 0x00, 0x1f, 0x80, 0x02, 0x02, 0x02];
+        assert_eq!(vp9(&vpcc).unwrap(), "vp09.00.31.08");
+    }
+
+    #[test]
+    fn find_sample_entry() {
+        fn atom(name: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+            let mut buf = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+            buf.extend_from_slice(name);
+            buf.extend_from_slice(payload);
+            buf
+        }
+
+        let mut entry = vec![0u8; VISUAL_SAMPLE_ENTRY_SIZE];
+        entry[24..28].copy_from_slice(&[0x07, 0x80, 0x04, 0x38]); // 1920x1080
+        entry.extend(atom(b"av1C", &[0x81, 0x08, 0x0c, 0x00]));
+
+        let mut tkhd = vec![0u8; 84];
+        tkhd[12..16].copy_from_slice(&2u32.to_be_bytes());
+
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(atom(b"av01", &entry));
+
+        let stbl = atom(b"stbl", &atom(b"stsd", &stsd));
+        let mdia = atom(b"mdia", &atom(b"minf", &stbl));
+        let trak = atom(b"trak", &[atom(b"tkhd", &tkhd), mdia].concat());
+        let moov = atom(b"moov", &trak);
+
+        let codec = video(&moov, 2).unwrap().unwrap();
+        assert_eq!(
+            codec,
+            VideoCodec {
+                codec: "av01.0.08M.08".to_string(),
+                width: 1920,
+                height: 1080,
+            }
+        );
+
+        assert!(video(&moov, 1).is_err());
+    }
+
+    #[test]
+    fn opus_config() {
+        let dops = [
+            0x00, 0x02, 0x01, 0x38, 0x00, 0x00, 0xbb, 0x80, 0x00, 0x00, 0x00,
+        ];
+        let mut audio = audio_codec();
+        opus(&dops, &mut audio).unwrap();
+
+        assert_eq!(audio.codec, "opus");
+        assert_eq!(audio.samplerate, 48000);
+        assert_eq!(audio.channel_count, 2);
+    }
+
+    #[test]
+    fn ac3_config() {
+        // 48kHz, 3/2 with LFE, 384 kbps
+        let dac3 = [0x10, 0x3d, 0xc0];
+        let mut audio = audio_codec();
+        ac3(&dac3, &mut audio).unwrap();
+
+        assert_eq!(audio.codec, "ac-3");
+        assert_eq!(audio.samplerate, 48000);
+        assert_eq!(audio.channel_count, 6);
+        assert_eq!(audio.bitrate, Some(384_000));
+    }
+
+    #[test]
+    fn eac3_config() {
+        // 640 kbps, 48kHz, 3/2 with LFE
+        let dec3 = [0x14, 0x00, 0x20, 0x0f, 0x00];
+        let mut audio = audio_codec();
+        eac3(&dec3, &mut audio).unwrap();
+
+        assert_eq!(audio.codec, "ec-3");
+        assert_eq!(audio.samplerate, 48000);
+        assert_eq!(audio.channel_count, 6);
+        assert_eq!(audio.bitrate, Some(640_000));
+    }
+
+    #[test]
+    fn flac_config() {
+        let mut dfla = vec![0, 0, 0, 0, 0x80, 0x00, 0x00, 0x22];
+        dfla.extend_from_slice(&[0x10, 0x00, 0x10, 0x00, 0, 0, 0, 0, 0, 0]);
+        // 44100 Hz, 2 channels, 16 bits
+        dfla.extend_from_slice(&[0x0a, 0xc4, 0x42, 0xf0, 0, 0, 0, 0]);
+        dfla.extend_from_slice(&[0; 16]); // md5
+
+        let mut audio = audio_codec();
+        flac(&dfla, &mut audio).unwrap();
+
+        assert_eq!(audio.codec, "flac");
+        assert_eq!(audio.samplerate, 44100);
+        assert_eq!(audio.channel_count, 2);
+    }
+
+    fn audio_codec() -> AudioCodec {
+        AudioCodec {
+            codec: String::new(),
+            samplerate: 0,
+            channel_count: 0,
+            bitrate: None,
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+// The amount of media used to estimate the frame rate and bitrate, in seconds.
+const WINDOW: u64 = 4;
+
+// The estimate is considered stable once the window contains this much media, in seconds.
+const STABLE: u64 = 2;
+
+// Advertise a new bitrate once it differs from the advertised bitrate by this many percent.
+const BITRATE_CHANGE: u64 = 25;
+
+/// The measured properties of a track, advertised in the catalog.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Estimate {
+    pub framerate: Option<u64>,
+    pub bitrate: Option<u32>,
+}
+
+// The size and duration of a single fragment.
+struct Fragment {
+    samples: u64,
+    duration: u64,
+    bytes: u64,
+}
+
+/// Estimates the frame rate and bitrate of a track from the fragments it carries.
+///
+/// Fragments are measured over a sliding window of media time, and an estimate is only returned
+/// once the window is stable and the measurement differs from the advertised value.
+pub struct Estimator {
+    // The number of units per second.
+    timescale: u64,
+
+    // Which fields to estimate.
+    framerate: bool,
+    bitrate: bool,
+
+    // The fragments within the window, and their total duration.
+    window: VecDeque<Fragment>,
+    duration: u64,
+
+    // The last estimate that was returned.
+    advertised: Estimate,
+}
+
+impl Estimator {
+    pub fn new(timescale: u64, framerate: bool, bitrate: bool) -> Self {
+        Self {
+            timescale,
+            framerate,
+            bitrate,
+            window: VecDeque::new(),
+            duration: 0,
+            advertised: Estimate::default(),
+        }
+    }
+
+    /// Record a fragment, returning the new estimate if it should be advertised.
+    pub fn record(&mut self, samples: u32, duration: u64, bytes: usize) -> Option<Estimate> {
+        if !self.framerate && !self.bitrate {
+            return None;
+        }
+
+        self.window.push_back(Fragment {
+            samples: samples.into(),
+            duration,
+            bytes: bytes as u64,
+        });
+        self.duration += duration;
+
+        // Drop the oldest fragments once the rest still fill the window.
+        while let Some(oldest) = self.window.front() {
+            if self.duration - oldest.duration < WINDOW * self.timescale {
+                break;
+            }
+
+            self.duration -= oldest.duration;
+            self.window.pop_front();
+        }
+
+        if self.timescale == 0 || self.duration < STABLE * self.timescale {
+            return None;
+        }
+
+        let samples: u64 = self.window.iter().map(|fragment| fragment.samples).sum();
+        let bytes: u64 = self.window.iter().map(|fragment| fragment.bytes).sum();
+
+        let mut estimate = self.advertised;
+        let mut changed = false;
+
+        if self.framerate {
+            // Round to the nearest frame, so small jitter in the sample durations is ignored.
+            let framerate = (samples * self.timescale + self.duration / 2) / self.duration;
+            if self.advertised.framerate != Some(framerate) {
+                estimate.framerate = Some(framerate);
+                changed = true;
+            }
+        }
+
+        if self.bitrate {
+            let bitrate = (bytes * 8 * self.timescale / self.duration).min(u32::MAX.into());
+            let significant = match self.advertised.bitrate {
+                Some(advertised) => {
+                    let advertised = advertised as u64;
+                    bitrate.abs_diff(advertised) * 100 > advertised * BITRATE_CHANGE
+                }
+                None => true,
+            };
+
+            if significant {
+                estimate.bitrate = Some(bitrate as u32);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return None;
+        }
+
+        self.advertised = estimate;
+        Some(estimate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stable_estimate() {
+        let mut estimator = Estimator::new(90000, true, true);
+
+        // One second fragments of 30 frames and 125KB, ie: 1Mbps
+        assert_eq!(estimator.record(30, 90000, 125_000), None);
+
+        let estimate = estimator.record(30, 90000, 125_000).unwrap();
+        assert_eq!(estimate.framerate, Some(30));
+        assert_eq!(estimate.bitrate, Some(1_000_000));
+
+        // Nothing changed, so nothing new to advertise.
+        assert_eq!(estimator.record(30, 90000, 125_000), None);
+
+        // Small fluctuations in bitrate are ignored.
+        assert_eq!(estimator.record(30, 90000, 150_000), None);
+    }
+
+    #[test]
+    fn significant_change() {
+        let mut estimator = Estimator::new(1000, false, true);
+
+        for _ in 0..4 {
+            estimator.record(30, 1000, 125_000);
+        }
+
+        // The bitrate doubles, which is advertised once enough of the window has changed.
+        let estimate = (0..4)
+            .find_map(|_| estimator.record(30, 1000, 250_000))
+            .unwrap();
+        assert_eq!(estimate.framerate, None);
+        assert!(estimate.bitrate.unwrap() > 1_250_000);
+    }
+}
@@ -1,2 +1,8 @@
+mod atom;
+mod broadcast;
+mod codec;
+mod estimate;
 mod media;
+
+pub use broadcast::*;
 pub use media::*;
@@ -1,14 +1,14 @@
 use bytes::BytesMut;
-use std::net;
+use std::{net, path};
 use url::Url;
 
 use anyhow::Context;
 use clap::Parser;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use moq_native_ietf::quic;
-use moq_pub::Media;
-use moq_transport::{coding::TrackNamespace, serve, session::Publisher};
+use moq_pub::{Broadcast, Media};
+use moq_transport::{coding::TrackNamespace, mlog::VantagePoint, serve, session::Session};
 
 #[derive(Parser, Clone)]
 pub struct Cli {
@@ -17,14 +17,30 @@ pub struct Cli {
     pub bind: net::SocketAddr,
 
     /// Advertise this frame rate in the catalog (informational)
-    // TODO auto-detect this from the input when not provided
-    #[arg(long, default_value = "24")]
-    pub fps: u8,
+    ///
+    /// Estimated from the sample durations of each input when not provided.
+    #[arg(long)]
+    pub fps: Option<u8>,
+
+    /// Advertise this video bit rate in the catalog (informational)
+    ///
+    /// Provide once per input, in the same order, to advertise the bit rate of each rendition.
+    /// Estimated from the fragment sizes of each input when not provided.
+    #[arg(long)]
+    pub bitrate: Vec<u32>,
+
+    /// Read fragmented MP4 from the given file, named pipe or unix socket instead of stdin.
+    ///
+    /// Provide multiple times to publish an ABR ladder, where each input is a rendition of the same content.
+    #[arg(long = "input")]
+    pub inputs: Vec<path::PathBuf>,
 
-    /// Advertise this bit rate in the catalog (informational)
-    // TODO auto-detect this from the input when not provided
-    #[arg(long, default_value = "1500000")]
-    pub bitrate: u32,
+    /// How each fragment is packaged into objects.
+    ///
+    /// CMAF publishes each fragment as-is, while LOC publishes each frame as a separate object
+    /// with the timestamp and decoder configuration in the object extension headers.
+    #[arg(long, value_enum, default_value_t = Packaging::Cmaf)]
+    pub packaging: Packaging,
 
     /// Connect to the given URL starting with https://
     #[arg()]
@@ -37,6 +53,25 @@ pub struct Cli {
     /// The TLS configuration.
     #[command(flatten)]
     pub tls: moq_native_ietf::tls::Args,
+
+    /// The mlog configuration.
+    #[command(flatten)]
+    pub mlog: moq_native_ietf::mlog::Args,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Packaging {
+    Cmaf,
+    Loc,
+}
+
+impl From<Packaging> for moq_catalog::TrackPackaging {
+    fn from(packaging: Packaging) -> Self {
+        match packaging {
+            Packaging::Cmaf => Self::Cmaf,
+            Packaging::Loc => Self::Loc,
+        }
+    }
 }
 
 #[tokio::main]
@@ -51,11 +86,33 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
+    let renditions = cli.inputs.len().max(1);
+    anyhow::ensure!(
+        cli.bitrate.is_empty() || cli.bitrate.len() == renditions,
+        "--bitrate must be provided once per input"
+    );
+
     let (writer, _, reader) =
         serve::Tracks::new(TrackNamespace::from_utf8_path(&cli.name)).produce();
-    let media = Media::new(writer)?;
+    let broadcast = Broadcast::new(writer, renditions)?;
+
+    let mut media = Vec::with_capacity(renditions);
+    for index in 0..renditions {
+        let mut rendition = broadcast
+            .rendition(index)?
+            .with_packaging(cli.packaging.into());
+        if let Some(bitrate) = cli.bitrate.get(index) {
+            rendition = rendition.with_bitrate(*bitrate);
+        }
+        if let Some(fps) = cli.fps {
+            rendition = rendition.with_framerate(fps.into());
+        }
+
+        media.push((rendition, cli.inputs.get(index).cloned()));
+    }
 
     let tls = cli.tls.load()?;
+    cli.mlog.validate()?;
 
     let quic = quic::Endpoint::new(moq_native_ietf::quic::Config {
         bind: cli.bind,
@@ -64,20 +121,25 @@ async fn main() -> anyhow::Result<()> {
     })?;
 
     log::info!("connecting to relay: url={}", cli.url);
-    let (session, connection_id) = quic.client.connect(&cli.url).await?;
+    let (session, connection_id, transport) = quic.client.connect(&cli.url).await?;
 
     log::info!(
         "connected with CID: {} (use this to look up qlog/mlog on server)",
         connection_id
     );
 
-    let (session, mut publisher) = Publisher::connect(session)
+    // Create the mlog for this connection ID if mlog directory is configured
+    let mlog = cli
+        .mlog
+        .create(&connection_id, "moq-pub", VantagePoint::Client)?;
+
+    let (session, mut publisher, _) = Session::connect(session, transport, mlog)
         .await
         .context("failed to create MoQ Transport publisher")?;
 
     tokio::select! {
         res = session.run() => res.context("session error")?,
-        res = run_media(media) => {
+        res = run_renditions(&broadcast, media) => {
             res.context("media error")?
         },
         res = publisher.announce(reader) => res.context("publisher error")?,
@@ -86,14 +148,83 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn run_media(mut media: Media) -> anyhow::Result<()> {
-    let mut input = tokio::io::stdin();
+async fn run_renditions(
+    broadcast: &Broadcast,
+    media: Vec<(Media, Option<path::PathBuf>)>,
+) -> anyhow::Result<()> {
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, (media, input)) in media.into_iter().enumerate() {
+        let broadcast = broadcast.clone();
+        tasks.spawn(async move {
+            let name = input
+                .as_ref()
+                .map_or("stdin".to_string(), |path| path.display().to_string());
+            let input = open_input(input.as_deref()).await?;
+            run_media(media, input)
+                .await
+                .with_context(|| format!("failed to publish {name}"))?;
+
+            // Remove the rendition from the catalog once its input has ended.
+            log::info!("finished publishing {name}");
+            broadcast.remove(index)
+        });
+    }
+
+    while let Some(res) = tasks.join_next().await {
+        res??;
+    }
+
+    Ok(())
+}
+
+// Open the input for a rendition, defaulting to stdin.
+async fn open_input(
+    path: Option<&path::Path>,
+) -> anyhow::Result<Box<dyn AsyncRead + Send + Unpin>> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(Box::new(tokio::io::stdin())),
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        let metadata = tokio::fs::metadata(path)
+            .await
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        if metadata.file_type().is_socket() {
+            let socket = tokio::net::UnixStream::connect(path)
+                .await
+                .with_context(|| format!("failed to connect to {}", path.display()))?;
+            return Ok(Box::new(socket));
+        }
+    }
+
+    // Regular files and named pipes can be read the same way.
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    Ok(Box::new(file))
+}
+
+async fn run_media(
+    mut media: Media,
+    mut input: Box<dyn AsyncRead + Send + Unpin>,
+) -> anyhow::Result<()> {
     let mut buf = BytesMut::new();
     loop {
-        input
+        let size = input
             .read_buf(&mut buf)
             .await
-            .context("failed to read from stdin")?;
-        media.parse(&mut buf).context("failed to parse media")?;
+            .context("failed to read input")?;
+        if size == 0 {
+            return media.finish(&mut buf).context("failed to parse media");
+        }
+
+        // Reserve room for the rest of the atom, so it's read in as few reads as possible.
+        if let Some(needed) = media.parse(&mut buf).context("failed to parse media")? {
+            buf.reserve(needed);
+        }
     }
 }
@@ -1,5 +1,7 @@
 use anyhow::{self, Context};
 use bytes::{Buf, Bytes};
+use moq_catalog::{loc, TrackPackaging};
+use moq_transport::data::ExtensionHeaders;
 use moq_transport::serve::{SubgroupWriter, SubgroupsWriter, TrackWriter, TracksWriter};
 use mp4::{self, ReadBox, TrackType};
 use std::cmp::max;
@@ -7,16 +9,29 @@ use std::collections::HashMap;
 use std::io::Cursor;
 use std::time;
 
+use crate::atom::{Atom, AtomReader, Next};
+use crate::estimate::{Estimate, Estimator};
+use crate::{codec, Broadcast};
+
 pub struct Media {
     // Tracks based on their track ID.
     tracks: HashMap<u32, Track>,
 
-    // The full broadcast of tracks
-    broadcast: TracksWriter,
+    // The broadcast shared with any other renditions.
+    broadcast: Broadcast,
+
+    // The index of this rendition within the broadcast.
+    index: usize,
+
+    // How each fragment is packaged into objects.
+    packaging: TrackPackaging,
+
+    // The init track of this rendition, only used by CMAF.
+    init: Option<SubgroupsWriter>,
 
-    // The init and catalog tracks
-    init: SubgroupsWriter,
-    catalog: SubgroupsWriter,
+    // Advertise this video frame rate and bitrate in the catalog, instead of estimating them.
+    framerate: Option<u64>,
+    bitrate: Option<u32>,
 
     // The ftyp and moov atoms at the start of the file.
     ftyp: Option<Bytes>,
@@ -24,75 +39,126 @@ pub struct Media {
 
     // The current track name
     current: Option<u32>,
+
+    // Splits the input into atoms.
+    atoms: AtomReader,
 }
 
 impl Media {
-    pub fn new(mut broadcast: TracksWriter) -> anyhow::Result<Self> {
-        let catalog = broadcast
-            .create(".catalog")
-            .context("broadcast closed")?
-            .subgroups()?;
-        let init = broadcast
-            .create("0.mp4")
-            .context("broadcast closed")?
-            .subgroups()?;
+    /// Create a broadcast consisting of a single rendition.
+    pub fn new(broadcast: TracksWriter) -> anyhow::Result<Self> {
+        Broadcast::new(broadcast, 1)?.rendition(0)
+    }
 
+    pub(crate) fn rendition(broadcast: Broadcast, index: usize) -> anyhow::Result<Self> {
         Ok(Media {
             tracks: Default::default(),
             broadcast,
-            catalog,
-            init,
+            index,
+            packaging: TrackPackaging::Cmaf,
+            init: None,
+            framerate: None,
+            bitrate: None,
             ftyp: None,
             moov: None,
             current: None,
+            atoms: AtomReader::default(),
         })
     }
 
+    /// Advertise the given bitrate for the video track of this rendition.
+    /// Otherwise the bitrate is estimated from the size of each fragment.
+    pub fn with_bitrate(mut self, bitrate: u32) -> Self {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    /// Publish using the given packaging, instead of CMAF.
+    ///
+    /// With LOC, each frame is published as a separate object without the moof and mdat,
+    /// and the timestamp and decoder configuration are carried in the object extension headers.
+    pub fn with_packaging(mut self, packaging: TrackPackaging) -> Self {
+        self.packaging = packaging;
+        self
+    }
+
+    /// Advertise the given frame rate for the video track of this rendition.
+    /// Otherwise the frame rate is estimated from the duration of each sample.
+    pub fn with_framerate(mut self, framerate: u64) -> Self {
+        self.framerate = Some(framerate);
+        self
+    }
+
     pub fn reset(&mut self) {
         for track in self.tracks.values_mut() {
             track.end_group();
         }
     }
 
-    // Parse the input buffer, reading any full atoms we can find.
-    // Keep appending more data and calling parse.
-    pub fn parse<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<()> {
-        while self.parse_atom(buf)? {}
+    /// Parse the input buffer, reading any full atoms we can find.
+    /// Keep appending more data and calling parse.
+    ///
+    /// Returns the number of bytes needed before the next atom can be parsed,
+    /// or None if the next atom extends to the end of the input.
+    pub fn parse<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<Option<usize>> {
+        loop {
+            match self.atoms.next(buf)? {
+                Next::Atom(atom) => self.parse_atom(atom)?,
+                Next::Needed(needed) => return Ok(Some(needed)),
+                Next::UntilEof => return Ok(None),
+            }
+        }
+    }
+
+    /// Parse the remainder of the input once it has ended.
+    ///
+    /// Returns an error if the input ended in the middle of an atom.
+    pub fn finish<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<()> {
+        self.parse(buf)?;
+
+        if let Some(atom) = self.atoms.finish(buf)? {
+            self.parse_atom(atom)?;
+        }
+
         Ok(())
     }
 
-    fn parse_atom<B: Buf>(&mut self, buf: &mut B) -> anyhow::Result<bool> {
-        let atom = match next_atom(buf)? {
-            Some(atom) => atom,
-            None => return Ok(false),
-        };
+    fn parse_atom(&mut self, atom: Atom) -> anyhow::Result<()> {
+        let kind = atom.kind();
+        let offset = atom.offset;
 
+        self.handle_atom(atom.name, atom.raw)
+            .with_context(|| format!("failed to parse {kind} atom at offset {offset}"))
+    }
+
+    fn handle_atom(&mut self, name: [u8; 4], atom: Bytes) -> anyhow::Result<()> {
         let mut reader = Cursor::new(&atom);
-        let header = mp4::BoxHeader::read(&mut reader)?;
 
-        match header.name {
-            mp4::BoxType::FtypBox => {
+        match &name {
+            b"ftyp" => {
                 if self.ftyp.is_some() {
                     tracing::debug!("multiple ftyp atoms");
-                    return Ok(true);
+                    return Ok(());
                 }
 
                 // Save the ftyp atom for later.
                 self.ftyp = Some(atom)
             }
-            mp4::BoxType::MoovBox => {
+            b"moov" => {
                 if self.moov.is_some() {
                     tracing::debug!("multiple moov atoms");
-                    return Ok(true);
+                    return Ok(());
                 }
 
                 // Parse the moov box so we can detect the timescales for each track.
+                let header = mp4::BoxHeader::read(&mut reader)?;
                 let moov = mp4::MoovBox::read_box(&mut reader, header.size)?;
 
                 self.setup(&moov, atom)?;
                 self.moov = Some(moov);
             }
-            mp4::BoxType::MoofBox => {
+            b"moof" => {
+                let header = mp4::BoxHeader::read(&mut reader)?;
                 let moof = mp4::MoofBox::read_box(&mut reader, header.size)?;
 
                 // Process the moof.
@@ -129,7 +195,7 @@ impl Media {
                     .header(atom, fragment)
                     .context("failed to publish moof")?;
             }
-            mp4::BoxType::MdatBox => {
+            b"mdat" => {
                 // Get the track ID from the previous moof.
                 let track = self.current.take().context("missing moof")?;
                 let track = self
@@ -138,31 +204,51 @@ impl Media {
                     .context("failed to find track")?;
 
                 // Publish the mdat atom.
-                track.data(atom).context("failed to publish mdat")?;
+                let estimate = track.data(atom).context("failed to publish mdat")?;
+
+                // Advertise any new measurements of the track in the catalog.
+                if let Some(estimate) = estimate {
+                    self.broadcast.update(&track.name, estimate)?;
+                }
             }
 
+            b"styp" | b"sidx" | b"prft" | b"emsg" => {
+                // Segment boxes that may appear between fragments, which aren't published.
+                tracing::trace!("skipping {} atom", String::from_utf8_lossy(&name));
+            }
             _ => {
                 // Skip unknown atoms
             }
         }
 
-        Ok(true)
+        Ok(())
     }
 
     fn setup(&mut self, moov: &mp4::MoovBox, raw: Bytes) -> anyhow::Result<()> {
-        // Combine the ftyp+moov atoms into a single object.
-        let mut init = self.ftyp.clone().context("missing ftyp")?.to_vec();
-        init.extend_from_slice(&raw);
-
-        // Create the catalog track with a single segment.
-        self.init.append(0)?.write(init.into())?;
+        // LOC doesn't use an init track, since the decoder configuration is sent with each keyframe.
+        let init_track = match self.packaging {
+            TrackPackaging::Cmaf => {
+                // Combine the ftyp+moov atoms into a single object.
+                let mut init = self.ftyp.clone().context("missing ftyp")?.to_vec();
+                init.extend_from_slice(&raw);
+
+                // Create the init track with a single segment.
+                let name = self.broadcast.init_name(self.index);
+                let mut track = self.broadcast.create(&name)?.subgroups()?;
+                track.append(0)?.write(init.into())?;
+                self.init = Some(track);
+
+                Some(name)
+            }
+            TrackPackaging::Loc => None,
+        };
 
         let mut tracks = Vec::new();
 
         // Produce the catalog
         for trak in &moov.traks {
             let id = trak.tkhd.track_id;
-            let name = format!("{id}.m4s");
+            let name = self.broadcast.track_name(self.index, id);
 
             let timescale = track_timescale(moov, id);
             let handler = (&trak.mdia.hdlr.handler_type).try_into()?;
@@ -170,10 +256,10 @@ impl Media {
             let mut selection_params = moq_catalog::SelectionParam::default();
 
             let mut track = moq_catalog::Track {
-                init_track: Some(self.init.name.clone()),
+                init_track: init_track.clone(),
                 name: name.clone(),
-                namespace: Some(self.broadcast.namespace.to_utf8_path()),
-                packaging: Some(moq_catalog::TrackPackaging::Cmaf),
+                namespace: Some(self.broadcast.namespace()),
+                packaging: Some(self.packaging.clone()),
                 render_group: Some(1),
                 ..Default::default()
             };
@@ -199,9 +285,8 @@ impl Media {
                 selection_params.codec = Some(codec_str);
                 selection_params.width = Some(width.into());
                 selection_params.height = Some(height.into());
-            } else if let Some(_hev1) = &stsd.hev1 {
-                // TODO https://github.com/gpac/mp4box.js/blob/325741b592d910297bf609bc7c400fc76101077b/src/box-codecs.js#L106
-                anyhow::bail!("HEVC not yet supported")
+                selection_params.framerate = self.framerate;
+                selection_params.bitrate = self.bitrate;
             } else if let Some(mp4a) = &stsd.mp4a {
                 let desc = &mp4a
                     .esds
@@ -222,101 +307,69 @@ impl Media {
                 if bitrate > 0 {
                     selection_params.bitrate = Some(bitrate);
                 }
-            } else if let Some(vp09) = &stsd.vp09 {
-                // https://github.com/gpac/mp4box.js/blob/325741b592d910297bf609bc7c400fc76101077b/src/box-codecs.js#L238
-                let vpcc = &vp09.vpcc;
-                let codec_str = format!(
-                    "vp09.0.{:02x}.{:02x}.{:02x}",
-                    vpcc.profile, vpcc.level, vpcc.bit_depth
-                );
-
-                selection_params.codec = Some(codec_str);
-                selection_params.width = Some(vp09.width.into());
-                selection_params.height = Some(vp09.height.into());
-
-                // TODO Test if this actually works; I'm just guessing based on mp4box.js
-                anyhow::bail!("VP9 not yet supported")
+            } else if let Some(video) = codec::video(&raw, id)? {
+                // hev1/hvc1, av01 and vp09 are parsed from the raw sample entry.
+                selection_params.codec = Some(video.codec);
+                selection_params.width = Some(video.width.into());
+                selection_params.height = Some(video.height.into());
+                selection_params.framerate = self.framerate;
+                selection_params.bitrate = self.bitrate;
+            } else if let Some(audio) = codec::audio(&raw, id)? {
+                // Opus, ac-3, ec-3 and fLaC are parsed from the raw sample entry.
+                selection_params.codec = Some(audio.codec);
+                selection_params.channel_config = Some(audio.channel_count.to_string());
+                selection_params.samplerate = Some(audio.samplerate);
+                selection_params.bitrate = audio.bitrate;
             } else {
-                // TODO add av01 support: https://github.com/gpac/mp4box.js/blob/325741b592d910297bf609bc7c400fc76101077b/src/box-codecs.js#L251
                 anyhow::bail!("unknown codec for track: {}", trak.tkhd.track_id);
             }
 
+            // Estimate anything that wasn't provided or found in the sample entry.
+            let estimator = Estimator::new(
+                timescale,
+                handler == TrackType::Video && selection_params.framerate.is_none(),
+                selection_params.bitrate.is_none(),
+            );
+
+            // Used when the fragments don't signal the sample duration or size.
+            let trex = moov
+                .mvex
+                .as_ref()
+                .filter(|mvex| mvex.trex.track_id == id)
+                .map(|mvex| SampleDefaults {
+                    duration: mvex.trex.default_sample_duration,
+                    size: mvex.trex.default_sample_size,
+                });
+
+            // LOC sends the video decoder configuration with each keyframe.
+            let config = match (&self.packaging, handler) {
+                (TrackPackaging::Loc, TrackType::Video) => {
+                    codec::decoder_config(&raw, id)?.map(Bytes::from)
+                }
+                _ => None,
+            };
+
             track.selection_params = selection_params;
 
             tracks.push(track);
 
             // Store the track publisher in a map so we can update it later.
-            let track = self.broadcast.create(&name).context("broadcast closed")?;
-            let track = Track::new(track, handler, timescale);
+            let track = self.broadcast.create(&name)?;
+            let mut track = Track::new(track, handler, timescale, estimator, trex);
+            if self.packaging == TrackPackaging::Loc {
+                track = track.with_loc(config);
+            }
             self.tracks.insert(id, track);
         }
 
-        let catalog = moq_catalog::Root {
-            version: 1,
-            streaming_format: 1,
-            streaming_format_version: "0.2".to_string(),
-            streaming_delta_updates: true,
-            common_track_fields: moq_catalog::CommonTrackFields::from_tracks(&mut tracks),
-            tracks,
-        };
-
-        let catalog_str = serde_json::to_string_pretty(&catalog)?;
-
-        log::info!("catalog: {}", catalog_str);
-
-        // Create a single fragment for the segment.
-        self.catalog.append(0)?.write(catalog_str.into())?;
-
-        Ok(())
+        self.broadcast.publish(self.index, tracks)
     }
 }
 
-// Find the next full atom in the buffer.
-// TODO return the amount of data still needed in Err?
-fn next_atom<B: Buf>(buf: &mut B) -> anyhow::Result<Option<Bytes>> {
-    let mut peek = Cursor::new(buf.chunk());
-
-    if peek.remaining() < 8 {
-        if buf.remaining() != buf.chunk().len() {
-            // TODO figure out a way to peek at the first 8 bytes
-            anyhow::bail!("TODO: vectored Buf not yet supported");
-        }
-
-        return Ok(None);
-    }
-
-    // Convert the first 4 bytes into the size.
-    let size = peek.get_u32();
-    let _type = peek.get_u32();
-
-    let size = match size {
-        // Runs until the end of the file.
-        0 => anyhow::bail!("TODO: unsupported EOF atom"),
-
-        // The next 8 bytes are the extended size to be used instead.
-        1 => {
-            let size_ext = peek.get_u64();
-            anyhow::ensure!(size_ext >= 16, "impossible extended box size: {}", size_ext);
-            size_ext as usize
-        }
-
-        2..=7 => {
-            anyhow::bail!("impossible box size: {}", size)
-        }
-
-        size => size as usize,
-    };
-
-    if buf.remaining() < size {
-        return Ok(None);
-    }
-
-    let atom = buf.copy_to_bytes(size);
-
-    Ok(Some(atom))
-}
-
 struct Track {
+    // The name of the track in the catalog
+    name: String,
+
     // The track we're producing
     track: SubgroupsWriter,
 
@@ -328,22 +381,65 @@ struct Track {
 
     // The type of track, ex. "vide" or "soun"
     handler: TrackType,
+
+    // Measures the frame rate and bitrate of the track.
+    estimator: Estimator,
+
+    // The sample defaults when not signalled in the fragment.
+    trex: Option<SampleDefaults>,
+
+    // The sample count, duration and size of the fragment being received.
+    pending: Option<(u32, u64, usize)>,
+
+    // Set when publishing each sample as a LOC object, with the optional decoder configuration.
+    loc: Option<Option<Bytes>>,
+
+    // The fragment waiting for its mdat, when using LOC.
+    fragment: Option<Fragment>,
 }
 
 impl Track {
-    fn new(track: TrackWriter, handler: TrackType, timescale: u64) -> Self {
+    fn new(
+        track: TrackWriter,
+        handler: TrackType,
+        timescale: u64,
+        estimator: Estimator,
+        trex: Option<SampleDefaults>,
+    ) -> Self {
         Self {
+            name: track.name.clone(),
             track: track.subgroups().unwrap(),
             current: None,
             timescale,
             handler,
+            estimator,
+            trex,
+            pending: None,
+            loc: None,
+            fragment: None,
         }
     }
 
+    // Publish each sample as a LOC object, including the decoder configuration with each keyframe.
+    fn with_loc(mut self, config: Option<Bytes>) -> Self {
+        self.loc = Some(config);
+        self
+    }
+
     pub fn header(&mut self, raw: Bytes, fragment: Fragment) -> anyhow::Result<()> {
+        // Measure the fragment once the mdat has been received.
+        let duration = fragment.duration.or_else(|| {
+            self.trex
+                .map(|trex| trex.duration as u64 * fragment.samples as u64)
+        });
+        self.pending = duration.map(|duration| (fragment.samples, duration, raw.len()));
+
         if let Some(current) = self.current.as_mut() {
             // Use the existing segment
-            current.write(raw)?;
+            match self.loc {
+                Some(_) => self.fragment = Some(fragment),
+                None => current.write(raw)?,
+            }
             return Ok(());
         }
 
@@ -367,8 +463,11 @@ impl Track {
             fragment.timestamp, segment.info.group_id, segment.info.subgroup_id, priority
         );
 
-        // Write the fragment in it's own object.
-        segment.write(raw)?;
+        // Write the fragment in it's own object, or wait for the mdat when using LOC.
+        match self.loc {
+            Some(_) => self.fragment = Some(fragment),
+            None => segment.write(raw)?,
+        }
 
         // Save for the next iteration
         self.current = Some(segment);
@@ -376,16 +475,80 @@ impl Track {
         Ok(())
     }
 
-    pub fn data(&mut self, raw: Bytes) -> anyhow::Result<()> {
-        let segment = self.current.as_mut().context("missing current fragment")?;
-        segment.write(raw)?;
+    /// Publish the mdat, returning a new estimate of the track's properties if it changed.
+    pub fn data(&mut self, raw: Bytes) -> anyhow::Result<Option<Estimate>> {
+        let size = raw.len();
 
-        Ok(())
+        match self.loc {
+            Some(_) => self.write_samples(raw)?,
+            None => {
+                let segment = self.current.as_mut().context("missing current fragment")?;
+                segment.write(raw)?;
+            }
+        }
+
+        let estimate = self.pending.take().and_then(|(samples, duration, header)| {
+            self.estimator.record(samples, duration, header + size)
+        });
+
+        Ok(estimate)
     }
 
     pub fn end_group(&mut self) {
         self.current = None;
     }
+
+    // Split the mdat into samples, publishing each as a LOC object.
+    fn write_samples(&mut self, mdat: Bytes) -> anyhow::Result<()> {
+        let fragment = self.fragment.take().context("missing moof")?;
+        let segment = self.current.as_mut().context("missing current fragment")?;
+        let config = self.loc.as_ref().context("not using LOC")?;
+
+        // Skip the mdat header, using the extended size if needed.
+        let header = match mdat.get(..4) {
+            Some([0, 0, 0, 1]) => 16,
+            _ => 8,
+        };
+        let mut payload = mdat.slice(header.min(mdat.len())..);
+
+        let mut decode_time = fragment.timestamp;
+        for sample in &fragment.table {
+            let size = sample
+                .size
+                .or(self.trex.map(|trex| trex.size))
+                .context("unknown sample size")? as usize;
+            let duration = sample
+                .duration
+                .or(self.trex.map(|trex| trex.duration))
+                .unwrap_or_default() as u64;
+            let cts = sample.cts;
+
+            anyhow::ensure!(payload.len() >= size, "mdat smaller than samples");
+            let sample = payload.split_to(size);
+
+            // The presentation and decode timestamps in microseconds.
+            // A negative composition offset can't move the presentation time before the start of the track.
+            let presentation = decode_time.saturating_add_signed(cts);
+            let timestamp = presentation * 1_000_000 / self.timescale;
+            let decode_timestamp = decode_time * 1_000_000 / self.timescale;
+            decode_time += duration;
+
+            let mut headers = ExtensionHeaders::new();
+            headers.set_intvalue(loc::TIMESTAMP, timestamp);
+            if decode_timestamp != timestamp {
+                headers.set_intvalue(loc::DECODE_TIMESTAMP, decode_timestamp);
+            }
+
+            // Each group starts with a keyframe, so include the decoder configuration.
+            if let Some(config) = config.as_ref().filter(|_| segment.is_empty()) {
+                headers.set_bytesvalue(loc::VIDEO_CONFIG, config.to_vec());
+            }
+
+            segment.create(sample.len(), Some(headers))?.write(sample)?;
+        }
+
+        Ok(())
+    }
 }
 
 struct Fragment {
@@ -397,6 +560,31 @@ struct Fragment {
 
     // True if this fragment is a keyframe.
     keyframe: bool,
+
+    // The number of samples in this fragment.
+    samples: u32,
+
+    // The total duration of the samples, in timescale units, if signalled in the fragment.
+    duration: Option<u64>,
+
+    // The size and timing of each sample, used to split the mdat into samples.
+    table: Vec<Sample>,
+}
+
+// A single sample in a fragment, falling back to the trex defaults when not signalled.
+struct Sample {
+    size: Option<u32>,
+    duration: Option<u32>,
+
+    // The composition time offset, which may be negative with a version 1 trun.
+    cts: i64,
+}
+
+// The sample defaults for a track, from the trex box.
+#[derive(Clone, Copy)]
+struct SampleDefaults {
+    duration: u32,
+    size: u32,
 }
 
 impl Fragment {
@@ -411,10 +599,17 @@ impl Fragment {
         // Detect if we should start a new segment.
         let keyframe = sample_keyframe(&moof);
 
+        let (samples, duration) = sample_duration(&moof);
+
+        let table = sample_table(&moof);
+
         Ok(Self {
             track,
             timestamp,
             keyframe,
+            samples,
+            duration,
+            table,
         })
     }
 
@@ -428,6 +623,67 @@ fn sample_timestamp(moof: &mp4::MoofBox) -> Option<u64> {
     Some(moof.trafs.first()?.tfdt.as_ref()?.base_media_decode_time)
 }
 
+// Returns the size, duration and composition offset of each sample, using the tfhd defaults if needed.
+fn sample_table(moof: &mp4::MoofBox) -> Vec<Sample> {
+    let traf = match moof.trafs.first() {
+        Some(traf) => traf,
+        None => return Vec::new(),
+    };
+    let trun = match &traf.trun {
+        Some(trun) => trun,
+        None => return Vec::new(),
+    };
+
+    (0..trun.sample_count as usize)
+        .map(|i| Sample {
+            size: trun
+                .sample_sizes
+                .get(i)
+                .copied()
+                .or(traf.tfhd.default_sample_size),
+            duration: trun
+                .sample_durations
+                .get(i)
+                .copied()
+                .or(traf.tfhd.default_sample_duration),
+            cts: trun
+                .sample_cts
+                .get(i)
+                .map(|&cts| match trun.version {
+                    0 => cts as i64,
+                    _ => cts as i32 as i64,
+                })
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+// Returns the number of samples and their total duration, if signalled in the trun or tfhd.
+fn sample_duration(moof: &mp4::MoofBox) -> (u32, Option<u64>) {
+    let mut samples = 0;
+    let mut duration = Some(0u64);
+
+    for traf in &moof.trafs {
+        let trun = match &traf.trun {
+            Some(t) => t,
+            None => continue,
+        };
+
+        samples += trun.sample_count;
+
+        duration = match trun.sample_durations.is_empty() {
+            false => {
+                duration.map(|d| d + trun.sample_durations.iter().map(|&d| d as u64).sum::<u64>())
+            }
+            true => duration
+                .zip(traf.tfhd.default_sample_duration)
+                .map(|(d, default)| d + default as u64 * trun.sample_count as u64),
+        };
+    }
+
+    (samples, duration)
+}
+
 fn sample_keyframe(moof: &mp4::MoofBox) -> bool {
     for traf in &moof.trafs {
         // TODO trak default flags if this is None
@@ -443,8 +699,10 @@ fn sample_keyframe(moof: &mp4::MoofBox) -> bool {
                 None => default_flags,
             };
 
-            if i == 0 && trun.first_sample_flags.is_some() {
-                flags = trun.first_sample_flags.unwrap();
+            if i == 0 {
+                if let Some(first) = trun.first_sample_flags {
+                    flags = first;
+                }
             }
 
             // https://chromium.googlesource.com/chromium/src/media/+/master/formats/mp4/track_run_iterator.cc#177
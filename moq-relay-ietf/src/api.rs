@@ -1,24 +1,83 @@
+use std::time;
+
+use rand::Rng;
 use url::Url;
 
+// The TTL assumed if moq-api doesn't return one, ex. an older version.
+const DEFAULT_TTL: time::Duration = time::Duration::from_secs(600);
+
+// The delay before retrying a failed refresh, doubled after each failure up to the refresh interval.
+const RETRY_DELAY: time::Duration = time::Duration::from_secs(1);
+
+/// How long origin leases last and how often they're refreshed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LeaseConfig {
+    /// The requested TTL; moq-api may grant a different one.
+    /// Defaults to the moq-api default.
+    pub ttl: Option<time::Duration>,
+
+    /// How often to refresh the lease.
+    /// Defaults to half of the granted TTL, and must be shorter than it.
+    pub refresh: Option<time::Duration>,
+}
+
 /// API client for moq-api.
 #[derive(Clone)]
 pub struct Api {
     client: moq_api::Client,
     origin: moq_api::Origin,
+    lease: LeaseConfig,
 }
 
 impl Api {
-    pub fn new(url: Url, node: Url) -> Self {
-        let origin = moq_api::Origin { url: node };
-        let client = moq_api::Client::new(url);
+    pub fn new(
+        url: Url,
+        node: Url,
+        config: moq_api::ClientConfig,
+        lease: LeaseConfig,
+    ) -> Result<Self, moq_api::ApiError> {
+        let origin = moq_api::Origin::new(node);
+        let client = moq_api::Client::with_config(url, config)?;
 
-        Self { client, origin }
+        Ok(Self {
+            client,
+            origin,
+            lease,
+        })
     }
 
-    /// Set the origin for a given namespace, returning a refresher.
+    /// Lease the given namespace, returning a refresher.
+    ///
+    /// If we already hold the lease, ex. after a restart, it's taken over instead of waiting for it to expire.
     pub async fn set_origin(&self, namespace: String) -> Result<Refresh, moq_api::ApiError> {
-        let refresh = Refresh::new(self.client.clone(), self.origin.clone(), namespace);
-        refresh.update().await?;
+        log::debug!(
+            "registering origin: namespace={} url={}",
+            namespace,
+            self.origin.url
+        );
+
+        let mut refresh = Refresh {
+            client: self.client.clone(),
+            origin: self.origin.clone(),
+            config: self.lease,
+            namespace,
+            token: 0,
+            interval: DEFAULT_TTL / 2,
+            released: false,
+        };
+
+        match refresh.create(false).await {
+            Err(moq_api::ApiError::Conflict) => {
+                log::info!(
+                    "namespace already leased, attempting takeover: namespace={} url={}",
+                    refresh.namespace,
+                    self.origin.url
+                );
+                refresh.create(true).await?
+            }
+            res => res?,
+        };
+
         Ok(refresh)
     }
 
@@ -31,58 +90,298 @@ impl Api {
     }
 }
 
-/// Periodically refreshes the origin registration in moq-api.
+/// Periodically refreshes the origin lease in moq-api.
+///
+/// Call [Refresh::release] when done, otherwise the lease is released in the background on drop.
 pub struct Refresh {
     client: moq_api::Client,
     origin: moq_api::Origin,
+    config: LeaseConfig,
     namespace: String,
-    refresh: tokio::time::Interval,
+
+    /// The fencing token for our lease.
+    token: u64,
+
+    /// How often to refresh, based on the granted TTL.
+    interval: time::Duration,
+
+    released: bool,
 }
 
 impl Refresh {
-    fn new(client: moq_api::Client, origin: moq_api::Origin, namespace: String) -> Self {
-        // Refresh every 5 minutes
-        let duration = tokio::time::Duration::from_secs(300);
-        let mut refresh = tokio::time::interval(tokio::time::Duration::from_secs(300));
-        refresh.reset_after(duration); // skip the first tick
+    /// Create the lease, replacing our own lease if `takeover` is set.
+    async fn create(&mut self, takeover: bool) -> Result<(), moq_api::ApiError> {
+        let options = moq_api::LeaseOptions {
+            takeover,
+            ttl: self.config.ttl.map(|ttl| ttl.as_secs()),
+        };
 
-        Self {
-            client,
-            origin,
-            namespace,
-            refresh,
-        }
+        let lease = self
+            .client
+            .create_lease(&self.namespace, self.origin.clone(), options)
+            .await?;
+
+        let ttl = match lease.ttl {
+            0 => DEFAULT_TTL,
+            ttl => time::Duration::from_secs(ttl),
+        };
+
+        self.token = lease.token;
+        self.interval = refresh_interval(self.config.refresh, ttl);
+
+        log::debug!(
+            "leased origin: namespace={} token={} ttl={:?} refresh={:?}",
+            self.namespace,
+            self.token,
+            ttl,
+            self.interval
+        );
+
+        Ok(())
     }
 
-    /// Update the origin registration in moq-api.
-    async fn update(&self) -> Result<(), moq_api::ApiError> {
+    /// Refresh the origin lease in moq-api.
+    async fn update(&mut self) -> Result<(), moq_api::ApiError> {
         log::debug!(
-            "registering origin: namespace={} url={}",
+            "refreshing origin: namespace={} url={} token={}",
             self.namespace,
-            self.origin.url
+            self.origin.url,
+            self.token
         );
-        // Register the origin in moq-api.
-        self.client
-            .set_origin(&self.namespace, self.origin.clone())
-            .await
+
+        let refresh = moq_api::LeaseRefresh {
+            token: self.token,
+            origin: None,
+            ttl: self.config.ttl.map(|ttl| ttl.as_secs()),
+        };
+
+        match self.client.refresh_lease(&self.namespace, refresh).await {
+            // The lease expired, so create a new one.
+            // This fails if another origin leased the namespace in the meantime.
+            Err(moq_api::ApiError::NotFound) => {
+                log::warn!(
+                    "origin lease expired, registering again: namespace={}",
+                    self.namespace
+                );
+                self.create(false).await
+            }
+            res => res,
+        }
     }
 
-    /// Run the refresher loop.
+    /// Run the refresher loop, retrying failed refreshes with backoff.
+    ///
+    /// Returns an error if the lease was taken over by another origin, or we're no longer allowed to hold it.
     pub async fn run(&mut self) -> anyhow::Result<()> {
+        let mut failures = 0;
+
         loop {
-            self.refresh.tick().await;
-            self.update().await?;
+            let delay = retry_delay(failures, self.interval);
+            tokio::time::sleep(jitter(delay)).await;
+
+            match self.update().await {
+                Ok(()) => {
+                    if failures > 0 {
+                        log::info!(
+                            "refreshed origin after {} failures: namespace={}",
+                            failures,
+                            self.namespace
+                        );
+                    }
+                    failures = 0;
+                }
+                Err(err @ (moq_api::ApiError::Conflict | moq_api::ApiError::Forbidden)) => {
+                    anyhow::bail!(
+                        "lost origin lease: namespace={} err={}",
+                        self.namespace,
+                        err
+                    )
+                }
+                Err(err) => {
+                    failures += 1;
+                    log::warn!(
+                        "failed to refresh origin, retrying: namespace={} failures={} err={}",
+                        self.namespace,
+                        failures,
+                        err
+                    );
+                }
+            }
+        }
+    }
+
+    /// Release the lease immediately, so other relays stop routing to us.
+    pub async fn release(mut self) {
+        self.released = true;
+
+        log::debug!(
+            "removing origin: namespace={} token={}",
+            self.namespace,
+            self.token
+        );
+
+        if let Err(err) = self.client.delete_origin(&self.namespace, self.token).await {
+            log::warn!(
+                "failed to remove origin: namespace={} err={}",
+                self.namespace,
+                err
+            );
         }
     }
 }
 
-/// Unregister the origin on drop.
+// Refresh at the requested interval if it's shorter than the granted TTL, otherwise at half of the TTL.
+fn refresh_interval(requested: Option<time::Duration>, ttl: time::Duration) -> time::Duration {
+    match requested {
+        Some(refresh) if refresh < ttl => refresh,
+        Some(refresh) => {
+            log::warn!(
+                "refresh interval exceeds the granted TTL, using half instead: refresh={:?} ttl={:?}",
+                refresh,
+                ttl
+            );
+            ttl / 2
+        }
+        None => ttl / 2,
+    }
+}
+
+// The delay before the next refresh, backing off after consecutive failures.
+fn retry_delay(failures: u32, interval: time::Duration) -> time::Duration {
+    match failures {
+        0 => interval,
+        n => (RETRY_DELAY * 2u32.saturating_pow(n - 1)).min(interval),
+    }
+}
+
+// Shorten the delay by up to 10%, so refreshes for many namespaces don't happen in lockstep.
+fn jitter(delay: time::Duration) -> time::Duration {
+    delay.mul_f64(rand::thread_rng().gen_range(0.9..=1.0))
+}
+
+/// Release the origin lease on drop, if it wasn't already released.
 impl Drop for Refresh {
     fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+
         // TODO this is really lazy
         let namespace = self.namespace.clone();
         let client = self.client.clone();
-        log::debug!("removing origin: namespace={}", namespace,);
-        tokio::spawn(async move { client.delete_origin(&namespace).await });
+        let token = self.token;
+        log::debug!("removing origin: namespace={} token={}", namespace, token);
+        tokio::spawn(async move { client.delete_origin(&namespace, token).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    const SECOND: time::Duration = time::Duration::from_secs(1);
+
+    // Serve moq-api on a random port, storing leases in memory.
+    async fn serve() -> Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let store = Arc::new(moq_api::MemoryStore::new());
+        tokio::spawn(moq_api::Server::serve(listener, store));
+
+        url.parse().unwrap()
+    }
+
+    fn api(url: &Url, node: &str, lease: LeaseConfig) -> Api {
+        Api::new(
+            url.clone(),
+            node.parse().unwrap(),
+            Default::default(),
+            lease,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn interval() {
+        assert_eq!(refresh_interval(None, 60 * SECOND), 30 * SECOND);
+        assert_eq!(
+            refresh_interval(Some(10 * SECOND), 60 * SECOND),
+            10 * SECOND
+        );
+
+        // The lease would expire before being refreshed.
+        assert_eq!(
+            refresh_interval(Some(60 * SECOND), 60 * SECOND),
+            30 * SECOND
+        );
+        assert_eq!(
+            refresh_interval(Some(90 * SECOND), 60 * SECOND),
+            30 * SECOND
+        );
+    }
+
+    #[test]
+    fn backoff() {
+        let interval = 30 * SECOND;
+        let delays: Vec<_> = (0..8).map(|n| retry_delay(n, interval)).collect();
+        assert_eq!(
+            delays,
+            [30, 1, 2, 4, 8, 16, 30, 30].map(|secs| secs * SECOND)
+        );
+
+        // The delay doesn't overflow after many failures.
+        assert_eq!(retry_delay(u32::MAX, interval), interval);
+
+        for _ in 0..100 {
+            let delay = jitter(10 * SECOND);
+            assert!(delay >= 9 * SECOND && delay <= 10 * SECOND, "{delay:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn lease() {
+        let url = serve().await;
+
+        // moq-api raises the requested TTL to its minimum of 10 seconds.
+        let config = LeaseConfig {
+            ttl: Some(5 * SECOND),
+            refresh: None,
+        };
+        let relay = api(&url, "https://a.example", config);
+        let mut first = relay.set_origin("foo".to_string()).await.unwrap();
+        assert_eq!(first.interval, 5 * SECOND);
+        first.update().await.unwrap();
+
+        // The requested refresh interval is used if it's shorter than the TTL.
+        let config = LeaseConfig {
+            ttl: Some(60 * SECOND),
+            refresh: Some(20 * SECOND),
+        };
+        let bar = api(&url, "https://a.example", config)
+            .set_origin("bar".to_string())
+            .await
+            .unwrap();
+        assert_eq!(bar.interval, 20 * SECOND);
+        bar.release().await;
+
+        // Another relay can't lease the namespace.
+        let other = api(&url, "https://b.example", LeaseConfig::default());
+        let res = other.set_origin("foo".to_string()).await;
+        assert!(matches!(res, Err(moq_api::ApiError::Conflict)));
+
+        // We take over our own lease, ex. after a restart, which fences the old token.
+        let second = relay.set_origin("foo".to_string()).await.unwrap();
+        assert!(second.token > first.token);
+        assert!(matches!(
+            first.update().await,
+            Err(moq_api::ApiError::Conflict)
+        ));
+        first.released = true;
+
+        second.release().await;
+        assert_eq!(relay.get_origin("foo").await.unwrap(), None);
     }
 }
@@ -1,11 +1,11 @@
 use anyhow::Context;
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use moq_transport::{
-    serve::Tracks,
+    serve::{Tracks, TracksReader, TracksRequest},
     session::{Announced, SessionError, Subscriber},
 };
 
-use crate::{Api, Locals, Producer};
+use crate::{Api, Locals, Producer, Refresh};
 
 /// Consumer of tracks from a remote Publisher
 #[derive(Clone)]
@@ -58,23 +58,49 @@ impl Consumer {
     }
 
     /// Serve an announce request.
-    async fn serve(mut self, mut announce: Announced) -> Result<(), anyhow::Error> {
-        let mut tasks = FuturesUnordered::new();
-
+    async fn serve(mut self, announce: Announced) -> Result<(), anyhow::Error> {
         // Produce the tracks for this announce and return the reader
-        let (_, mut request, reader) = Tracks::new(announce.namespace.clone()).produce();
+        let (_, request, reader) = Tracks::new(announce.namespace.clone()).produce();
+
+        // Register the local tracks, unregister on drop
+        // This happens first so a duplicate announce doesn't take over our own origin lease.
+        let _register = self.locals.register(reader.clone()).await?;
+
+        // Lease the namespace in the API, if any
+        let mut refresh = match self.api.as_ref() {
+            Some(api) => Some(api.set_origin(reader.namespace.to_utf8_path()).await?),
+            None => None,
+        };
+
+        let res = self
+            .serve_tracks(announce, request, reader, refresh.as_mut())
+            .await;
+
+        // Release the lease now, rather than letting it point at us until it expires.
+        if let Some(refresh) = refresh {
+            refresh.release().await;
+        }
+
+        res
+    }
+
+    /// Serve subscribe requests for an announce, while refreshing the origin lease.
+    async fn serve_tracks(
+        self,
+        mut announce: Announced,
+        mut request: TracksRequest,
+        reader: TracksReader,
+        refresh: Option<&mut Refresh>,
+    ) -> Result<(), anyhow::Error> {
+        let mut tasks = FuturesUnordered::new();
 
         // Start refreshing the API origin, if any
-        if let Some(api) = self.api.as_ref() {
-            let mut refresh = api.set_origin(reader.namespace.to_utf8_path()).await?;
+        if let Some(refresh) = refresh {
             tasks.push(
                 async move { refresh.run().await.context("failed refreshing origin") }.boxed(),
             );
         }
 
-        // Register the local tracks, unregister on drop
-        let _register = self.locals.register(reader.clone()).await?;
-
         // Accept the announce with an OK response
         announce.ok()?;
 
@@ -18,7 +18,8 @@ pub use remote::*;
 pub use session::*;
 pub use web::*;
 
-use std::{net, path::PathBuf};
+use anyhow::Context;
+use std::{fs, net, path::PathBuf, time};
 use url::Url;
 
 #[derive(Parser, Clone)]
@@ -35,9 +36,9 @@ pub struct Cli {
     #[arg(long)]
     pub qlog_dir: Option<PathBuf>,
 
-    /// Directory to write mlog files (one per connection)
-    #[arg(long)]
-    pub mlog_dir: Option<PathBuf>,
+    /// The mlog configuration.
+    #[command(flatten)]
+    pub mlog: moq_native_ietf::mlog::Args,
 
     /// Forward all announces to the provided server for authentication/routing.
     /// If not provided, the relay accepts every unique announce.
@@ -49,6 +50,32 @@ pub struct Cli {
     #[arg(long)]
     pub api: Option<Url>,
 
+    /// Present this bearer token to moq-api.
+    #[arg(long, requires = "api")]
+    pub api_token: Option<String>,
+
+    /// Present the certificate chain at this path to moq-api using mTLS, encoded as PEM.
+    #[arg(long, requires_all = ["api", "api_key"])]
+    pub api_cert: Option<PathBuf>,
+
+    /// The private key for --api-cert, encoded as PEM.
+    #[arg(long, requires = "api_cert")]
+    pub api_key: Option<PathBuf>,
+
+    /// Trust the moq-api certificate roots at this path, encoded as PEM, in addition to the default roots.
+    #[arg(long, requires = "api")]
+    pub api_root: Vec<PathBuf>,
+
+    /// Request that our origin leases expire after this many seconds unless refreshed.
+    /// Defaults to the moq-api default.
+    #[arg(long, requires = "api", value_parser = clap::value_parser!(u64).range(1..))]
+    pub api_ttl: Option<u64>,
+
+    /// Refresh our origin leases every this many seconds.
+    /// Defaults to half of the TTL granted by moq-api.
+    #[arg(long, requires = "api", value_parser = clap::value_parser!(u64).range(1..))]
+    pub api_refresh: Option<u64>,
+
     /// The hostname that we advertise to other origins.
     /// The provided certificate must be valid for this address.
     #[arg(long)]
@@ -64,12 +91,32 @@ pub struct Cli {
     #[arg(long)]
     pub qlog_serve: bool,
 
-    /// Serve mlog files over HTTPS at /mlog/:cid
+    /// Serve mlog files over HTTPS at /mlog/:cid, and list rotated files at /mlog/:cid/segments
     /// Requires --dev to enable the web server. Only serves files by exact CID - no index.
     #[arg(long)]
     pub mlog_serve: bool,
 }
 
+impl Cli {
+    // Load the credentials presented to moq-api.
+    fn api_config(&self) -> anyhow::Result<moq_api::ClientConfig> {
+        let read = |path: &PathBuf| {
+            fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+        };
+
+        let identity = match (&self.api_cert, &self.api_key) {
+            (Some(cert), Some(key)) => Some([read(cert)?, read(key)?].concat()),
+            _ => None,
+        };
+
+        Ok(moq_api::ClientConfig {
+            token: self.api_token.clone(),
+            identity,
+            roots: self.api_root.iter().map(read).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     env_logger::init();
@@ -82,6 +129,7 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
     let tls = cli.tls.load()?;
+    let api_config = cli.api_config()?;
 
     if tls.server.is_none() {
         anyhow::bail!("missing TLS certificates");
@@ -95,10 +143,9 @@ async fn main() -> anyhow::Result<()> {
         None
     };
 
-    // Determine mlog directory for both relay and web server
-    let mlog_dir_for_relay = cli.mlog_dir.clone();
+    // Determine mlog directory for the web server
     let mlog_dir_for_web = if cli.mlog_serve {
-        cli.mlog_dir.clone()
+        cli.mlog.mlog_dir.clone()
     } else {
         None
     };
@@ -108,9 +155,14 @@ async fn main() -> anyhow::Result<()> {
         tls: tls.clone(),
         bind: cli.bind,
         qlog_dir: qlog_dir_for_relay,
-        mlog_dir: mlog_dir_for_relay,
+        mlog: cli.mlog,
         node: cli.node,
         api: cli.api,
+        api_config,
+        api_lease: LeaseConfig {
+            ttl: cli.api_ttl.map(time::Duration::from_secs),
+            refresh: cli.api_refresh.map(time::Duration::from_secs),
+        },
         announce: cli.announce,
     })?;
 
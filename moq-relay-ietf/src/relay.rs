@@ -4,9 +4,13 @@ use anyhow::Context;
 
 use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use moq_native_ietf::quic;
+use moq_transport::mlog::VantagePoint;
 use url::Url;
 
-use crate::{Api, Consumer, Locals, Producer, Remotes, RemotesConsumer, RemotesProducer, Session};
+use crate::{
+    Api, Consumer, LeaseConfig, Locals, Producer, Remotes, RemotesConsumer, RemotesProducer,
+    Session,
+};
 
 /// Configuration for the relay.
 pub struct RelayConfig {
@@ -19,8 +23,8 @@ pub struct RelayConfig {
     /// Directory to write qlog files (one per connection)
     pub qlog_dir: Option<PathBuf>,
 
-    /// Directory to write mlog files (one per connection)
-    pub mlog_dir: Option<PathBuf>,
+    /// Write mlog files (one per connection)
+    pub mlog: moq_native_ietf::mlog::Args,
 
     /// Forward all announcements to the (optional) URL.
     pub announce: Option<Url>,
@@ -28,6 +32,12 @@ pub struct RelayConfig {
     /// Connect to the HTTP moq-api at this URL.
     pub api: Option<Url>,
 
+    /// The credentials presented to moq-api.
+    pub api_config: moq_api::ClientConfig,
+
+    /// How long our origin leases last and how often they're refreshed.
+    pub api_lease: LeaseConfig,
+
     /// Our hostname which we advertise to other origins.
     /// We use QUIC, so the certificate must be valid for this address.
     pub node: Option<Url>,
@@ -37,7 +47,7 @@ pub struct RelayConfig {
 pub struct Relay {
     quic: quic::Endpoint,
     announce_url: Option<Url>,
-    mlog_dir: Option<PathBuf>,
+    mlog: moq_native_ietf::mlog::Args,
     locals: Locals,
     api: Option<Api>,
     remotes: Option<(RemotesProducer, RemotesConsumer)>,
@@ -53,20 +63,15 @@ impl Relay {
         })?;
 
         // Validate mlog directory if provided
-        if let Some(mlog_dir) = &config.mlog_dir {
-            if !mlog_dir.exists() {
-                anyhow::bail!("mlog directory does not exist: {}", mlog_dir.display());
-            }
-            if !mlog_dir.is_dir() {
-                anyhow::bail!("mlog path is not a directory: {}", mlog_dir.display());
-            }
+        config.mlog.validate()?;
+        if let Some(mlog_dir) = &config.mlog.mlog_dir {
             log::info!("mlog output enabled: {}", mlog_dir.display());
         }
 
         // Create an API client if we have the necessary configuration
         let api = if let (Some(url), Some(node)) = (config.api, config.node) {
             log::info!("using moq-api: url={} node={}", url, node);
-            Some(Api::new(url, node))
+            Some(Api::new(url, node, config.api_config, config.api_lease)?)
         } else {
             None
         };
@@ -85,7 +90,7 @@ impl Relay {
         Ok(Self {
             quic,
             announce_url: config.announce,
-            mlog_dir: config.mlog_dir,
+            mlog: config.mlog,
             api,
             locals,
             remotes,
@@ -107,7 +112,7 @@ impl Relay {
             log::info!("forwarding announces to {}", url);
 
             // Establish a QUIC connection to the forward URL
-            let (session, _quic_client_initial_cid) = self
+            let (session, _quic_client_initial_cid, transport) = self
                 .quic
                 .client
                 .connect(url)
@@ -116,7 +121,7 @@ impl Relay {
 
             // Create the MoQ session over the connection
             let (session, publisher, subscriber) =
-                moq_transport::session::Session::connect(session, None)
+                moq_transport::session::Session::connect(session, transport, None)
                     .await
                     .context("failed to establish forward session")?;
 
@@ -148,11 +153,14 @@ impl Relay {
             tokio::select! {
                 // Accept a new QUIC connection
                 res = server.accept() => {
-                    let (conn, connection_id) = res.context("failed to accept QUIC connection")?;
+                    let (conn, connection_id, transport) = res.context("failed to accept QUIC connection")?;
 
-                    // Construct mlog path from connection ID if mlog directory is configured
-                    let mlog_path = self.mlog_dir.as_ref()
-                        .map(|dir| dir.join(format!("{}_server.mlog", connection_id)));
+                    // Create the mlog for this connection ID if mlog directory is configured
+                    let mlog = self.mlog.create(&connection_id, "moq-relay", VantagePoint::Server)
+                        .unwrap_or_else(|err| {
+                            log::warn!("failed to create mlog: {}", err);
+                            None
+                        });
 
                     let locals = self.locals.clone();
                     let remotes = remotes.clone();
@@ -163,7 +171,7 @@ impl Relay {
                     tasks.push(async move {
 
                         // Create the MoQ session over the connection (setup handshake etc)
-                        let (session, publisher, subscriber) = match moq_transport::session::Session::accept(conn, mlog_path).await {
+                        let (session, publisher, subscriber) = match moq_transport::session::Session::accept(conn, transport, mlog).await {
                             Ok(session) => session,
                             Err(err) => {
                                 log::warn!("failed to accept MoQ session: {}", err);
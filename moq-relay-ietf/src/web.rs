@@ -64,8 +64,12 @@ impl Web {
 
         // Optionally add mlog serving endpoint
         if state.mlog_dir.is_some() {
-            app = app.route("/mlog/:cid", get(serve_mlog));
-            log::info!("mlog files available at /mlog/:cid");
+            app = app
+                .route("/mlog/:cid", get(serve_mlog))
+                .route("/mlog/:cid/segments", get(list_mlog));
+            log::info!(
+                "mlog files available at /mlog/:cid, rotated files listed at /mlog/:cid/segments"
+            );
         }
 
         // Add state and CORS layer
@@ -135,6 +139,24 @@ async fn serve_qlog(
     })
 }
 
+// Returns the file name for an mlog request, which may name a rotated file, ex. `{cid}_server.mlog.2`.
+fn mlog_filename(cid: &str) -> String {
+    let (base, index) = match cid.rsplit_once('.') {
+        Some((base, index)) if !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) => {
+            (base, Some(index))
+        }
+        _ => (cid, None),
+    };
+
+    // Strip _server.mlog suffix if present to get the base CID
+    let base_cid = base.strip_suffix("_server.mlog").unwrap_or(base);
+
+    match index {
+        Some(index) => format!("{}_server.mlog.{}", base_cid, index),
+        None => format!("{}_server.mlog", base_cid),
+    }
+}
+
 async fn serve_mlog(
     Path(cid): Path<String>,
     State(state): State<WebState>,
@@ -145,11 +167,8 @@ async fn serve_mlog(
         "Mlog serving not enabled".to_string(),
     ))?;
 
-    // Strip _server.mlog suffix if present to get the base CID
-    let base_cid = cid.strip_suffix("_server.mlog").unwrap_or(&cid);
-
     // Construct the expected filename
-    let filename = format!("{}_server.mlog", base_cid);
+    let filename = mlog_filename(&cid);
     let file_path = mlog_dir.join(&filename);
 
     // Security: Ensure the path is still within mlog_dir (prevent path traversal)
@@ -179,3 +198,52 @@ async fn serve_mlog(
         )
     })
 }
+
+// Lists the mlog files for a connection in the order they were written, one per line.
+// Each can be fetched from /mlog/:name.
+async fn list_mlog(
+    Path(cid): Path<String>,
+    State(state): State<WebState>,
+) -> Result<String, (StatusCode, String)> {
+    let mlog_dir = state.mlog_dir.as_ref().ok_or((
+        StatusCode::NOT_FOUND,
+        "Mlog serving not enabled".to_string(),
+    ))?;
+
+    let first = mlog_filename(&cid);
+
+    let mut entries = tokio::fs::read_dir(mlog_dir.as_ref()).await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Invalid mlog directory: {}", e),
+        )
+    })?;
+
+    // The first file has no index, and rotated files are numbered from 1.
+    let mut segments = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let index = match name.strip_prefix(&first) {
+            Some("") => 0,
+            Some(suffix) => match suffix.strip_prefix('.').and_then(|i| i.parse().ok()) {
+                Some(index) => index,
+                None => continue,
+            },
+            None => continue,
+        };
+
+        segments.push((index, name));
+    }
+
+    if segments.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Mlog file not found: {}", first),
+        ));
+    }
+
+    segments.sort();
+
+    let names: Vec<_> = segments.into_iter().map(|(_, name)| name).collect();
+    Ok(names.join("\n") + "\n")
+}
@@ -0,0 +1,179 @@
+use std::{collections::VecDeque, time::Duration};
+
+use moq_catalog::{Root, Track};
+
+use crate::select::sort_ladder;
+
+// The number of objects used to estimate the throughput.
+const WINDOW: usize = 16;
+
+// Smaller objects are ignored, since their download time is dominated by latency.
+const MIN_SAMPLE_SIZE: u64 = 4096;
+
+// Only switch to a rendition using at most this percent of the measured throughput.
+const SAFETY: u64 = 80;
+
+/// Estimates the network throughput from the time taken to download each object.
+///
+/// A live track can only be received as fast as it's produced, so the transfer time of individual
+/// objects is measured instead, since each object is sent as fast as the network allows.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    samples: VecDeque<(u64, Duration)>,
+}
+
+impl Throughput {
+    /// Record the size of an object and the time taken to receive it.
+    pub fn record(&mut self, bytes: u64, elapsed: Duration) {
+        if bytes < MIN_SAMPLE_SIZE || elapsed.is_zero() {
+            return;
+        }
+
+        if self.samples.len() == WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((bytes, elapsed));
+    }
+
+    /// The estimated throughput in bits per second, once enough objects have been measured.
+    pub fn estimate(&self) -> Option<u64> {
+        if self.samples.len() < WINDOW / 2 {
+            return None;
+        }
+
+        let bytes: u64 = self.samples.iter().map(|(bytes, _)| bytes).sum();
+        let elapsed: Duration = self.samples.iter().map(|(_, elapsed)| *elapsed).sum();
+
+        Some((bytes * 8 * 1_000_000 / elapsed.as_micros().max(1) as u64).max(1))
+    }
+}
+
+/// Chooses between the renditions of an alternate group based on the measured throughput.
+pub struct Abr {
+    // Sorted by increasing bit rate.
+    ladder: Vec<Track>,
+
+    // The name of the current rendition.
+    current: String,
+
+    throughput: Throughput,
+}
+
+impl Abr {
+    /// Start with the lowest bit rate rendition, switching up once the throughput is known.
+    pub fn new(ladder: Vec<Track>) -> Self {
+        let current = ladder
+            .first()
+            .map(|track| track.name.clone())
+            .unwrap_or_default();
+
+        Self {
+            ladder,
+            current,
+            throughput: Throughput::default(),
+        }
+    }
+
+    /// The current rendition.
+    pub fn current(&self) -> Option<&Track> {
+        self.ladder.iter().find(|track| track.name == self.current)
+    }
+
+    pub fn record(&mut self, bytes: u64, elapsed: Duration) {
+        self.throughput.record(bytes, elapsed);
+    }
+
+    /// Refresh the bit rates of the renditions from an updated catalog.
+    ///
+    /// Renditions that were removed from the catalog are never chosen again.
+    pub fn update(&mut self, catalog: &Root) {
+        self.ladder.retain_mut(|rendition| {
+            match catalog
+                .tracks
+                .iter()
+                .find(|track| track.name == rendition.name)
+            {
+                Some(track) => {
+                    rendition.selection_params.bitrate = track.selection_params.bitrate;
+                    true
+                }
+                None => false,
+            }
+        });
+
+        sort_ladder(&mut self.ladder);
+    }
+
+    /// Called at each group boundary, returning the rendition to switch to, if any.
+    pub fn switch(&mut self) -> Option<&Track> {
+        let budget = self.throughput.estimate()? * SAFETY / 100;
+
+        let bitrates = self
+            .ladder
+            .iter()
+            .enumerate()
+            .filter_map(|(index, track)| Some((index, track.selection_params.bitrate?)));
+
+        // The highest rendition within budget, or the lowest known one if none fit.
+        let mut target = None;
+        for (index, bitrate) in bitrates {
+            if target.is_none() || bitrate as u64 <= budget {
+                target = Some(index);
+            }
+        }
+
+        // The current rendition may have been removed from the catalog.
+        let target = match (target, self.current()) {
+            (Some(target), _) => &self.ladder[target],
+            (None, None) => self.ladder.first()?,
+            (None, Some(_)) => return None,
+        };
+
+        if target.name == self.current {
+            return None;
+        }
+
+        self.current = target.name.clone();
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str, bitrate: u32) -> Track {
+        let mut track = Track {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        track.selection_params.bitrate = Some(bitrate);
+        track
+    }
+
+    #[test]
+    fn switch_renditions() {
+        let mut abr = Abr::new(vec![
+            track("low", 1_000_000),
+            track("mid", 3_000_000),
+            track("high", 6_000_000),
+        ]);
+        assert_eq!(abr.current().unwrap().name, "low");
+
+        // Not enough samples yet.
+        assert!(abr.switch().is_none());
+
+        // 500KB in 1s, ie: 4Mbps, so 3.2Mbps is available.
+        for _ in 0..WINDOW {
+            abr.record(500_000, Duration::from_secs(1));
+        }
+        assert_eq!(abr.switch().unwrap().name, "mid");
+        assert!(abr.switch().is_none());
+
+        // The throughput drops to 800Kbps, below every rendition.
+        for _ in 0..WINDOW {
+            abr.record(100_000, Duration::from_secs(1));
+        }
+        assert_eq!(abr.switch().unwrap().name, "low");
+    }
+}
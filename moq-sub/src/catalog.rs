@@ -0,0 +1,75 @@
+use anyhow::Context;
+use moq_transport::serve::{
+    MixedReader, SubgroupObjectReader, SubgroupReader, SubgroupsReader, TrackReader,
+    TrackReaderMode,
+};
+
+/// Reads the catalog track, merging delta updates into a live catalog.
+///
+/// Each group starts with a full catalog, followed by JSON Patch delta updates.
+/// A newer group replaces the current one, since it starts with the full catalog again.
+pub struct CatalogReader {
+    groups: SubgroupsReader,
+    group: Option<SubgroupReader>,
+    merger: moq_catalog::Merger,
+}
+
+impl CatalogReader {
+    pub async fn new(track: TrackReader) -> anyhow::Result<Self> {
+        let groups = match track.mode().await? {
+            TrackReaderMode::Subgroups(groups)
+            | TrackReaderMode::Mixed(MixedReader {
+                subgroups: groups, ..
+            }) => groups,
+            _ => anyhow::bail!("expected catalog subgroups"),
+        };
+
+        Ok(Self {
+            groups,
+            group: None,
+            merger: moq_catalog::Merger::new(),
+        })
+    }
+
+    /// Returns the next version of the catalog, or None when the track has ended.
+    pub async fn next(&mut self) -> anyhow::Result<Option<moq_catalog::Root>> {
+        loop {
+            let group = match self.group.as_mut() {
+                Some(group) => group,
+                None => match self.groups.next().await? {
+                    Some(group) => self.group.insert(group),
+                    None => return Ok(None),
+                },
+            };
+
+            tokio::select! {
+                object = group.next() => match object? {
+                    Some(object) => {
+                        let object_id = object.object_id;
+                        let payload = recv_object(object).await?;
+                        let catalog = self
+                            .merger
+                            .push(object_id, &payload)
+                            .context("failed to decode catalog")?;
+
+                        return Ok(Some(catalog.clone()));
+                    }
+                    // Wait for the next group.
+                    None => self.group = None,
+                },
+                next = self.groups.next() => match next? {
+                    Some(next) => self.group = Some(next),
+                    None => return Ok(None),
+                },
+            }
+        }
+    }
+}
+
+async fn recv_object(mut object: SubgroupObjectReader) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(object.size);
+    while let Some(chunk) = object.read().await? {
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(buf)
+}
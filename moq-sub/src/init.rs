@@ -0,0 +1,266 @@
+//! Combine the tracks of multiple init segments into a single init segment.
+//!
+//! Each rendition in the catalog can reference its own init track, so the video and audio tracks
+//! that are played together may be described by different init segments.  The output only has
+//! room for one, so the chosen traks are copied into a single moov along with their trex boxes.
+use anyhow::Context;
+
+use crate::select::Kind;
+
+/// A parsed init segment, ie: the ftyp and moov atoms.
+#[derive(Debug, Clone)]
+pub struct Init {
+    ftyp: Vec<u8>,
+    mvhd: Vec<u8>,
+    traks: Vec<Trak>,
+    trexs: Vec<(u32, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone)]
+struct Trak {
+    id: u32,
+    handler: [u8; 4],
+    timescale: Option<u32>,
+    raw: Vec<u8>,
+}
+
+impl Init {
+    pub fn parse(buf: &[u8]) -> anyhow::Result<Self> {
+        let mut ftyp = None;
+        let mut moov = None;
+        for (name, payload, raw) in boxes(buf) {
+            match &name {
+                b"ftyp" => ftyp = Some(raw.to_vec()),
+                b"moov" => moov = Some(payload),
+                _ => {}
+            }
+        }
+
+        let ftyp = ftyp.context("missing ftyp atom")?;
+        let moov = moov.context("missing moov atom")?;
+
+        let mut mvhd = None;
+        let mut traks = Vec::new();
+        let mut trexs = Vec::new();
+
+        for (name, payload, raw) in boxes(moov) {
+            match &name {
+                b"mvhd" => mvhd = Some(raw.to_vec()),
+                b"trak" => traks.push(Trak {
+                    id: track_id(payload)?,
+                    handler: handler(payload)?,
+                    timescale: timescale(payload),
+                    raw: raw.to_vec(),
+                }),
+                b"mvex" => {
+                    for (name, payload, raw) in boxes(payload) {
+                        if &name == b"trex" {
+                            let id = payload.get(4..8).context("trex too small")?;
+                            trexs.push((u32::from_be_bytes(id.try_into()?), raw.to_vec()));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            ftyp,
+            mvhd: mvhd.context("missing mvhd atom")?,
+            traks,
+            trexs,
+        })
+    }
+
+    // The first trak of the given kind.
+    fn trak(&self, kind: Kind) -> Option<&Trak> {
+        let handler = match kind {
+            Kind::Video => b"vide",
+            Kind::Audio => b"soun",
+        };
+
+        self.traks.iter().find(|trak| &trak.handler == handler)
+    }
+
+    /// The timescale of the first trak of the given kind, from its mdhd.
+    pub fn timescale(&self, kind: Kind) -> Option<u32> {
+        self.trak(kind)?.timescale
+    }
+
+    /// The default sample duration of the first trak of the given kind, from its trex.
+    pub fn default_duration(&self, kind: Kind) -> Option<u32> {
+        let id = self.trak(kind)?.id;
+        let (_, trex) = self.trexs.iter().find(|(trex, _)| *trex == id)?;
+
+        // Skip the header, version, flags, track ID and sample description index.
+        let duration = trex.get(20..24)?;
+        Some(u32::from_be_bytes(duration.try_into().ok()?))
+    }
+}
+
+/// Build a single init segment containing the first trak of each kind from its init segment.
+///
+/// The ftyp and mvhd are taken from the first init segment.
+pub fn merge(parts: &[(Kind, &Init)]) -> anyhow::Result<Vec<u8>> {
+    let (_, first) = parts.first().context("no init segments")?;
+
+    let mut traks = Vec::new();
+    let mut trexs = Vec::new();
+
+    for (kind, init) in parts {
+        let trak = init
+            .trak(*kind)
+            .with_context(|| format!("missing {kind:?} trak"))?;
+
+        // We don't rewrite the fragments, so the track IDs must be unique.
+        anyhow::ensure!(
+            !traks.iter().any(|t: &&Trak| t.id == trak.id),
+            "conflicting track id: {}",
+            trak.id
+        );
+        traks.push(trak);
+
+        match init.trexs.iter().find(|(id, _)| *id == trak.id) {
+            Some((_, trex)) => trexs.extend_from_slice(trex),
+            None => write_box(&mut trexs, b"trex", |out| {
+                out.extend_from_slice(&[0; 4]); // version and flags
+                out.extend_from_slice(&trak.id.to_be_bytes());
+                out.extend_from_slice(&1u32.to_be_bytes()); // sample description index
+                out.extend_from_slice(&[0; 12]);
+            }),
+        }
+    }
+
+    let mut out = first.ftyp.clone();
+    write_box(&mut out, b"moov", |out| {
+        out.extend_from_slice(&first.mvhd);
+        for trak in traks {
+            out.extend_from_slice(&trak.raw);
+        }
+        write_box(out, b"mvex", |out| out.extend_from_slice(&trexs));
+    });
+
+    Ok(out)
+}
+
+// The track ID from the tkhd of a trak.
+fn track_id(trak: &[u8]) -> anyhow::Result<u32> {
+    let tkhd = child(trak, b"tkhd").context("missing tkhd")?;
+    let offset = match tkhd.first() {
+        Some(1) => 20,
+        _ => 12,
+    };
+
+    let id = tkhd.get(offset..offset + 4).context("tkhd too small")?;
+    Ok(u32::from_be_bytes(id.try_into()?))
+}
+
+// The timescale from the mdhd of a trak.
+fn timescale(trak: &[u8]) -> Option<u32> {
+    let mdhd = child(child(trak, b"mdia")?, b"mdhd")?;
+    let offset = match mdhd.first() {
+        Some(1) => 20,
+        _ => 12,
+    };
+
+    let timescale = mdhd.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes(timescale.try_into().ok()?))
+}
+
+// The handler type from the hdlr of a trak.
+fn handler(trak: &[u8]) -> anyhow::Result<[u8; 4]> {
+    let hdlr = child(trak, b"mdia")
+        .and_then(|mdia| child(mdia, b"hdlr"))
+        .context("missing hdlr")?;
+
+    let handler = hdlr.get(8..12).context("hdlr too small")?;
+    Ok(handler.try_into()?)
+}
+
+fn child<'a>(buf: &'a [u8], name: &[u8; 4]) -> Option<&'a [u8]> {
+    boxes(buf)
+        .find(|(n, _, _)| n == name)
+        .map(|(_, payload, _)| payload)
+}
+
+// Iterate over the boxes in a buffer, returning the name, payload and the entire box.
+// Stops at the first malformed box.
+pub(crate) fn boxes(mut buf: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8], &[u8])> {
+    std::iter::from_fn(move || {
+        let header = buf.get(..8)?;
+        let name: [u8; 4] = header[4..8].try_into().ok()?;
+
+        let (size, offset) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            0 => (buf.len(), 8),
+            1 => (
+                u64::from_be_bytes(buf.get(8..16)?.try_into().ok()?) as usize,
+                16,
+            ),
+            size => (size as usize, 8),
+        };
+
+        if size < offset || size > buf.len() {
+            return None;
+        }
+
+        let (raw, rest) = buf.split_at(size);
+        buf = rest;
+
+        Some((name, &raw[offset..], raw))
+    })
+}
+
+fn write_box(out: &mut Vec<u8>, name: &[u8; 4], payload: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(name);
+    payload(out);
+
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init(tracks: &[(u32, &[u8; 4])]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_box(&mut out, b"ftyp", |out| out.extend_from_slice(b"isom"));
+        write_box(&mut out, b"moov", |out| {
+            write_box(out, b"mvhd", |out| out.extend_from_slice(&[0; 100]));
+            for (id, handler) in tracks {
+                write_box(out, b"trak", |out| {
+                    write_box(out, b"tkhd", |out| {
+                        out.extend_from_slice(&[0; 12]);
+                        out.extend_from_slice(&id.to_be_bytes());
+                    });
+                    write_box(out, b"mdia", |out| {
+                        write_box(out, b"hdlr", |out| {
+                            out.extend_from_slice(&[0; 8]);
+                            out.extend_from_slice(*handler);
+                        });
+                    });
+                });
+            }
+        });
+        out
+    }
+
+    #[test]
+    fn merge_traks() {
+        let video = Init::parse(&init(&[(1, b"vide"), (2, b"soun")])).unwrap();
+        let audio = Init::parse(&init(&[(3, b"soun")])).unwrap();
+
+        let merged = merge(&[(Kind::Video, &video), (Kind::Audio, &audio)]).unwrap();
+        let merged = Init::parse(&merged).unwrap();
+
+        let ids: Vec<_> = merged.traks.iter().map(|trak| trak.id).collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(merged.trexs.len(), 2);
+
+        // The same track ID can't be used twice.
+        let conflict = Init::parse(&init(&[(1, b"soun")])).unwrap();
+        assert!(merge(&[(Kind::Video, &video), (Kind::Audio, &conflict)]).is_err());
+    }
+}
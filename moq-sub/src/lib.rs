@@ -1 +1,7 @@
+pub mod abr;
+pub mod catalog;
+pub mod init;
+pub mod loc;
 pub mod media;
+pub mod segment;
+pub mod select;
@@ -0,0 +1,882 @@
+//! Remux LOC tracks, where each object is a single encoded frame, into something a player understands.
+//!
+//! The timestamp and decoder configuration of each frame are carried in the object extension headers,
+//! see [moq_catalog::loc].  The frames are written either as fragmented MP4 (one moof+mdat per frame),
+//! or as an elementary stream: Annex-B for H.264/H.265 video and ADTS for AAC audio.
+use anyhow::Context;
+use moq_catalog::loc;
+use moq_transport::coding::Value;
+use moq_transport::data::ExtensionHeaders;
+
+// The timescale used for every track, since LOC timestamps are in microseconds.
+const TIMESCALE: u32 = 1_000_000;
+
+// The AAC sampling frequency index table.
+const AAC_SAMPLE_RATES: [u32; 13] = [
+    96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+];
+
+/// The format written to the output.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Output {
+    /// Fragmented MP4, with the video and audio tracks interleaved.
+    #[default]
+    Fmp4,
+
+    /// H.264 or H.265 Annex-B elementary stream of the video track.
+    Annexb,
+
+    /// AAC ADTS elementary stream of the audio track.
+    Adts,
+}
+
+impl Output {
+    /// Returns true if the output includes tracks of the given kind.
+    pub fn accepts(&self, video: bool) -> bool {
+        match self {
+            Output::Fmp4 => true,
+            Output::Annexb => video,
+            Output::Adts => !video,
+        }
+    }
+}
+
+/// A single encoded frame, decoded from a LOC object.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The presentation timestamp in microseconds.
+    pub timestamp: u64,
+
+    /// The decode timestamp in microseconds, which only differs from the presentation timestamp for reordered frames.
+    pub decode_timestamp: u64,
+
+    /// The decoder configuration, sent with the first frame of each group.
+    pub config: Option<Vec<u8>>,
+
+    /// The encoded frame.
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    pub fn new(headers: &ExtensionHeaders, payload: Vec<u8>) -> anyhow::Result<Self> {
+        let timestamp = match headers.get(loc::TIMESTAMP).map(|kvp| &kvp.value) {
+            Some(Value::IntValue(timestamp)) => *timestamp,
+            _ => anyhow::bail!("missing LOC timestamp"),
+        };
+
+        let decode_timestamp = match headers.get(loc::DECODE_TIMESTAMP).map(|kvp| &kvp.value) {
+            Some(Value::IntValue(decode_timestamp)) => *decode_timestamp,
+            _ => timestamp,
+        };
+
+        let config = match headers.get(loc::VIDEO_CONFIG).map(|kvp| &kvp.value) {
+            Some(Value::BytesValue(config)) => Some(config.clone()),
+            _ => None,
+        };
+
+        Ok(Self {
+            timestamp,
+            decode_timestamp,
+            config,
+            payload,
+        })
+    }
+}
+
+/// Remuxes the frames of one or more LOC tracks into a single output.
+pub struct Remuxer {
+    output: Output,
+    tracks: Vec<RemuxTrack>,
+
+    // Set once the init segment has been written, when using fMP4.
+    initialized: bool,
+
+    // The moof sequence number.
+    sequence: u32,
+}
+
+struct RemuxTrack {
+    kind: Kind,
+    width: u32,
+    height: u32,
+
+    // The decoder configuration, received with the first frame of each group for video.
+    config: Option<Vec<u8>>,
+
+    // The previous frame, written once the next frame tells us its duration.
+    pending: Option<Frame>,
+    duration: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Avc,
+    Hevc,
+    Av1,
+    Vp9,
+    Aac {
+        profile: u8,
+        samplerate: u32,
+        channels: u16,
+    },
+    Opus {
+        channels: u16,
+    },
+}
+
+impl Kind {
+    fn new(track: &moq_catalog::Track) -> anyhow::Result<Self> {
+        let params = &track.selection_params;
+        let codec = params.codec.as_deref().context("missing codec")?;
+        let channels = || -> anyhow::Result<u16> {
+            let channels = params.channel_config.as_deref().unwrap_or("2");
+            channels.parse().context("invalid channel config")
+        };
+
+        let kind = match codec.split('.').next().unwrap_or_default() {
+            "avc1" | "avc3" => Self::Avc,
+            "hev1" | "hvc1" => Self::Hevc,
+            "av01" => Self::Av1,
+            "vp09" => Self::Vp9,
+            "mp4a" => Self::Aac {
+                // ie: mp4a.40.2 for AAC-LC, which must fit in the 5 bit audio object type.
+                profile: codec
+                    .rsplit('.')
+                    .next()
+                    .and_then(|profile| profile.parse().ok())
+                    .filter(|profile| (1..31).contains(profile))
+                    .context("invalid AAC codec")?,
+                samplerate: params.samplerate.context("missing samplerate")?,
+                channels: channels()?,
+            },
+            "opus" => Self::Opus {
+                channels: channels()?,
+            },
+            _ => anyhow::bail!("unsupported LOC codec: {}", codec),
+        };
+
+        Ok(kind)
+    }
+
+    fn is_video(&self) -> bool {
+        matches!(self, Self::Avc | Self::Hevc | Self::Av1 | Self::Vp9)
+    }
+}
+
+impl Remuxer {
+    /// Create a remuxer for the given catalog tracks, which are referenced by index from now on.
+    pub fn new(output: Output, tracks: &[moq_catalog::Track]) -> anyhow::Result<Self> {
+        let tracks = tracks
+            .iter()
+            .map(|track| {
+                let kind = Kind::new(track)?;
+                anyhow::ensure!(
+                    output.accepts(kind.is_video()),
+                    "track {} is not supported by {:?}",
+                    track.name,
+                    output
+                );
+
+                Ok(RemuxTrack {
+                    kind,
+                    width: track.selection_params.width.unwrap_or_default(),
+                    height: track.selection_params.height.unwrap_or_default(),
+                    config: None,
+                    pending: None,
+                    duration: 0,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        match output {
+            Output::Fmp4 => anyhow::ensure!(!tracks.is_empty(), "no tracks to remux"),
+            Output::Annexb => anyhow::ensure!(
+                matches!(tracks.as_slice(), [t] if matches!(t.kind, Kind::Avc | Kind::Hevc)),
+                "Annex-B requires a single H.264 or H.265 track"
+            ),
+            Output::Adts => anyhow::ensure!(
+                matches!(tracks.as_slice(), [t] if matches!(t.kind, Kind::Aac { .. })),
+                "ADTS requires a single AAC track"
+            ),
+        }
+
+        Ok(Self {
+            output,
+            tracks,
+            initialized: false,
+            sequence: 0,
+        })
+    }
+
+    /// Remux a frame of the given track, returning the bytes to write to the output.
+    ///
+    /// Frames are dropped until the decoder configuration of every video track is known.
+    pub fn frame(&mut self, index: usize, frame: Frame) -> anyhow::Result<Vec<u8>> {
+        let track = self.tracks.get_mut(index).context("unknown track")?;
+        if let Some(config) = &frame.config {
+            track.config = Some(config.clone());
+        }
+
+        match self.output {
+            Output::Fmp4 => self.fmp4(index, frame),
+            Output::Annexb => annexb(track, &frame),
+            Output::Adts => adts(track.kind, &frame.payload),
+        }
+    }
+
+    fn fmp4(&mut self, index: usize, frame: Frame) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::new();
+
+        if !self.initialized {
+            let ready = self
+                .tracks
+                .iter()
+                .all(|track| !track.kind.is_video() || track.config.is_some());
+            if !ready {
+                return Ok(out);
+            }
+
+            self.init(&mut out)?;
+            self.initialized = true;
+        }
+
+        let track = &mut self.tracks[index];
+
+        // Video frames are only written once the track has started with a keyframe.
+        if track.kind.is_video() && track.pending.is_none() && frame.config.is_none() {
+            return Ok(out);
+        }
+
+        // Delay each frame by one, so we know its duration.
+        let previous = match track.pending.replace(frame) {
+            Some(previous) => previous,
+            None => return Ok(out),
+        };
+
+        // Frames are in decode order, so the duration is the difference between decode timestamps.
+        let next = track.pending.as_ref().unwrap().decode_timestamp;
+        match next.checked_sub(previous.decode_timestamp) {
+            Some(duration) if duration > 0 => track.duration = duration,
+            // Reuse the last duration if the timestamps don't increase.
+            _ => {}
+        }
+
+        let keyframe = !track.kind.is_video() || previous.config.is_some();
+        self.sequence += 1;
+
+        fragment(
+            &mut out,
+            self.sequence,
+            index as u32 + 1,
+            &previous,
+            self.tracks[index].duration,
+            keyframe,
+        );
+
+        Ok(out)
+    }
+
+    // Write the ftyp and moov atoms, describing every track.
+    fn init(&self, out: &mut Vec<u8>) -> anyhow::Result<()> {
+        write_box(out, b"ftyp", |out| {
+            out.extend_from_slice(b"isom");
+            out.extend_from_slice(&0x200u32.to_be_bytes());
+            out.extend_from_slice(b"isomiso6mp41");
+        });
+
+        let mut traks = Vec::new();
+        for (index, track) in self.tracks.iter().enumerate() {
+            trak(&mut traks, index as u32 + 1, track)?;
+        }
+
+        write_box(out, b"moov", |out| {
+            write_full_box(out, b"mvhd", 0, 0, |out| {
+                out.extend_from_slice(&[0; 8]); // creation and modification time
+                out.extend_from_slice(&1000u32.to_be_bytes());
+                out.extend_from_slice(&0u32.to_be_bytes()); // duration
+                out.extend_from_slice(&0x00010000u32.to_be_bytes()); // rate
+                out.extend_from_slice(&0x0100u16.to_be_bytes()); // volume
+                out.extend_from_slice(&[0; 10]);
+                out.extend_from_slice(&MATRIX);
+                out.extend_from_slice(&[0; 24]);
+                out.extend_from_slice(&(self.tracks.len() as u32 + 1).to_be_bytes());
+            });
+
+            out.extend_from_slice(&traks);
+
+            write_box(out, b"mvex", |out| {
+                for index in 0..self.tracks.len() {
+                    write_full_box(out, b"trex", 0, 0, |out| {
+                        out.extend_from_slice(&(index as u32 + 1).to_be_bytes());
+                        out.extend_from_slice(&1u32.to_be_bytes()); // sample description index
+                        out.extend_from_slice(&[0; 12]); // default duration, size and flags
+                    });
+                }
+            });
+        });
+
+        Ok(())
+    }
+}
+
+// The identity matrix used by mvhd and tkhd.
+const MATRIX: [u8; 36] = [
+    0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 0, 0, 0, 0, 0x40, 0, 0, 0,
+];
+
+fn trak(out: &mut Vec<u8>, id: u32, track: &RemuxTrack) -> anyhow::Result<()> {
+    let entry = sample_entry(track)?;
+    let video = track.kind.is_video();
+
+    write_box(out, b"trak", |out| {
+        write_full_box(out, b"tkhd", 0, 3, |out| {
+            out.extend_from_slice(&[0; 8]); // creation and modification time
+            out.extend_from_slice(&id.to_be_bytes());
+            out.extend_from_slice(&[0; 4]);
+            out.extend_from_slice(&0u32.to_be_bytes()); // duration
+            out.extend_from_slice(&[0; 8]);
+            out.extend_from_slice(&[0; 4]); // layer and alternate group
+            out.extend_from_slice(&(if video { 0u16 } else { 0x0100 }).to_be_bytes());
+            out.extend_from_slice(&[0; 2]);
+            out.extend_from_slice(&MATRIX);
+            out.extend_from_slice(&(track.width << 16).to_be_bytes());
+            out.extend_from_slice(&(track.height << 16).to_be_bytes());
+        });
+
+        write_box(out, b"mdia", |out| {
+            write_full_box(out, b"mdhd", 0, 0, |out| {
+                out.extend_from_slice(&[0; 8]); // creation and modification time
+                out.extend_from_slice(&TIMESCALE.to_be_bytes());
+                out.extend_from_slice(&0u32.to_be_bytes()); // duration
+                out.extend_from_slice(&0x55c4u16.to_be_bytes()); // und
+                out.extend_from_slice(&[0; 2]);
+            });
+
+            write_full_box(out, b"hdlr", 0, 0, |out| {
+                out.extend_from_slice(&[0; 4]);
+                out.extend_from_slice(if video { b"vide" } else { b"soun" });
+                out.extend_from_slice(&[0; 12]);
+                out.push(0); // empty name
+            });
+
+            write_box(out, b"minf", |out| {
+                match video {
+                    true => write_full_box(out, b"vmhd", 0, 1, |out| {
+                        out.extend_from_slice(&[0; 8]); // graphics mode and opcolor
+                    }),
+                    false => write_full_box(out, b"smhd", 0, 0, |out| {
+                        out.extend_from_slice(&[0; 4]); // balance
+                    }),
+                }
+
+                write_box(out, b"dinf", |out| {
+                    write_full_box(out, b"dref", 0, 0, |out| {
+                        out.extend_from_slice(&1u32.to_be_bytes());
+                        write_full_box(out, b"url ", 0, 1, |_| {});
+                    });
+                });
+
+                write_box(out, b"stbl", |out| {
+                    write_full_box(out, b"stsd", 0, 0, |out| {
+                        out.extend_from_slice(&1u32.to_be_bytes());
+                        out.extend_from_slice(&entry);
+                    });
+
+                    // Every sample is in the fragments.
+                    for name in [b"stts", b"stsc", b"stco"] {
+                        write_full_box(out, name, 0, 0, |out| {
+                            out.extend_from_slice(&0u32.to_be_bytes());
+                        });
+                    }
+                    write_full_box(out, b"stsz", 0, 0, |out| {
+                        out.extend_from_slice(&[0; 8]);
+                    });
+                });
+            });
+        });
+    });
+
+    Ok(())
+}
+
+// Build the sample entry, using the received decoder configuration for video.
+fn sample_entry(track: &RemuxTrack) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    let (name, config): (&[u8; 4], &[u8; 4]) = match track.kind {
+        Kind::Avc => (b"avc1", b"avcC"),
+        Kind::Hevc => (b"hev1", b"hvcC"),
+        Kind::Av1 => (b"av01", b"av1C"),
+        Kind::Vp9 => (b"vp09", b"vpcC"),
+        Kind::Aac {
+            profile,
+            samplerate,
+            channels,
+        } => {
+            let esds = esds(profile, samplerate, channels)?;
+            write_audio_entry(&mut out, b"mp4a", channels, samplerate, |out| {
+                write_box(out, b"esds", |out| out.extend_from_slice(&esds));
+            });
+            return Ok(out);
+        }
+        Kind::Opus { channels } => {
+            anyhow::ensure!(channels <= 2, "unsupported Opus channel count");
+            write_audio_entry(&mut out, b"Opus", channels, 48000, |out| {
+                write_box(out, b"dOps", |out| {
+                    out.push(0); // version
+                    out.push(channels as u8);
+                    out.extend_from_slice(&0u16.to_be_bytes()); // pre-skip
+                    out.extend_from_slice(&48000u32.to_be_bytes());
+                    out.extend_from_slice(&0u16.to_be_bytes()); // output gain
+                    out.push(0); // channel mapping family
+                });
+            });
+            return Ok(out);
+        }
+    };
+
+    let payload = track.config.as_ref().context("missing decoder config")?;
+
+    write_box(&mut out, name, |out| {
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+        out.extend_from_slice(&[0; 16]);
+        out.extend_from_slice(&(track.width as u16).to_be_bytes());
+        out.extend_from_slice(&(track.height as u16).to_be_bytes());
+        out.extend_from_slice(&0x00480000u32.to_be_bytes()); // 72 dpi
+        out.extend_from_slice(&0x00480000u32.to_be_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&1u16.to_be_bytes()); // frame count
+        out.extend_from_slice(&[0; 32]); // compressor name
+        out.extend_from_slice(&0x0018u16.to_be_bytes()); // depth
+        out.extend_from_slice(&0xffffu16.to_be_bytes());
+        write_box(out, config, |out| out.extend_from_slice(payload));
+    });
+
+    Ok(out)
+}
+
+fn write_audio_entry(
+    out: &mut Vec<u8>,
+    name: &[u8; 4],
+    channels: u16,
+    samplerate: u32,
+    config: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, name, |out| {
+        out.extend_from_slice(&[0; 6]);
+        out.extend_from_slice(&1u16.to_be_bytes()); // data reference index
+        out.extend_from_slice(&[0; 8]);
+        out.extend_from_slice(&channels.to_be_bytes());
+        out.extend_from_slice(&16u16.to_be_bytes()); // sample size
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(samplerate.min(u16::MAX.into()) << 16).to_be_bytes());
+        config(out);
+    });
+}
+
+// The payload of the esds box, containing the AudioSpecificConfig.
+fn esds(profile: u8, samplerate: u32, channels: u16) -> anyhow::Result<Vec<u8>> {
+    let index = aac_samplerate_index(samplerate)?;
+    let config = [
+        (profile << 3) | (index >> 1),
+        (index << 7) | ((channels as u8 & 0x0f) << 3),
+    ];
+
+    let mut decoder = vec![0x40, 0x15, 0, 0, 0]; // AAC audio, buffer size
+    decoder.extend_from_slice(&[0; 8]); // max and average bitrate
+    decoder.extend_from_slice(&[0x05, config.len() as u8]);
+    decoder.extend_from_slice(&config);
+
+    let mut es = vec![0, 0, 0]; // ES_ID and flags
+    es.extend_from_slice(&[0x04, decoder.len() as u8]);
+    es.extend_from_slice(&decoder);
+    es.extend_from_slice(&[0x06, 1, 2]); // SLConfigDescriptor
+
+    let mut out = vec![0, 0, 0, 0]; // version and flags
+    out.extend_from_slice(&[0x03, es.len() as u8]);
+    out.extend_from_slice(&es);
+
+    Ok(out)
+}
+
+fn aac_samplerate_index(samplerate: u32) -> anyhow::Result<u8> {
+    AAC_SAMPLE_RATES
+        .iter()
+        .position(|&rate| rate == samplerate)
+        .map(|index| index as u8)
+        .context("unsupported AAC samplerate")
+}
+
+// Write a moof+mdat containing a single sample.
+fn fragment(
+    out: &mut Vec<u8>,
+    sequence: u32,
+    id: u32,
+    frame: &Frame,
+    duration: u64,
+    keyframe: bool,
+) {
+    let flags: u32 = match keyframe {
+        true => 0x02000000,
+        false => 0x01010000,
+    };
+
+    let moof = |data_offset: u32| {
+        let mut out = Vec::new();
+        write_box(&mut out, b"moof", |out| {
+            write_full_box(out, b"mfhd", 0, 0, |out| {
+                out.extend_from_slice(&sequence.to_be_bytes());
+            });
+            write_box(out, b"traf", |out| {
+                // default-base-is-moof
+                write_full_box(out, b"tfhd", 0, 0x020000, |out| {
+                    out.extend_from_slice(&id.to_be_bytes());
+                });
+                write_full_box(out, b"tfdt", 1, 0, |out| {
+                    out.extend_from_slice(&frame.decode_timestamp.to_be_bytes());
+                });
+                // data offset, sample duration, size, flags and a signed composition offset
+                write_full_box(out, b"trun", 1, 0x000f01, |out| {
+                    out.extend_from_slice(&1u32.to_be_bytes());
+                    out.extend_from_slice(&data_offset.to_be_bytes());
+                    out.extend_from_slice(&(duration.min(u32::MAX.into()) as u32).to_be_bytes());
+                    out.extend_from_slice(&(frame.payload.len() as u32).to_be_bytes());
+                    out.extend_from_slice(&flags.to_be_bytes());
+                    out.extend_from_slice(&composition_offset(frame).to_be_bytes());
+                });
+            });
+        });
+        out
+    };
+
+    // The data offset points past the moof and mdat header, which doesn't change the size of the moof.
+    let size = moof(0).len() as u32;
+    out.extend_from_slice(&moof(size + 8));
+
+    write_box(out, b"mdat", |out| out.extend_from_slice(&frame.payload));
+}
+
+// The presentation time relative to the decode time, clamped to fit the trun.
+fn composition_offset(frame: &Frame) -> i32 {
+    let offset = frame.timestamp as i64 - frame.decode_timestamp as i64;
+    offset.clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+// Convert a length prefixed H.264/H.265 frame to Annex-B, inserting the parameter sets before keyframes.
+fn annexb(track: &RemuxTrack, frame: &Frame) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+
+    let config = match &track.config {
+        Some(config) => config,
+        // We can't decode anything until the first keyframe.
+        None => return Ok(out),
+    };
+
+    let (length_size, parameter_sets) = match track.kind {
+        Kind::Avc => avcc(config)?,
+        Kind::Hevc => hvcc(config)?,
+        _ => anyhow::bail!("Annex-B requires H.264 or H.265"),
+    };
+
+    if frame.config.is_some() {
+        for nalu in parameter_sets {
+            out.extend_from_slice(&[0, 0, 0, 1]);
+            out.extend_from_slice(nalu);
+        }
+    }
+
+    let mut payload = frame.payload.as_slice();
+    while !payload.is_empty() {
+        let (size, rest) = split(payload, length_size)?;
+        let size = size
+            .iter()
+            .fold(0usize, |size, &b| (size << 8) | b as usize);
+        let (nalu, rest) = split(rest, size)?;
+
+        out.extend_from_slice(&[0, 0, 0, 1]);
+        out.extend_from_slice(nalu);
+        payload = rest;
+    }
+
+    Ok(out)
+}
+
+// Parse the NALU length size and the SPS/PPS from an avcC box.
+fn avcc(config: &[u8]) -> anyhow::Result<(usize, Vec<&[u8]>)> {
+    let header = config.get(..6).context("avcC too small")?;
+    let length_size = (header[4] & 0x03) as usize + 1;
+
+    let mut rest = &config[6..];
+    let mut nalus = Vec::new();
+
+    // The SPS count is in the lower 5 bits, followed by the PPS count.
+    let mut count = header[5] & 0x1f;
+    for pps in [false, true] {
+        if pps {
+            let (byte, remaining) = split(rest, 1)?;
+            count = byte[0];
+            rest = remaining;
+        }
+
+        for _ in 0..count {
+            let (nalu, remaining) = parameter_set(rest)?;
+            nalus.push(nalu);
+            rest = remaining;
+        }
+    }
+
+    Ok((length_size, nalus))
+}
+
+// Parse the NALU length size and the VPS/SPS/PPS from an hvcC box.
+fn hvcc(config: &[u8]) -> anyhow::Result<(usize, Vec<&[u8]>)> {
+    let header = config.get(..23).context("hvcC too small")?;
+    let length_size = (header[21] & 0x03) as usize + 1;
+
+    let mut rest = &config[23..];
+    let mut nalus = Vec::new();
+
+    for _ in 0..header[22] {
+        // Skip the NAL unit type
+        let (array, remaining) = split(rest, 3)?;
+        rest = remaining;
+
+        for _ in 0..u16::from_be_bytes([array[1], array[2]]) {
+            let (nalu, remaining) = parameter_set(rest)?;
+            nalus.push(nalu);
+            rest = remaining;
+        }
+    }
+
+    Ok((length_size, nalus))
+}
+
+// Parse a 16-bit length prefixed parameter set.
+fn parameter_set(buf: &[u8]) -> anyhow::Result<(&[u8], &[u8])> {
+    let (size, rest) = split(buf, 2)?;
+    split(rest, u16::from_be_bytes([size[0], size[1]]) as usize)
+}
+
+fn split(buf: &[u8], size: usize) -> anyhow::Result<(&[u8], &[u8])> {
+    anyhow::ensure!(buf.len() >= size, "truncated NAL unit");
+    Ok(buf.split_at(size))
+}
+
+// Prefix an AAC frame with an ADTS header.
+fn adts(kind: Kind, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let (profile, samplerate, channels) = match kind {
+        Kind::Aac {
+            profile,
+            samplerate,
+            channels,
+        } => (profile, samplerate, channels as u8),
+        _ => anyhow::bail!("ADTS requires AAC"),
+    };
+
+    // ADTS only has room for the Main, LC, SSR and LTP object types.
+    anyhow::ensure!(
+        (1..=4).contains(&profile),
+        "AAC object type {profile} can't be written as ADTS"
+    );
+
+    let index = aac_samplerate_index(samplerate)?;
+    let size = payload.len() + 7;
+    anyhow::ensure!(size < 1 << 13, "AAC frame too large for ADTS");
+
+    let mut out = Vec::with_capacity(size);
+    out.extend_from_slice(&[
+        0xff,
+        0xf1, // MPEG-4, no CRC
+        ((profile - 1) << 6) | (index << 2) | ((channels >> 2) & 0x01),
+        ((channels & 0x03) << 6) | (size >> 11) as u8,
+        (size >> 3) as u8,
+        ((size & 0x07) << 5) as u8 | 0x1f,
+        0xfc,
+    ]);
+    out.extend_from_slice(payload);
+
+    Ok(out)
+}
+
+fn write_box(out: &mut Vec<u8>, name: &[u8; 4], payload: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(name);
+    payload(out);
+
+    let size = (out.len() - start) as u32;
+    out[start..start + 4].copy_from_slice(&size.to_be_bytes());
+}
+
+fn write_full_box(
+    out: &mut Vec<u8>,
+    name: &[u8; 4],
+    version: u8,
+    flags: u32,
+    payload: impl FnOnce(&mut Vec<u8>),
+) {
+    write_box(out, name, |out| {
+        out.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+        payload(out);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(codec: &str) -> moq_catalog::Track {
+        let mut track = moq_catalog::Track {
+            name: "1.loc".to_string(),
+            ..Default::default()
+        };
+        track.selection_params.codec = Some(codec.to_string());
+        track.selection_params.samplerate = Some(44100);
+        track.selection_params.channel_config = Some("2".to_string());
+        track
+    }
+
+    #[test]
+    fn annexb_parameter_sets() {
+        let mut remuxer = Remuxer::new(Output::Annexb, &[track("avc1.64001f")]).unwrap();
+
+        // An avcC with 4 byte lengths, one SPS and one PPS.
+        let config = vec![1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 2, 0x67, 1, 1, 0, 1, 0x68];
+        let keyframe = Frame {
+            timestamp: 0,
+            decode_timestamp: 0,
+            config: Some(config),
+            payload: vec![0, 0, 0, 2, 0x65, 9],
+        };
+
+        let out = remuxer.frame(0, keyframe).unwrap();
+        assert_eq!(
+            out,
+            vec![0, 0, 0, 1, 0x67, 1, 0, 0, 0, 1, 0x68, 0, 0, 0, 1, 0x65, 9]
+        );
+
+        let delta = Frame {
+            timestamp: 33_333,
+            decode_timestamp: 33_333,
+            config: None,
+            payload: vec![0, 0, 0, 1, 0x41],
+        };
+        assert_eq!(remuxer.frame(0, delta).unwrap(), vec![0, 0, 0, 1, 0x41]);
+    }
+
+    #[test]
+    fn adts_header() {
+        let mut remuxer = Remuxer::new(Output::Adts, &[track("mp4a.40.2")]).unwrap();
+
+        let frame = Frame {
+            timestamp: 0,
+            decode_timestamp: 0,
+            config: None,
+            payload: vec![0xaa; 9],
+        };
+
+        let out = remuxer.frame(0, frame).unwrap();
+        assert_eq!(&out[..7], &[0xff, 0xf1, 0x50, 0x80, 0x02, 0x1f, 0xfc]);
+        assert_eq!(out.len(), 16);
+    }
+
+    #[test]
+    fn adts_object_types() {
+        assert!(Remuxer::new(Output::Adts, &[track("mp4a.40.0")]).is_err());
+
+        // HE-AAC needs explicit signalling, which ADTS can't carry.
+        let mut remuxer = Remuxer::new(Output::Adts, &[track("mp4a.40.5")]).unwrap();
+        let frame = Frame {
+            timestamp: 0,
+            decode_timestamp: 0,
+            config: None,
+            payload: vec![0xaa; 9],
+        };
+        let err = remuxer.frame(0, frame).unwrap_err();
+        assert!(err.to_string().contains("object type 5"), "{err}");
+    }
+
+    #[test]
+    fn fmp4_decode_timestamps() {
+        let mut video = track("avc1.64001f");
+        video.selection_params.width = Some(1280);
+        video.selection_params.height = Some(720);
+        let mut remuxer = Remuxer::new(Output::Fmp4, &[video]).unwrap();
+
+        // An I, P and B frame, in decode order.
+        let frames = [(33_333, 0), (100_000, 33_333), (66_666, 66_666)];
+        let mut outputs = frames.iter().enumerate().map(|(i, &(pts, dts))| {
+            let frame = Frame {
+                timestamp: pts,
+                decode_timestamp: dts,
+                config: (i == 0).then(|| vec![1, 0x64, 0, 0x1f, 0xff, 0xe0, 0]),
+                payload: vec![0, 0, 0, 1, 0x41],
+            };
+            remuxer.frame(0, frame).unwrap()
+        });
+        outputs.next();
+        outputs.next();
+
+        // The P frame is written once the B frame arrives.
+        let out = outputs.next().unwrap();
+        let mut reader = std::io::Cursor::new(&out);
+        let header = mp4::BoxHeader::read(&mut reader).unwrap();
+        let moof = <mp4::MoofBox as mp4::ReadBox<_>>::read_box(&mut reader, header.size).unwrap();
+
+        let traf = &moof.trafs[0];
+        assert_eq!(traf.tfdt.as_ref().unwrap().base_media_decode_time, 33_333);
+        let trun = traf.trun.as_ref().unwrap();
+        assert_eq!(trun.sample_durations, [33_333]);
+        assert_eq!(trun.sample_cts, [66_667]);
+    }
+
+    #[test]
+    fn fmp4_waits_for_config() {
+        let mut video = track("avc1.64001f");
+        video.selection_params.width = Some(1280);
+        video.selection_params.height = Some(720);
+
+        let tracks = [video, track("mp4a.40.2")];
+        let mut remuxer = Remuxer::new(Output::Fmp4, &tracks).unwrap();
+
+        let audio = |timestamp| Frame {
+            timestamp,
+            decode_timestamp: timestamp,
+            config: None,
+            payload: vec![1, 2, 3],
+        };
+
+        // Nothing can be written until the video decoder configuration is known.
+        assert!(remuxer.frame(1, audio(0)).unwrap().is_empty());
+
+        let keyframe = Frame {
+            timestamp: 0,
+            decode_timestamp: 0,
+            config: Some(vec![1, 0x64, 0, 0x1f, 0xff, 0xe0, 0]),
+            payload: vec![0, 0, 0, 1, 0x65],
+        };
+        let init = remuxer.frame(0, keyframe).unwrap();
+        assert_eq!(&init[4..8], b"ftyp");
+
+        let mut reader = std::io::Cursor::new(&init);
+        let mp4 = mp4::Mp4Reader::read_header(&mut reader, init.len() as u64).unwrap();
+        assert_eq!(mp4.tracks().len(), 2);
+
+        // The keyframe is written once the next frame tells us its duration.
+        let out = remuxer
+            .frame(
+                0,
+                Frame {
+                    timestamp: 33_333,
+                    decode_timestamp: 33_333,
+                    config: None,
+                    payload: vec![0, 0, 0, 1, 0x41],
+                },
+            )
+            .unwrap();
+        assert_eq!(&out[4..8], b"moof");
+    }
+}
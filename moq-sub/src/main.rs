@@ -5,8 +5,8 @@ use clap::Parser;
 use url::Url;
 
 use moq_native_ietf::quic;
-use moq_sub::media::Media;
-use moq_transport::{coding::TrackNamespace, serve::Tracks};
+use moq_sub::{loc, media::Media, segment::SegmentArgs, select::Filter};
+use moq_transport::{coding::TrackNamespace, mlog::VantagePoint, serve::Tracks, session::Session};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -22,27 +22,37 @@ async fn main() -> anyhow::Result<()> {
 
     let config = Config::parse();
     let tls = config.tls.load()?;
+    config.mlog.validate()?;
     let quic = quic::Endpoint::new(quic::Config {
         bind: config.bind,
         qlog_dir: None,
         tls,
     })?;
 
-    let (session, connection_id) = quic.client.connect(&config.url).await?;
+    let (session, connection_id, transport) = quic.client.connect(&config.url).await?;
 
     log::info!(
         "connected with CID: {} (use this to look up qlog/mlog on server)",
         connection_id
     );
 
-    let (session, subscriber) = moq_transport::session::Subscriber::connect(session)
+    // Create the mlog for this connection ID if mlog directory is configured
+    let mlog = config
+        .mlog
+        .create(&connection_id, "moq-sub", VantagePoint::Client)?;
+
+    let (session, _, subscriber) = Session::connect(session, transport, mlog)
         .await
         .context("failed to create MoQ Transport session")?;
 
     // Associate empty set of Tracks with provided namespace
     let tracks = Tracks::new(TrackNamespace::from_utf8_path(&config.name));
 
-    let mut media = Media::new(subscriber, tracks, out, config.catalog).await?;
+    let mut media = Media::new(subscriber, tracks, out, config.catalog)
+        .await?
+        .with_loc_output(config.loc_output)
+        .with_filter(config.filter)
+        .with_segments(config.segments);
 
     tokio::select! {
         res = session.run() => res.context("session error")?,
@@ -70,14 +80,37 @@ pub struct Config {
     #[command(flatten)]
     pub tls: moq_native_ietf::tls::Args,
 
+    /// The mlog configuration.
+    #[command(flatten)]
+    pub mlog: moq_native_ietf::mlog::Args,
+
     /// Request the catalog track (to get other track names)
     ///
     /// First download the track named ".catalog" to find out the
     /// track names to subscribe to.  Other parameters like video
     /// dimension are extracted from the tracks themselves.  Default:
     /// "0.mp4" for the init track, "{track_id}.m4s" for the rest.
+    ///
+    /// With a catalog, the tracks are chosen using the filters below,
+    /// switching between alternate renditions based on the throughput.
     #[arg(long)]
     pub catalog: bool,
+
+    /// Filters used to choose the tracks from the catalog.
+    #[command(flatten)]
+    pub filter: Filter,
+
+    /// The output format when the catalog advertises LOC tracks
+    ///
+    /// LOC publishes each frame as a separate object, which is remuxed
+    /// into fragmented MP4, or an Annex-B (H.264/H.265) or ADTS (AAC)
+    /// elementary stream of a single track.  Requires --catalog.
+    #[arg(long, value_enum, default_value_t = loc::Output::Fmp4)]
+    pub loc_output: loc::Output,
+
+    /// Write segments and manifests to a directory instead of STDOUT.
+    #[command(flatten)]
+    pub segments: SegmentArgs,
 }
 
 fn moq_url(s: &str) -> Result<Url, String> {
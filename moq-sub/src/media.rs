@@ -1,25 +1,36 @@
-use std::{io::Cursor, sync::Arc};
+use std::{collections::HashMap, io::Cursor, sync::Arc, time::Instant};
 
 use anyhow::Context;
 use log::{debug, info, trace, warn};
 use moq_transport::serve::{
-    SubgroupObjectReader, SubgroupReader, TrackReader, TrackReaderMode, Tracks, TracksReader,
-    TracksWriter,
+    MixedReader, SubgroupObjectReader, SubgroupReader, SubgroupsReader, TrackReader,
+    TrackReaderMode, Tracks, TracksReader, TracksWriter,
 };
 use moq_transport::session::Subscriber;
+
+use crate::{
+    abr::Abr,
+    catalog::CatalogReader,
+    init::{self, Init},
+    loc,
+    segment::{SegmentArgs, Segmenter},
+    select::{Filter, Kind},
+};
 use mp4::ReadBox;
 use tokio::{
     io::{AsyncReadExt, AsyncWrite, AsyncWriteExt},
-    sync::Mutex,
+    sync::{watch, Mutex},
     task::JoinSet,
 };
 
 pub struct Media<O> {
-    subscriber: Subscriber,
-    broadcast: TracksReader,
-    tracks_writer: TracksWriter,
+    subscriptions: Subscriptions,
     output: Arc<Mutex<O>>,
     request_catalog: bool,
+    loc_output: loc::Output,
+    filter: Filter,
+    segments: SegmentArgs,
+    catalog: Option<watch::Receiver<moq_catalog::Root>>,
 }
 
 impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
@@ -32,33 +43,70 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
         let (tracks_writer, _tracks_request, tracks_reader) = tracks.produce();
         let broadcast = tracks_reader; // breadcrumb for navigating API name changes
         Ok(Self {
-            subscriber,
-            broadcast,
-            tracks_writer,
+            subscriptions: Subscriptions {
+                subscriber,
+                broadcast,
+                tracks_writer: Arc::new(std::sync::Mutex::new(tracks_writer)),
+            },
             output: Arc::new(Mutex::new(output)),
             request_catalog,
+            loc_output: loc::Output::default(),
+            filter: Filter::default(),
+            segments: SegmentArgs::default(),
+            catalog: None,
         })
     }
 
+    /// Remux LOC tracks into the given format, when the catalog advertises LOC packaging.
+    pub fn with_loc_output(mut self, output: loc::Output) -> Self {
+        self.loc_output = output;
+        self
+    }
+
+    /// Choose the tracks to play from the catalog using the given filters.
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Write the segments of every rendition to a directory, instead of playing to the output.
+    pub fn with_segments(mut self, segments: SegmentArgs) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    /// The live catalog, including any delta updates, once requested and received.
+    pub fn catalog(&self) -> Option<watch::Receiver<moq_catalog::Root>> {
+        self.catalog.clone()
+    }
+
     pub async fn run(&mut self) -> anyhow::Result<()> {
-        let catalog = if self.request_catalog {
-            // The catalog track has no standardized name, but
-            // both moq-pub of moq-rs and gst-moq-pub uses ".catalog".
-            let buf = self.download_first_object(".catalog", "catalog").await?;
-            let s = std::str::from_utf8(&buf)?;
-            let c: moq_catalog::Root = serde_json::from_str(s)?;
-            info!("catalog: {c:#?}");
-            anyhow::ensure!(c.version == 1, "Unknown catalog version");
-            Some(c)
-        } else {
-            None
-        };
+        if !self.request_catalog {
+            return self.run_default().await;
+        }
+
+        let catalog = self.subscribe_catalog().await?;
+        info!("catalog: {catalog:#?}");
+
+        if self.segments.output_dir.is_some() {
+            return self.run_segments(&catalog).await;
+        }
+
+        // LOC tracks don't have an init track, so they're remuxed instead.
+        if catalog.tracks.iter().any(is_loc) {
+            return self.run_loc(&catalog).await;
+        }
+
+        self.run_catalog(&catalog).await
+    }
+
+    // Without a catalog, play the first avc1 and mp4a tracks of the "0.mp4" init track.
+    async fn run_default(&mut self) -> anyhow::Result<()> {
         let moov = {
-            let init_track_name: &str = match catalog {
-                Some(ref c) => &c.tracks[0].init_track.clone().unwrap(),
-                None => "0.mp4",
-            };
-            let buf = self.download_first_object(init_track_name, "init").await?;
+            let buf = self
+                .subscriptions
+                .download_first_object("0.mp4", "init")
+                .await?;
             self.output.lock().await.write_all(&buf).await?;
             let mut reader = Cursor::new(&buf);
 
@@ -76,12 +124,9 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
         let mut has_video = false;
         let mut has_audio = false;
         let mut tracks = vec![];
-        for (idx, trak) in moov.traks.into_iter().enumerate() {
+        for trak in moov.traks {
             let id = trak.tkhd.track_id;
-            let name: String = match catalog {
-                Some(ref c) => c.tracks[idx].name.clone(),
-                None => format!("{id}.m4s"),
-            };
+            let name = format!("{id}.m4s");
             info!("found track {name}");
             let mut active = false;
             if !has_video && trak.mdia.minf.stbl.stsd.avc1.is_some() {
@@ -95,23 +140,7 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
                 info!("using {name} for audio");
             }
             if active {
-                let track = self
-                    .tracks_writer
-                    .create(&name)
-                    .context("failed to create track")?;
-
-                let mut subscriber = self.subscriber.clone();
-                tokio::task::spawn(async move {
-                    subscriber.subscribe(track).await.unwrap_or_else(|err| {
-                        warn!("failed to subscribe to track: {err:?}");
-                    });
-                });
-
-                tracks.push(
-                    self.broadcast
-                        .subscribe(self.broadcast.namespace.clone(), &name)
-                        .context("no track")?,
-                );
+                tracks.push(self.subscriptions.subscribe(&name, "media")?);
             }
         }
 
@@ -120,7 +149,7 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
         for track in tracks {
             let out = self.output.clone();
             tasks.spawn(async move {
-                let name = track.name.clone();
+                let name = track.reader.name.clone();
                 if let Err(err) = Self::recv_track(track, out).await {
                     warn!("failed to play track {name}: {err:?}");
                 }
@@ -130,52 +159,263 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
         Ok(())
     }
 
-    async fn download_first_object(
-        &mut self,
-        track_name: &str,
-        alias: &'static str,
-    ) -> anyhow::Result<Vec<u8>> {
-        let track = self
-            .tracks_writer
-            .create(track_name)
-            .context(format!("failed to create {alias} track"))?;
+    // Play the video and audio tracks chosen from the catalog, switching between alternates.
+    async fn run_catalog(&mut self, catalog: &moq_catalog::Root) -> anyhow::Result<()> {
+        let selection = self.filter.select(catalog);
+        anyhow::ensure!(
+            !selection.video.is_empty() || !selection.audio.is_empty(),
+            "no tracks match the filters"
+        );
 
-        let mut subscriber = self.subscriber.clone();
-        tokio::task::spawn(async move {
-            subscriber.subscribe(track).await.unwrap_or_else(|err| {
-                warn!("failed to subscribe to {alias} track: {err:?}");
-            });
+        let ladders = [
+            (Kind::Video, selection.video),
+            (Kind::Audio, selection.audio),
+        ];
+        let ladders: Vec<_> = ladders
+            .into_iter()
+            .filter(|(_, ladder)| !ladder.is_empty())
+            .collect();
+
+        let player = Arc::new(Player {
+            subscriptions: self.subscriptions.clone(),
+            output: self.output.clone(),
+            state: Mutex::new(PlayerState::default()),
         });
 
-        let track = self
-            .broadcast
-            .subscribe(self.broadcast.namespace.clone(), track_name)
-            .context(format!("no {alias} track"))?;
-        let mut group = match track.mode().await? {
-            TrackReaderMode::Subgroups(mut groups) => {
-                groups.next().await?.context(format!("no {alias} group"))?
+        // Write the init segment for the initial renditions before any fragments.
+        for (kind, ladder) in &ladders {
+            info!(
+                "using {} for {kind:?}, alternates: {}",
+                ladder[0].name,
+                ladder.len() - 1
+            );
+            player
+                .state
+                .lock()
+                .await
+                .selected
+                .push((*kind, ladder[0].clone()));
+        }
+        player.write_init().await?;
+
+        let mut tasks = JoinSet::new();
+        for (kind, ladder) in ladders {
+            let player = player.clone();
+            let catalog = self.catalog.clone();
+            tasks.spawn(async move {
+                if let Err(err) = Self::play(kind, ladder, player, catalog).await {
+                    warn!("failed to play {kind:?}: {err:?}");
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    // Play the renditions of the given kind, switching at group boundaries based on the throughput.
+    async fn play(
+        kind: Kind,
+        ladder: Vec<moq_catalog::Track>,
+        player: Arc<Player<O>>,
+        mut catalog: Option<watch::Receiver<moq_catalog::Root>>,
+    ) -> anyhow::Result<()> {
+        let mut abr = Abr::new(ladder);
+        let current = abr.current().context("empty ladder")?;
+
+        let mut subscription = player.subscriptions.subscribe(&current.name, "media")?;
+        let mut groups = subgroups(&subscription.reader).await?;
+
+        // Groups before this ID were already played from the previous rendition.
+        let mut next_group = 0;
+
+        while let Some(mut group) = groups.next().await? {
+            if group.group_id < next_group {
+                continue;
             }
-            _ => anyhow::bail!("expected {alias} segment"),
-        };
 
-        let object = group
-            .next()
-            .await?
-            .context(format!("no {alias} fragment"))?;
-        let buf = Self::recv_object(object).await?;
-        Ok(buf)
+            trace!("group={} start", group.group_id);
+            while let Some(object) = group.next().await? {
+                let start = Instant::now();
+                let buf = Self::recv_object(object).await?;
+                abr.record(buf.len() as u64, start.elapsed());
+
+                player.output.lock().await.write_all(&buf).await?;
+            }
+
+            if let Some(catalog) = catalog
+                .as_mut()
+                .filter(|c| c.has_changed().unwrap_or(false))
+            {
+                abr.update(&catalog.borrow_and_update());
+            }
+
+            let next = match abr.switch() {
+                Some(next) => next.clone(),
+                None => continue,
+            };
+
+            info!("switching {kind:?} to {}", next.name);
+
+            // Subscribe before writing the init segment, so we don't miss the next group.
+            subscription = player.subscriptions.subscribe(&next.name, "media")?;
+            groups = subgroups(&subscription.reader).await?;
+            next_group = group.group_id + 1;
+
+            player.select(kind, next).await?;
+        }
+
+        Ok(())
     }
 
-    async fn recv_track(track: TrackReader, out: Arc<Mutex<O>>) -> anyhow::Result<()> {
-        let name = track.name.clone();
-        debug!("track {name}: start");
-        if let TrackReaderMode::Subgroups(mut groups) = track.mode().await? {
-            while let Some(group) = groups.next().await? {
-                let out = out.clone();
-                if let Err(err) = Self::recv_group(group, out).await {
-                    warn!("failed to receive group: {err:?}");
+    // Write every rendition chosen from the catalog to a directory, one segment per group.
+    async fn run_segments(&mut self, catalog: &moq_catalog::Root) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !catalog.tracks.iter().any(is_loc),
+            "segment output requires CMAF tracks"
+        );
+
+        let selection = self.filter.select(catalog);
+        let tracks: Vec<_> = selection
+            .video
+            .into_iter()
+            .map(|track| (Kind::Video, track))
+            .chain(
+                selection
+                    .audio
+                    .into_iter()
+                    .map(|track| (Kind::Audio, track)),
+            )
+            .collect();
+        anyhow::ensure!(!tracks.is_empty(), "no tracks match the filters");
+
+        let segmenter = Arc::new(Segmenter::new(self.segments.clone()).await?);
+        let mut inits: HashMap<String, Init> = HashMap::new();
+
+        let mut tasks = JoinSet::new();
+        for (kind, track) in tracks {
+            let name = track.init_track.clone().context("missing init track")?;
+            if !inits.contains_key(&name) {
+                let buf = self
+                    .subscriptions
+                    .download_first_object(&name, "init")
+                    .await?;
+                inits.insert(name.clone(), Init::parse(&buf)?);
+            }
+
+            info!("writing {kind:?} segments for {}", track.name);
+            let subscription = self.subscriptions.subscribe(&track.name, "media")?;
+            let index = segmenter.add(kind, track, &inits[&name]).await?;
+
+            let segmenter = segmenter.clone();
+            tasks.spawn(async move {
+                let name = subscription.reader.name.clone();
+                if let Err(err) = Self::recv_segments(subscription, index, &segmenter).await {
+                    warn!("failed to write track {name}: {err:?}");
+                }
+                if let Err(err) = segmenter.finish(index).await {
+                    warn!("failed to finish track {name}: {err:?}");
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    // Select the first LOC video and audio track supported by the output, and remux them.
+    async fn run_loc(&mut self, catalog: &moq_catalog::Root) -> anyhow::Result<()> {
+        let mut has_video = false;
+        let mut has_audio = false;
+        let mut selected = vec![];
+
+        for track in catalog
+            .tracks
+            .iter()
+            .filter(|track| is_loc(track) && self.filter.matches(track))
+        {
+            let video = Kind::of(track) == Kind::Video;
+            if !self.loc_output.accepts(video) {
+                continue;
+            }
+
+            match video {
+                true if !has_video => has_video = true,
+                false if !has_audio => has_audio = true,
+                _ => continue,
+            }
+
+            info!(
+                "using {} for {}",
+                track.name,
+                if video { "video" } else { "audio" }
+            );
+            selected.push(track.clone());
+        }
+
+        let remuxer = loc::Remuxer::new(self.loc_output, &selected)?;
+        let remuxer = Arc::new(Mutex::new(remuxer));
+
+        info!("playing {} LOC tracks", selected.len());
+        let mut tasks = JoinSet::new();
+        for (index, track) in selected.iter().enumerate() {
+            let track = self.subscriptions.subscribe(&track.name, "media")?;
+            let remuxer = remuxer.clone();
+            let out = self.output.clone();
+            tasks.spawn(async move {
+                let name = track.reader.name.clone();
+                if let Err(err) = Self::recv_loc_track(track, index, remuxer, out).await {
+                    warn!("failed to play track {name}: {err:?}");
+                }
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    // Subscribe to the catalog, returning the first catalog and merging any updates in the background.
+    async fn subscribe_catalog(&mut self) -> anyhow::Result<moq_catalog::Root> {
+        // The catalog track has no standardized name, but
+        // both moq-pub of moq-rs and gst-moq-pub uses ".catalog".
+        let subscription = self.subscriptions.subscribe(".catalog", "catalog")?;
+        let mut reader = CatalogReader::new(subscription.reader.clone()).await?;
+        let catalog = reader.next().await?.context("no catalog")?;
+
+        let (tx, rx) = watch::channel(catalog.clone());
+        self.catalog = Some(rx);
+
+        tokio::task::spawn(async move {
+            // Keep the subscription alive for as long as we're reading updates.
+            let _subscription = subscription;
+
+            loop {
+                match reader.next().await {
+                    Ok(Some(catalog)) => {
+                        info!("catalog updated: {} tracks", catalog.tracks.len());
+                        debug!("catalog: {catalog:#?}");
+                        if tx.send(catalog).is_err() {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        warn!("failed to read catalog: {err:?}");
+                        break;
+                    }
                 }
             }
+        });
+
+        Ok(catalog)
+    }
+
+    async fn recv_track(track: Subscription, out: Arc<Mutex<O>>) -> anyhow::Result<()> {
+        let name = track.reader.name.clone();
+        debug!("track {name}: start");
+        let mut groups = subgroups(&track.reader).await?;
+        while let Some(group) = groups.next().await? {
+            let out = out.clone();
+            if let Err(err) = Self::recv_group(group, out).await {
+                warn!("failed to receive group: {err:?}");
+            }
         }
         debug!("track {name}: finish");
         Ok(())
@@ -198,6 +438,51 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
         Ok(())
     }
 
+    async fn recv_loc_track(
+        track: Subscription,
+        index: usize,
+        remuxer: Arc<Mutex<loc::Remuxer>>,
+        out: Arc<Mutex<O>>,
+    ) -> anyhow::Result<()> {
+        let name = track.reader.name.clone();
+        debug!("track {name}: start");
+        let mut groups = subgroups(&track.reader).await?;
+        while let Some(mut group) = groups.next().await? {
+            trace!("group={} start", group.group_id);
+            while let Some(object) = group.next().await? {
+                let headers = object.extension_headers.clone();
+                let payload = Self::recv_object(object).await?;
+                let frame = loc::Frame::new(&headers, payload)?;
+
+                // Hold the remuxer while writing, so the init segment is written before any fragment.
+                let mut remuxer = remuxer.lock().await;
+                let buf = remuxer.frame(index, frame)?;
+                out.lock().await.write_all(&buf).await?;
+            }
+        }
+        debug!("track {name}: finish");
+        Ok(())
+    }
+
+    async fn recv_segments(
+        track: Subscription,
+        index: usize,
+        segmenter: &Segmenter,
+    ) -> anyhow::Result<()> {
+        let mut groups = subgroups(&track.reader).await?;
+        while let Some(mut group) = groups.next().await? {
+            trace!("group={} start", group.group_id);
+
+            let mut objects = Vec::new();
+            while let Some(object) = group.next().await? {
+                objects.push(Self::recv_object(object).await?);
+            }
+
+            segmenter.write(index, group.group_id, objects).await?;
+        }
+        Ok(())
+    }
+
     async fn recv_object(mut object: SubgroupObjectReader) -> anyhow::Result<Vec<u8>> {
         let mut buf = Vec::with_capacity(object.size);
         while let Some(chunk) = object.read().await? {
@@ -207,6 +492,164 @@ impl<O: AsyncWrite + Send + Unpin + 'static> Media<O> {
     }
 }
 
+// Subscribes to tracks in the broadcast, shared with the tasks playing each track.
+#[derive(Clone)]
+struct Subscriptions {
+    subscriber: Subscriber,
+    broadcast: TracksReader,
+    tracks_writer: Arc<std::sync::Mutex<TracksWriter>>,
+}
+
+impl Subscriptions {
+    // Subscribe to the given track, until the returned subscription is dropped.
+    fn subscribe(&self, track_name: &str, alias: &'static str) -> anyhow::Result<Subscription> {
+        let track = self
+            .tracks_writer
+            .lock()
+            .unwrap()
+            .create(track_name)
+            .context(format!("failed to create {alias} track"))?;
+
+        let mut subscriber = self.subscriber.clone();
+        let task = tokio::task::spawn(async move {
+            subscriber.subscribe(track).await.unwrap_or_else(|err| {
+                warn!("failed to subscribe to {alias} track: {err:?}");
+            });
+        });
+
+        let mut broadcast = self.broadcast.clone();
+        let reader = broadcast
+            .subscribe(broadcast.namespace.clone(), track_name)
+            .context(format!("no {alias} track"))?;
+
+        Ok(Subscription { reader, task })
+    }
+
+    async fn download_first_object(
+        &self,
+        track_name: &str,
+        alias: &'static str,
+    ) -> anyhow::Result<Vec<u8>> {
+        let track = self.subscribe(track_name, alias)?;
+        let mut group = subgroups(&track.reader)
+            .await?
+            .next()
+            .await?
+            .context(format!("no {alias} group"))?;
+
+        let object = group
+            .next()
+            .await?
+            .context(format!("no {alias} fragment"))?;
+
+        let mut buf = Vec::with_capacity(object.size);
+        let mut object = object;
+        while let Some(chunk) = object.read().await? {
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(buf)
+    }
+}
+
+// An active subscription, which unsubscribes when dropped.
+struct Subscription {
+    reader: TrackReader,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// The renditions currently being played, and the init segments used to describe them.
+struct Player<O> {
+    subscriptions: Subscriptions,
+    output: Arc<Mutex<O>>,
+    state: Mutex<PlayerState>,
+}
+
+#[derive(Default)]
+struct PlayerState {
+    // The current rendition of each kind.
+    selected: Vec<(Kind, moq_catalog::Track)>,
+
+    // The init segments that have been downloaded, by track name.
+    inits: HashMap<String, Init>,
+
+    // The init tracks used by the last init segment written to the output.
+    written: Vec<String>,
+}
+
+impl<O: AsyncWrite + Send + Unpin + 'static> Player<O> {
+    // Switch to a new rendition, writing a new init segment if it's needed.
+    async fn select(&self, kind: Kind, track: moq_catalog::Track) -> anyhow::Result<()> {
+        {
+            let mut state = self.state.lock().await;
+            match state.selected.iter_mut().find(|(k, _)| *k == kind) {
+                Some((_, selected)) => *selected = track,
+                None => state.selected.push((kind, track)),
+            }
+        }
+
+        self.write_init().await
+    }
+
+    // Write an init segment describing the selected renditions, unless it's unchanged.
+    async fn write_init(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().await;
+
+        let names = state
+            .selected
+            .iter()
+            .map(|(_, track)| track.init_track.clone().context("missing init track"))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if names == state.written {
+            return Ok(());
+        }
+
+        for name in &names {
+            if !state.inits.contains_key(name) {
+                let buf = self
+                    .subscriptions
+                    .download_first_object(name, "init")
+                    .await?;
+                state.inits.insert(name.clone(), Init::parse(&buf)?);
+            }
+        }
+
+        let parts: Vec<_> = state
+            .selected
+            .iter()
+            .zip(&names)
+            .map(|((kind, _), name)| (*kind, &state.inits[name]))
+            .collect();
+        let init = init::merge(&parts)?;
+
+        self.output.lock().await.write_all(&init).await?;
+        state.written = names;
+
+        Ok(())
+    }
+}
+
+// Returns the subgroups of a track, regardless of whether it also has datagrams.
+async fn subgroups(track: &TrackReader) -> anyhow::Result<SubgroupsReader> {
+    match track.mode().await? {
+        TrackReaderMode::Subgroups(groups)
+        | TrackReaderMode::Mixed(MixedReader {
+            subgroups: groups, ..
+        }) => Ok(groups),
+        _ => anyhow::bail!("expected subgroups"),
+    }
+}
+
+// Returns true if the track uses LOC packaging, after resolving the common track fields.
+fn is_loc(track: &moq_catalog::Track) -> bool {
+    track.packaging == Some(moq_catalog::TrackPackaging::Loc)
+}
+
 // Read a full MP4 atom into a vector.
 async fn read_atom<R: AsyncReadExt + Unpin>(reader: &mut R) -> anyhow::Result<Vec<u8>> {
     // Read the 8 bytes for the size + type
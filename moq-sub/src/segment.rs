@@ -0,0 +1,703 @@
+//! Write the CMAF segments of each rendition to a directory, one per group.
+//!
+//! Each rendition gets its own directory containing an init segment and the most recent groups,
+//! along with an optional live HLS (fMP4) playlist and DASH manifest describing them.  Segment
+//! timing comes from the fragments themselves, so the manifests don't depend on the wall clock.
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use mp4::ReadBox;
+use tokio::sync::Mutex;
+
+use crate::{
+    init::{self, Init},
+    select::Kind,
+};
+
+/// Options used to write segments to a directory.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct SegmentArgs {
+    /// Write the CMAF segments of every rendition chosen from the catalog to the given directory,
+    /// one per group, instead of playing to STDOUT.  Requires --catalog.
+    #[arg(long, requires = "catalog")]
+    pub output_dir: Option<PathBuf>,
+
+    /// The number of segments to keep for each rendition, deleting older ones.
+    ///
+    /// Use 0 to keep every segment.
+    #[arg(long, default_value_t = 10)]
+    pub retention: usize,
+
+    /// Write a live HLS master playlist and a media playlist for each rendition.
+    #[arg(long, requires = "output_dir")]
+    pub hls: bool,
+
+    /// Write a live DASH manifest describing every rendition.
+    #[arg(long, requires = "output_dir")]
+    pub dash: bool,
+}
+
+/// A segment written to disk, containing a single group.
+#[derive(Debug, Clone)]
+pub struct Segment {
+    /// Incremented for each segment of the rendition, even if groups are skipped.
+    pub sequence: u64,
+
+    /// The group ID, used as the file name.
+    pub group_id: u64,
+
+    /// The decode time of the first sample, in the timescale of the rendition.
+    pub start: u64,
+
+    /// The duration of every sample, in the timescale of the rendition.
+    pub duration: u64,
+
+    pub size: usize,
+}
+
+impl Segment {
+    fn file_name(&self) -> String {
+        format!("{}.m4s", self.group_id)
+    }
+}
+
+/// The segments written for a single track.
+#[derive(Debug)]
+pub struct Rendition {
+    pub kind: Kind,
+    pub track: moq_catalog::Track,
+    pub timescale: u32,
+
+    // Used when a fragment doesn't include the sample durations.
+    default_duration: Option<u32>,
+
+    // The decode time of the first segment ever written, which is presentation time zero.
+    origin: Option<u64>,
+
+    // Only the retained segments, oldest first.
+    segments: VecDeque<Segment>,
+    next_sequence: u64,
+    ended: bool,
+}
+
+impl Rendition {
+    pub fn new(kind: Kind, track: moq_catalog::Track, init: &Init) -> anyhow::Result<Self> {
+        let timescale = init
+            .timescale(kind)
+            .filter(|timescale| *timescale > 0)
+            .context("missing timescale")?;
+
+        Ok(Self {
+            kind,
+            track,
+            timescale,
+            default_duration: init.default_duration(kind),
+            origin: None,
+            segments: VecDeque::new(),
+            next_sequence: 0,
+            ended: false,
+        })
+    }
+
+    /// The name of the directory containing the segments.
+    pub fn dir_name(&self) -> String {
+        self.track
+            .name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '_',
+            })
+            .collect()
+    }
+
+    /// Add a segment, returning any that fall outside of the retention window.
+    pub fn push(
+        &mut self,
+        group_id: u64,
+        start: u64,
+        duration: u64,
+        size: usize,
+        retention: usize,
+    ) -> Vec<Segment> {
+        self.origin.get_or_insert(start);
+
+        self.segments.push_back(Segment {
+            sequence: self.next_sequence,
+            group_id,
+            start,
+            duration,
+            size,
+        });
+        self.next_sequence += 1;
+
+        let mut expired = Vec::new();
+        while retention > 0 && self.segments.len() > retention {
+            expired.extend(self.segments.pop_front());
+        }
+        expired
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter()
+    }
+
+    // The duration of a segment in seconds.
+    fn seconds(&self, duration: u64) -> f64 {
+        duration as f64 / self.timescale as f64
+    }
+
+    // The longest segment in whole seconds, rounded up.
+    fn target_duration(&self) -> u64 {
+        self.segments
+            .iter()
+            .map(|segment| self.seconds(segment.duration).ceil() as u64)
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
+    /// The advertised bit rate, or the peak bit rate of the retained segments.
+    pub fn bandwidth(&self) -> u64 {
+        if let Some(bitrate) = self.track.selection_params.bitrate {
+            return bitrate as u64;
+        }
+
+        self.segments
+            .iter()
+            .filter(|segment| segment.duration > 0)
+            .map(|segment| (segment.size as f64 * 8.0 / self.seconds(segment.duration)) as u64)
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// The decode time and duration of a group, from the fragments of each object.
+///
+/// Each object contains one or more moof and mdat atoms.
+pub fn timing(objects: &[Vec<u8>], default_duration: Option<u32>) -> anyhow::Result<(u64, u64)> {
+    let mut start = None;
+    let mut duration = 0;
+
+    for object in objects {
+        for (name, _, raw) in init::boxes(object) {
+            if &name != b"moof" {
+                continue;
+            }
+
+            let mut reader = Cursor::new(raw);
+            let header = mp4::BoxHeader::read(&mut reader)?;
+            let moof = mp4::MoofBox::read_box(&mut reader, header.size)?;
+
+            for traf in &moof.trafs {
+                if start.is_none() {
+                    start = traf.tfdt.as_ref().map(|tfdt| tfdt.base_media_decode_time);
+                }
+
+                let trun = match &traf.trun {
+                    Some(trun) => trun,
+                    None => continue,
+                };
+
+                duration += match trun.sample_durations.is_empty() {
+                    false => trun.sample_durations.iter().map(|&d| d as u64).sum(),
+                    true => {
+                        let default = traf
+                            .tfhd
+                            .default_sample_duration
+                            .or(default_duration)
+                            .context("missing sample duration")?;
+                        default as u64 * trun.sample_count as u64
+                    }
+                };
+            }
+        }
+    }
+
+    Ok((start.context("missing tfdt")?, duration))
+}
+
+/// A live HLS media playlist for the rendition.
+pub fn media_playlist(rendition: &Rendition) -> String {
+    let mut out = String::new();
+    let first = rendition.segments().next().map(|s| s.sequence);
+
+    writeln!(out, "#EXTM3U").unwrap();
+    writeln!(out, "#EXT-X-VERSION:7").unwrap();
+    writeln!(out, "#EXT-X-TARGETDURATION:{}", rendition.target_duration()).unwrap();
+    writeln!(out, "#EXT-X-MEDIA-SEQUENCE:{}", first.unwrap_or_default()).unwrap();
+    writeln!(out, "#EXT-X-INDEPENDENT-SEGMENTS").unwrap();
+    writeln!(out, "#EXT-X-MAP:URI=\"init.mp4\"").unwrap();
+
+    for segment in rendition.segments() {
+        writeln!(out, "#EXTINF:{:.3},", rendition.seconds(segment.duration)).unwrap();
+        writeln!(out, "{}", segment.file_name()).unwrap();
+    }
+
+    if rendition.ended {
+        writeln!(out, "#EXT-X-ENDLIST").unwrap();
+    }
+
+    out
+}
+
+/// A HLS master playlist referencing the media playlist of each rendition.
+///
+/// The audio renditions are alternatives of every video rendition.  Without video, each audio
+/// rendition is its own variant.
+pub fn master_playlist(renditions: &[Rendition]) -> String {
+    let mut out = String::new();
+    writeln!(out, "#EXTM3U").unwrap();
+    writeln!(out, "#EXT-X-VERSION:7").unwrap();
+    writeln!(out, "#EXT-X-INDEPENDENT-SEGMENTS").unwrap();
+
+    let (video, audio): (Vec<_>, Vec<_>) = renditions.iter().partition(|r| r.kind == Kind::Video);
+    let uri = |rendition: &Rendition| format!("{}/playlist.m3u8", rendition.dir_name());
+    let codec = |rendition: &Rendition| rendition.track.selection_params.codec.clone();
+
+    if video.is_empty() {
+        for rendition in audio {
+            write!(out, "#EXT-X-STREAM-INF:BANDWIDTH={}", rendition.bandwidth()).unwrap();
+            if let Some(codec) = codec(rendition) {
+                write!(out, ",CODECS=\"{codec}\"").unwrap();
+            }
+            writeln!(out).unwrap();
+            writeln!(out, "{}", uri(rendition)).unwrap();
+        }
+
+        return out;
+    }
+
+    for (index, rendition) in audio.iter().enumerate() {
+        write!(
+            out,
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"{}\"",
+            rendition.track.name
+        )
+        .unwrap();
+        if let Some(lang) = &rendition.track.selection_params.language {
+            write!(out, ",LANGUAGE=\"{lang}\"").unwrap();
+        }
+        let default = if index == 0 { "YES" } else { "NO" };
+        writeln!(
+            out,
+            ",DEFAULT={default},AUTOSELECT=YES,URI=\"{}\"",
+            uri(rendition)
+        )
+        .unwrap();
+    }
+
+    // The bandwidth of a variant includes the audio played with it.
+    let audio_bandwidth = audio
+        .iter()
+        .map(|r| r.bandwidth())
+        .max()
+        .unwrap_or_default();
+    let audio_codec = audio.first().and_then(|r| codec(r));
+
+    for rendition in video {
+        let params = &rendition.track.selection_params;
+        write!(
+            out,
+            "#EXT-X-STREAM-INF:BANDWIDTH={}",
+            rendition.bandwidth() + audio_bandwidth
+        )
+        .unwrap();
+
+        let codecs: Vec<_> = codec(rendition)
+            .into_iter()
+            .chain(audio_codec.clone())
+            .collect();
+        if !codecs.is_empty() {
+            write!(out, ",CODECS=\"{}\"", codecs.join(",")).unwrap();
+        }
+        if let (Some(width), Some(height)) = (params.width, params.height) {
+            write!(out, ",RESOLUTION={width}x{height}").unwrap();
+        }
+        if let Some(framerate) = params.framerate {
+            write!(out, ",FRAME-RATE={framerate}").unwrap();
+        }
+        if !audio.is_empty() {
+            write!(out, ",AUDIO=\"audio\"").unwrap();
+        }
+        writeln!(out).unwrap();
+        writeln!(out, "{}", uri(rendition)).unwrap();
+    }
+
+    out
+}
+
+/// A DASH manifest describing every rendition, with an explicit list of segments.
+///
+/// Presentation time zero is the first segment of each rendition, which is available at the given
+/// time.  The manifest becomes static once every rendition has ended.
+pub fn manifest(renditions: &[Rendition], available: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let timestamp = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let seconds = |seconds: f64| format!("PT{seconds:.3}S");
+
+    let ended = renditions.iter().all(|r| r.ended);
+    let target = renditions
+        .iter()
+        .map(|r| r.target_duration())
+        .max()
+        .unwrap_or(1);
+
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    write!(
+        out,
+        r#"<MPD xmlns="urn:mpeg:dash:schema:mpd:2011" profiles="urn:mpeg:dash:profile:isoff-live:2011" minBufferTime="{}""#,
+        seconds(target as f64)
+    )
+    .unwrap();
+
+    if ended {
+        // The longest rendition, from presentation time zero.
+        let duration = renditions
+            .iter()
+            .filter_map(|r| {
+                let last = r.segments().last()?;
+                Some(r.seconds(last.start + last.duration - r.origin?))
+            })
+            .fold(0.0, f64::max);
+        write!(
+            out,
+            r#" type="static" mediaPresentationDuration="{}""#,
+            seconds(duration)
+        )
+        .unwrap();
+    } else {
+        // Only the retained segments can be requested.
+        let depth = renditions
+            .iter()
+            .map(|r| r.seconds(r.segments().map(|s| s.duration).sum()))
+            .reduce(f64::min)
+            .unwrap_or_default();
+        write!(
+            out,
+            r#" type="dynamic" availabilityStartTime="{}" publishTime="{}" minimumUpdatePeriod="{}" timeShiftBufferDepth="{}""#,
+            timestamp(available),
+            timestamp(now),
+            seconds(target as f64),
+            seconds(depth),
+        )
+        .unwrap();
+    }
+    writeln!(out, ">").unwrap();
+    writeln!(out, r#"  <Period id="0" start="PT0S">"#).unwrap();
+
+    for kind in [Kind::Video, Kind::Audio] {
+        let renditions: Vec<_> = renditions.iter().filter(|r| r.kind == kind).collect();
+        if renditions.is_empty() {
+            continue;
+        }
+
+        let content = match kind {
+            Kind::Video => "video",
+            Kind::Audio => "audio",
+        };
+        writeln!(
+            out,
+            r#"    <AdaptationSet contentType="{content}" mimeType="{content}/mp4" segmentAlignment="true">"#
+        )
+        .unwrap();
+
+        for rendition in renditions {
+            representation(&mut out, rendition);
+        }
+
+        writeln!(out, "    </AdaptationSet>").unwrap();
+    }
+
+    writeln!(out, "  </Period>").unwrap();
+    writeln!(out, "</MPD>").unwrap();
+
+    out
+}
+
+fn representation(out: &mut String, rendition: &Rendition) {
+    let params = &rendition.track.selection_params;
+    let dir = rendition.dir_name();
+
+    write!(
+        out,
+        r#"      <Representation id="{}" bandwidth="{}""#,
+        escape(&rendition.track.name),
+        rendition.bandwidth()
+    )
+    .unwrap();
+    if let Some(codec) = &params.codec {
+        write!(out, r#" codecs="{}""#, escape(codec)).unwrap();
+    }
+    if let (Some(width), Some(height)) = (params.width, params.height) {
+        write!(out, r#" width="{width}" height="{height}""#).unwrap();
+    }
+    if let Some(samplerate) = params.samplerate {
+        write!(out, r#" audioSamplingRate="{samplerate}""#).unwrap();
+    }
+    if let Some(lang) = &params.language {
+        write!(out, r#" lang="{}""#, escape(lang)).unwrap();
+    }
+    writeln!(out, ">").unwrap();
+
+    writeln!(
+        out,
+        r#"        <SegmentList timescale="{}" presentationTimeOffset="{}">"#,
+        rendition.timescale,
+        rendition.origin.unwrap_or_default()
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"          <Initialization sourceURL="{dir}/init.mp4"/>"#
+    )
+    .unwrap();
+
+    writeln!(out, "          <SegmentTimeline>").unwrap();
+    for segment in rendition.segments() {
+        writeln!(
+            out,
+            r#"            <S t="{}" d="{}"/>"#,
+            segment.start, segment.duration
+        )
+        .unwrap();
+    }
+    writeln!(out, "          </SegmentTimeline>").unwrap();
+
+    for segment in rendition.segments() {
+        writeln!(
+            out,
+            r#"          <SegmentURL media="{dir}/{}"/>"#,
+            segment.file_name()
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "        </SegmentList>").unwrap();
+    writeln!(out, "      </Representation>").unwrap();
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Writes the segments of each rendition to a directory, along with the manifests.
+pub struct Segmenter {
+    args: SegmentArgs,
+    dir: PathBuf,
+    available: DateTime<Utc>,
+    renditions: Mutex<Vec<Rendition>>,
+}
+
+impl Segmenter {
+    pub async fn new(args: SegmentArgs) -> anyhow::Result<Self> {
+        let dir = args
+            .output_dir
+            .clone()
+            .context("missing output directory")?;
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+
+        Ok(Self {
+            args,
+            dir,
+            available: Utc::now(),
+            renditions: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Add a rendition, writing its init segment and returning its index.
+    pub async fn add(
+        &self,
+        kind: Kind,
+        track: moq_catalog::Track,
+        init: &Init,
+    ) -> anyhow::Result<usize> {
+        let rendition = Rendition::new(kind, track, init)?;
+
+        let dir = self.dir.join(rendition.dir_name());
+        tokio::fs::create_dir_all(&dir).await?;
+        write_atomic(&dir.join("init.mp4"), &init::merge(&[(kind, init)])?).await?;
+
+        let mut renditions = self.renditions.lock().await;
+        renditions.push(rendition);
+        Ok(renditions.len() - 1)
+    }
+
+    /// Write the objects of a group as a single segment, then update the manifests.
+    pub async fn write(
+        &self,
+        index: usize,
+        group_id: u64,
+        objects: Vec<Vec<u8>>,
+    ) -> anyhow::Result<()> {
+        let mut renditions = self.renditions.lock().await;
+        let rendition = renditions.get_mut(index).context("unknown rendition")?;
+
+        let (start, duration) = timing(&objects, rendition.default_duration)?;
+        let buf = objects.concat();
+
+        let dir = self.dir.join(rendition.dir_name());
+        write_atomic(&dir.join(format!("{group_id}.m4s")), &buf).await?;
+
+        let expired = rendition.push(group_id, start, duration, buf.len(), self.args.retention);
+
+        self.write_manifests(&renditions).await?;
+
+        // Remove the expired segments only once they're no longer in the manifests.
+        for segment in expired {
+            let path = dir.join(segment.file_name());
+            if let Err(err) = tokio::fs::remove_file(&path).await {
+                log::warn!("failed to remove {}: {err}", path.display());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mark the rendition as ended, so the manifests stop advertising new segments.
+    pub async fn finish(&self, index: usize) -> anyhow::Result<()> {
+        let mut renditions = self.renditions.lock().await;
+        if let Some(rendition) = renditions.get_mut(index) {
+            rendition.ended = true;
+        }
+
+        self.write_manifests(&renditions).await
+    }
+
+    async fn write_manifests(&self, renditions: &[Rendition]) -> anyhow::Result<()> {
+        if self.args.hls {
+            for rendition in renditions {
+                let path = self.dir.join(rendition.dir_name()).join("playlist.m3u8");
+                write_atomic(&path, media_playlist(rendition).as_bytes()).await?;
+            }
+
+            let path = self.dir.join("master.m3u8");
+            write_atomic(&path, master_playlist(renditions).as_bytes()).await?;
+        }
+
+        if self.args.dash {
+            let path = self.dir.join("manifest.mpd");
+            let mpd = manifest(renditions, self.available, Utc::now());
+            write_atomic(&path, mpd.as_bytes()).await?;
+        }
+
+        Ok(())
+    }
+}
+
+// Write to a temporary file and rename it, so readers never see a partial file.
+async fn write_atomic(path: &Path, buf: &[u8]) -> anyhow::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    tokio::fs::write(&tmp, buf)
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    tokio::fs::rename(&tmp, path).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendition(kind: Kind, name: &str, bitrate: Option<u32>) -> Rendition {
+        let mut track = moq_catalog::Track {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        track.selection_params.bitrate = bitrate;
+        match kind {
+            Kind::Video => {
+                track.selection_params.codec = Some("avc1.64001f".to_string());
+                track.selection_params.width = Some(1280);
+                track.selection_params.height = Some(720);
+            }
+            Kind::Audio => track.selection_params.codec = Some("mp4a.40.2".to_string()),
+        }
+
+        Rendition {
+            kind,
+            track,
+            timescale: 90_000,
+            default_duration: None,
+            origin: None,
+            segments: VecDeque::new(),
+            next_sequence: 0,
+            ended: false,
+        }
+    }
+
+    #[test]
+    fn retention() {
+        let mut video = rendition(Kind::Video, "video/720p", Some(2_500_000));
+        assert_eq!(video.dir_name(), "video_720p");
+
+        for group_id in 0..4 {
+            let expired = video.push(group_id + 5, group_id * 180_000, 180_000, 1000, 3);
+            assert_eq!(expired.len(), usize::from(group_id == 3));
+        }
+
+        let groups: Vec<_> = video.segments().map(|s| s.group_id).collect();
+        assert_eq!(groups, [6, 7, 8]);
+        assert_eq!(video.origin, Some(0));
+
+        let playlist = media_playlist(&video);
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:2\n"));
+        assert!(playlist.contains("#EXT-X-MEDIA-SEQUENCE:1\n"));
+        assert!(playlist.contains("#EXTINF:2.000,\n6.m4s\n"));
+        assert!(!playlist.contains("#EXT-X-ENDLIST"));
+
+        video.ended = true;
+        assert!(media_playlist(&video).ends_with("8.m4s\n#EXT-X-ENDLIST\n"));
+    }
+
+    #[test]
+    fn manifests() {
+        let mut video = rendition(Kind::Video, "720p", Some(2_500_000));
+        let mut audio = rendition(Kind::Audio, "audio", None);
+        for group_id in 0..2 {
+            video.push(group_id, 1000 + group_id * 180_000, 180_000, 1000, 0);
+            audio.push(group_id, group_id * 180_000, 180_000, 32_000, 0);
+        }
+
+        // 32KB over 2s.
+        assert_eq!(audio.bandwidth(), 128_000);
+
+        let renditions = [video, audio];
+        let master = master_playlist(&renditions);
+        assert!(master.contains(
+            "#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"audio\",NAME=\"audio\",DEFAULT=YES,AUTOSELECT=YES,URI=\"audio/playlist.m3u8\"\n"
+        ));
+        assert!(master.contains(
+            "#EXT-X-STREAM-INF:BANDWIDTH=2628000,CODECS=\"avc1.64001f,mp4a.40.2\",RESOLUTION=1280x720,AUDIO=\"audio\"\n720p/playlist.m3u8\n"
+        ));
+
+        let now = Utc::now();
+        let mpd = manifest(&renditions, now, now);
+        assert!(mpd.contains(r#"type="dynamic""#));
+        assert!(mpd.contains(r#"timeShiftBufferDepth="PT4.000S""#));
+        assert!(mpd.contains(r#"<SegmentList timescale="90000" presentationTimeOffset="1000">"#));
+        assert!(mpd.contains(r#"<S t="181000" d="180000"/>"#));
+        assert!(mpd.contains(r#"<SegmentURL media="audio/1.m4s"/>"#));
+
+        let [mut video, mut audio] = renditions;
+        video.ended = true;
+        audio.ended = true;
+        let mpd = manifest(&[video, audio], now, now);
+        assert!(mpd.contains(r#"type="static" mediaPresentationDuration="PT4.000S""#));
+    }
+}
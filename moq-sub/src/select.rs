@@ -0,0 +1,196 @@
+use moq_catalog::{Root, Track};
+
+/// Filters used to choose the tracks to play from the catalog.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Filter {
+    /// Only play tracks whose codec starts with the given prefix, ie: avc1 or mp4a.40.2
+    ///
+    /// Provide multiple times to allow multiple codecs.
+    #[arg(long = "codec")]
+    pub codecs: Vec<String>,
+
+    /// Only play video tracks up to the given width.
+    #[arg(long)]
+    pub max_width: Option<u32>,
+
+    /// Only play video tracks up to the given height.
+    #[arg(long)]
+    pub max_height: Option<u32>,
+
+    /// Only play tracks up to the given bit rate.
+    #[arg(long)]
+    pub max_bitrate: Option<u32>,
+
+    /// Only play tracks in the given language, when the catalog advertises one.
+    #[arg(long)]
+    pub lang: Option<String>,
+
+    /// Play the highest bit rate rendition allowed by the filters, instead of switching
+    /// renditions based on the measured throughput.
+    #[arg(long)]
+    pub no_abr: bool,
+}
+
+/// The kind of media carried by a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Video,
+    Audio,
+}
+
+impl Kind {
+    pub fn of(track: &Track) -> Self {
+        match track.selection_params.width.is_some() {
+            true => Kind::Video,
+            false => Kind::Audio,
+        }
+    }
+}
+
+/// The renditions chosen for each kind, sorted by increasing bit rate.
+///
+/// There's more than one rendition only when the chosen track is part of an alternate group,
+/// in which case the player switches between them.
+#[derive(Debug, Default)]
+pub struct Selection {
+    pub video: Vec<Track>,
+    pub audio: Vec<Track>,
+}
+
+impl Filter {
+    /// Returns true if the track satisfies every filter.
+    pub fn matches(&self, track: &Track) -> bool {
+        let params = &track.selection_params;
+
+        let codec = params.codec.as_deref().unwrap_or_default();
+        if !self.codecs.is_empty() && !self.codecs.iter().any(|c| codec.starts_with(c.as_str())) {
+            return false;
+        }
+
+        let within = |value: Option<u32>, max: Option<u32>| match (value, max) {
+            (Some(value), Some(max)) => value <= max,
+            _ => true,
+        };
+
+        if !within(params.width, self.max_width)
+            || !within(params.height, self.max_height)
+            || !within(params.bitrate, self.max_bitrate)
+        {
+            return false;
+        }
+
+        match (&params.language, &self.lang) {
+            (Some(language), Some(lang)) => language.eq_ignore_ascii_case(lang),
+            _ => true,
+        }
+    }
+
+    /// Choose the video and audio renditions to play from the catalog.
+    pub fn select(&self, catalog: &Root) -> Selection {
+        Selection {
+            video: self.ladder(catalog, Kind::Video),
+            audio: self.ladder(catalog, Kind::Audio),
+        }
+    }
+
+    // The first matching track of the given kind, along with its alternates.
+    fn ladder(&self, catalog: &Root, kind: Kind) -> Vec<Track> {
+        let mut candidates = catalog
+            .tracks
+            .iter()
+            .filter(|track| Kind::of(track) == kind && self.matches(track));
+
+        let first = match candidates.next() {
+            Some(first) => first,
+            None => return Vec::new(),
+        };
+
+        let mut ladder = vec![first.clone()];
+        if let Some(alt_group) = first.alt_group {
+            ladder.extend(
+                candidates
+                    .filter(|track| track.alt_group == Some(alt_group))
+                    .cloned(),
+            );
+        }
+
+        sort_ladder(&mut ladder);
+
+        if self.no_abr {
+            ladder.drain(..ladder.len() - 1);
+        }
+
+        ladder
+    }
+}
+
+/// Sort renditions by increasing bit rate, with any unknown bit rates last.
+pub fn sort_ladder(ladder: &mut [Track]) {
+    ladder.sort_by_key(|track| match track.selection_params.bitrate {
+        Some(bitrate) => (false, bitrate),
+        None => (true, 0),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(name: &str, width: Option<u32>, bitrate: u32, alt_group: Option<u16>) -> Track {
+        let mut track = Track {
+            name: name.to_string(),
+            alt_group,
+            ..Default::default()
+        };
+        let codec = if width.is_some() {
+            "avc1.64001f"
+        } else {
+            "mp4a.40.2"
+        };
+        track.selection_params.codec = Some(codec.to_string());
+        track.selection_params.width = width;
+        track.selection_params.bitrate = Some(bitrate);
+        track
+    }
+
+    fn catalog(tracks: Vec<Track>) -> Root {
+        Root {
+            version: 1,
+            streaming_format: 1,
+            streaming_format_version: "0.2".to_string(),
+            streaming_delta_updates: true,
+            common_track_fields: Default::default(),
+            tracks,
+        }
+    }
+
+    #[test]
+    fn select_ladder() {
+        let catalog = catalog(vec![
+            track("1080p", Some(1920), 4_500_000, Some(1)),
+            track("720p", Some(1280), 2_500_000, Some(1)),
+            track("360p", Some(640), 800_000, Some(1)),
+            track("audio", None, 128_000, None),
+        ]);
+
+        let filter = Filter {
+            max_width: Some(1280),
+            ..Default::default()
+        };
+        let selection = filter.select(&catalog);
+
+        let names: Vec<_> = selection.video.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["360p", "720p"]);
+        assert_eq!(selection.audio[0].name, "audio");
+
+        let filter = Filter {
+            codecs: vec!["avc1".to_string()],
+            no_abr: true,
+            ..Default::default()
+        };
+        let selection = filter.select(&catalog);
+        assert_eq!(selection.video.len(), 1);
+        assert_eq!(selection.video[0].name, "1080p");
+        assert!(selection.audio.is_empty());
+    }
+}
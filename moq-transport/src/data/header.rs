@@ -44,6 +44,40 @@ impl StreamHeaderType {
         )
     }
 
+    /// The subgroup ID is not encoded in the header, instead it is the Object ID of the first object.
+    pub fn has_first_object_id(&self) -> bool {
+        matches!(
+            *self,
+            StreamHeaderType::SubgroupFirstObjectId
+                | StreamHeaderType::SubgroupFirstObjectIdExt
+                | StreamHeaderType::SubgroupFirstObjectIdEndOfGroup
+                | StreamHeaderType::SubgroupFirstObjectIdExtEndOfGroup
+        )
+    }
+
+    /// The subgroup contains the last object of the group.
+    pub fn is_end_of_group(&self) -> bool {
+        let header_type = *self as u64;
+        (0x18..=0x1d).contains(&header_type)
+    }
+
+    /// Returns the *EndOfGroup variant of a subgroup header type, or itself if there is none.
+    pub fn with_end_of_group(&self) -> Self {
+        match *self {
+            StreamHeaderType::SubgroupZeroId => StreamHeaderType::SubgroupZeroIdEndOfGroup,
+            StreamHeaderType::SubgroupZeroIdExt => StreamHeaderType::SubgroupZeroIdExtEndOfGroup,
+            StreamHeaderType::SubgroupFirstObjectId => {
+                StreamHeaderType::SubgroupFirstObjectIdEndOfGroup
+            }
+            StreamHeaderType::SubgroupFirstObjectIdExt => {
+                StreamHeaderType::SubgroupFirstObjectIdExtEndOfGroup
+            }
+            StreamHeaderType::SubgroupId => StreamHeaderType::SubgroupIdEndOfGroup,
+            StreamHeaderType::SubgroupIdExt => StreamHeaderType::SubgroupIdExtEndOfGroup,
+            other => other,
+        }
+    }
+
     pub fn has_subgroup_id(&self) -> bool {
         matches!(
             *self,
@@ -256,6 +290,29 @@ mod tests {
         assert!(!ht.has_subgroup_id());
     }
 
+    #[test]
+    fn stream_header_type_end_of_group() {
+        let ht = StreamHeaderType::SubgroupIdExt;
+        assert!(!ht.is_end_of_group());
+        assert_eq!(
+            ht.with_end_of_group(),
+            StreamHeaderType::SubgroupIdExtEndOfGroup
+        );
+        assert!(ht.with_end_of_group().is_end_of_group());
+        assert!(ht.with_end_of_group().has_subgroup_id());
+        assert!(ht.with_end_of_group().has_extension_headers());
+
+        let ht = StreamHeaderType::SubgroupFirstObjectId.with_end_of_group();
+        assert_eq!(ht, StreamHeaderType::SubgroupFirstObjectIdEndOfGroup);
+        assert!(ht.has_first_object_id());
+        assert!(!ht.has_subgroup_id());
+
+        assert_eq!(
+            StreamHeaderType::Fetch.with_end_of_group(),
+            StreamHeaderType::Fetch
+        );
+    }
+
     #[test]
     fn decode_bad_stream_header_type() {
         let data: Vec<u8> = vec![0x00]; // Invalid filter type
@@ -1,28 +1,10 @@
-// TODO: Unimplemented control message events (not yet needed for basic relay interop testing):
-// - SubscribeUpdate (parsed/created)
-// - PublishNamespaceDone (parsed/created)
-// - PublishNamespaceCancel (parsed/created)
-// - TrackStatus, TrackStatusOk, TrackStatusError (parsed/created)
-// - SubscribeNamespace, SubscribeNamespaceOk, SubscribeNamespaceError, UnsubscribeNamespace (parsed/created)
-// - Fetch, FetchOk, FetchError, FetchCancel (parsed/created)
-// - Publish, PublishOk, PublishError, PublishDone (parsed/created)
-// - MaxRequestId (parsed/created)
-// - RequestsBlocked (parsed/created)
-//
-// TODO: Unimplemented data plane events (from draft-pardue-moq-qlog-moq-events):
-// - stream_type_set (when stream type becomes known)
-// - object_datagram_status_created/parsed
-// - fetch_header_created/parsed
-// - fetch_object_created/parsed
-//
-// TODO: stream_id field currently uses placeholder value (0)
-// - Need to plumb actual QUIC stream IDs through web_transport abstractions
-// - This would enable correlation between QUIC qlog and MoQ mlog events
+// The stream_id fields are the QUIC stream IDs, so events can be correlated with qlog.
+// Datagrams use the stream that established the session.
 
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 
-use crate::{coding, data, message, setup};
+use crate::{coding, data, message, message::Message, setup};
 
 /// MoQ Transport event following qlog patterns
 #[serde_with::skip_serializing_none]
@@ -66,6 +48,27 @@ pub enum EventData {
     #[serde(rename = "object_datagram_created")]
     ObjectDatagramCreated(ObjectDatagramCreated),
 
+    #[serde(rename = "object_datagram_status_parsed")]
+    ObjectDatagramStatusParsed(ObjectDatagramStatusParsed),
+
+    #[serde(rename = "object_datagram_status_created")]
+    ObjectDatagramStatusCreated(ObjectDatagramStatusCreated),
+
+    #[serde(rename = "fetch_header_parsed")]
+    FetchHeaderParsed(FetchHeaderParsed),
+
+    #[serde(rename = "fetch_header_created")]
+    FetchHeaderCreated(FetchHeaderCreated),
+
+    #[serde(rename = "fetch_object_parsed")]
+    FetchObjectParsed(FetchObjectParsed),
+
+    #[serde(rename = "fetch_object_created")]
+    FetchObjectCreated(FetchObjectCreated),
+
+    #[serde(rename = "stream_type_set")]
+    StreamTypeSet(StreamTypeSet),
+
     #[serde(rename = "loglevel")]
     LogLevel(LogLevelEvent),
 }
@@ -160,6 +163,84 @@ pub struct ObjectDatagramCreated {
     pub object: JsonValue,
 }
 
+/// Object Datagram Status parsed event (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectDatagramStatusParsed {
+    pub stream_id: u64,
+
+    /// Object-specific fields
+    #[serde(flatten)]
+    pub object: JsonValue,
+}
+
+/// Object Datagram Status created event (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectDatagramStatusCreated {
+    pub stream_id: u64,
+
+    /// Object-specific fields
+    #[serde(flatten)]
+    pub object: JsonValue,
+}
+
+/// Fetch header parsed event (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchHeaderParsed {
+    pub stream_id: u64,
+
+    /// Header-specific fields
+    #[serde(flatten)]
+    pub header: JsonValue,
+}
+
+/// Fetch header created event (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchHeaderCreated {
+    pub stream_id: u64,
+
+    /// Header-specific fields
+    #[serde(flatten)]
+    pub header: JsonValue,
+}
+
+/// Fetch object parsed event (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchObjectParsed {
+    pub stream_id: u64,
+
+    /// Object-specific fields
+    #[serde(flatten)]
+    pub object: JsonValue,
+}
+
+/// Fetch object created event (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchObjectCreated {
+    pub stream_id: u64,
+
+    /// Object-specific fields
+    #[serde(flatten)]
+    pub object: JsonValue,
+}
+
+/// Stream type set event, emitted once the type of a stream is known (data plane)
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamTypeSet {
+    /// "local" or "remote", depending on which endpoint opened the stream
+    pub owner: Option<String>,
+    pub stream_id: u64,
+
+    /// "control", "subgroup_header" or "fetch_header"
+    pub stream_type: String,
+}
+
 /// LogLevel event for flexible logging (qlog loglevel schema)
 /// See: https://www.ietf.org/archive/id/draft-ietf-quic-qlog-main-schema-12.html#name-loglevel-events
 #[serde_with::skip_serializing_none]
@@ -207,18 +288,52 @@ fn create_control_message_event(
 
 /// Create a control_message_parsed event for CLIENT_SETUP
 pub fn client_setup_parsed(time: f64, stream_id: u64, msg: &setup::Client) -> Event {
-    let versions: Vec<String> = msg.versions.0.iter().map(|v| format!("{:?}", v)).collect();
     create_control_message_event(
         time,
         stream_id,
         true,
         "client_setup",
-        json!(
-        {
-            "number_of_supported_versions": msg.versions.0.len(),
-            "supported_versions": versions,
-            "parameters": key_value_pairs_to_vec(&msg.params.0),
-        }),
+        client_setup_to_json(msg),
+    )
+}
+
+/// Create a control_message_created event for CLIENT_SETUP
+pub fn client_setup_created(time: f64, stream_id: u64, msg: &setup::Client) -> Event {
+    create_control_message_event(
+        time,
+        stream_id,
+        false,
+        "client_setup",
+        client_setup_to_json(msg),
+    )
+}
+
+/// Helper to convert CLIENT_SETUP message to JSON
+fn client_setup_to_json(msg: &setup::Client) -> JsonValue {
+    let versions: Vec<String> = msg.versions.0.iter().map(|v| format!("{:?}", v)).collect();
+    json!({
+        "number_of_supported_versions": msg.versions.0.len(),
+        "supported_versions": versions,
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    })
+}
+
+/// Helper to convert SERVER_SETUP message to JSON
+fn server_setup_to_json(msg: &setup::Server) -> JsonValue {
+    json!({
+        "selected_version": format!("{:?}", msg.version),
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    })
+}
+
+/// Create a control_message_parsed event for SERVER_SETUP
+pub fn server_setup_parsed(time: f64, stream_id: u64, msg: &setup::Server) -> Event {
+    create_control_message_event(
+        time,
+        stream_id,
+        true,
+        "server_setup",
+        server_setup_to_json(msg),
     )
 }
 
@@ -229,11 +344,7 @@ pub fn server_setup_created(time: f64, stream_id: u64, msg: &setup::Server) -> E
         stream_id,
         false,
         "server_setup",
-        json!(
-        {
-            "selected_version": format!("{:?}", msg.version),
-            "parameters": key_value_pairs_to_vec(&msg.params.0),
-        }),
+        server_setup_to_json(msg),
     )
 }
 
@@ -250,27 +361,11 @@ fn subscribe_to_json(msg: &message::Subscribe) -> JsonValue {
     });
 
     // Add optional fields based on filter type
-    if let Some(start_loc) = &msg.start_location {
-        json["start_group"] = json!(start_loc.group_id);
-        json["start_object"] = json!(start_loc.object_id);
-    }
-    if let Some(end_group) = msg.end_group_id {
-        json["end_group"] = json!(end_group);
-    }
+    add_subscription_range(&mut json, msg.start_location.as_ref(), msg.end_group_id);
 
     json
 }
 
-/// Create a control_message_parsed event for SUBSCRIBE
-pub fn subscribe_parsed(time: f64, stream_id: u64, msg: &message::Subscribe) -> Event {
-    create_control_message_event(time, stream_id, true, "subscribe", subscribe_to_json(msg))
-}
-
-/// Create a control_message_created event for SUBSCRIBE
-pub fn subscribe_created(time: f64, stream_id: u64, msg: &message::Subscribe) -> Event {
-    create_control_message_event(time, stream_id, false, "subscribe", subscribe_to_json(msg))
-}
-
 /// Helper to convert SUBSCRIBE_OK message to JSON
 fn subscribe_ok_to_json(msg: &message::SubscribeOk) -> JsonValue {
     let mut json = json!({
@@ -283,234 +378,332 @@ fn subscribe_ok_to_json(msg: &message::SubscribeOk) -> JsonValue {
     });
 
     // Add optional largest_location fields if content exists
-    if msg.content_exists {
-        if let Some(largest) = &msg.largest_location {
-            json["largest_group_id"] = json!(largest.group_id);
-            json["largest_object_id"] = json!(largest.object_id);
-        }
-    }
+    add_largest_location(&mut json, msg.content_exists, msg.largest_location.as_ref());
 
     json
 }
 
-/// Create a control_message_parsed event for SUBSCRIBE_OK
-pub fn subscribe_ok_parsed(time: f64, stream_id: u64, msg: &message::SubscribeOk) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "subscribe_ok",
-        subscribe_ok_to_json(msg),
-    )
+/// Helper to convert SUBSCRIBE_ERROR message to JSON
+fn subscribe_error_to_json(msg: &message::SubscribeError) -> JsonValue {
+    json!({
+        "subscribe_id": msg.id,
+        "error_code": msg.error_code,
+        "reason_phrase": &msg.reason_phrase.0,
+    })
 }
 
-/// Create a control_message_created event for SUBSCRIBE_OK
-pub fn subscribe_ok_created(time: f64, stream_id: u64, msg: &message::SubscribeOk) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "subscribe_ok",
-        subscribe_ok_to_json(msg),
-    )
+/// Helper to convert PUBLISH_NAMESPACE message to JSON
+fn publish_namespace_to_json(msg: &message::PublishNamespace) -> JsonValue {
+    json!({
+        "request_id": msg.id,
+        "track_namespace": msg.track_namespace.to_string(),
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    })
 }
 
-/// Helper to convert SUBSCRIBE_ERROR message to JSON
-fn subscribe_error_to_json(msg: &message::SubscribeError) -> JsonValue {
+/// Helper to convert PUBLISH_NAMESPACE_OK message to JSON
+fn publish_namespace_ok_to_json(msg: &message::PublishNamespaceOk) -> JsonValue {
     json!({
-        "subscribe_id": msg.id,
+        "request_id": msg.id,
+    })
+}
+
+/// Helper to convert PUBLISH_NAMESPACE_ERROR message to JSON
+fn publish_namespace_error_to_json(msg: &message::PublishNamespaceError) -> JsonValue {
+    json!({
+        "request_id": msg.id,
         "error_code": msg.error_code,
         "reason_phrase": &msg.reason_phrase.0,
     })
 }
 
-/// Create a control_message_parsed event for SUBSCRIBE_ERROR
-pub fn subscribe_error_parsed(time: f64, stream_id: u64, msg: &message::SubscribeError) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "subscribe_error",
-        subscribe_error_to_json(msg),
-    )
+/// Helper to convert UNSUBSCRIBE message to JSON
+fn unsubscribe_to_json(msg: &message::Unsubscribe) -> JsonValue {
+    json!({
+        "subscribe_id": msg.id,
+    })
 }
 
-/// Create a control_message_created event for SUBSCRIBE_ERROR
-pub fn subscribe_error_created(time: f64, stream_id: u64, msg: &message::SubscribeError) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "subscribe_error",
-        subscribe_error_to_json(msg),
-    )
+/// Helper to convert GOAWAY message to JSON
+fn go_away_to_json(msg: &message::GoAway) -> JsonValue {
+    json!({
+        "new_session_uri": &msg.uri.0,
+    })
 }
 
-/// Helper to convert PUBLISH_NAMESPACE message to JSON
-fn publish_namespace_to_json(msg: &message::PublishNamespace) -> JsonValue {
+/// Helper to add the optional start and end of a subscription to JSON
+fn add_subscription_range(
+    json: &mut JsonValue,
+    start_location: Option<&coding::Location>,
+    end_group_id: Option<u64>,
+) {
+    if let Some(start_loc) = start_location {
+        json["start_group"] = json!(start_loc.group_id);
+        json["start_object"] = json!(start_loc.object_id);
+    }
+    if let Some(end_group) = end_group_id {
+        json["end_group"] = json!(end_group);
+    }
+}
+
+/// Helper to add the optional largest location to JSON, if content exists
+fn add_largest_location(
+    json: &mut JsonValue,
+    content_exists: bool,
+    largest_location: Option<&coding::Location>,
+) {
+    if let Some(largest) = largest_location.filter(|_| content_exists) {
+        json["largest_group_id"] = json!(largest.group_id);
+        json["largest_object_id"] = json!(largest.object_id);
+    }
+}
+
+/// Helper to convert the messages carrying an error code and reason phrase to JSON
+fn request_error_to_json(
+    id: u64,
+    error_code: u64,
+    reason_phrase: &coding::ReasonPhrase,
+) -> JsonValue {
+    json!({
+        "request_id": id,
+        "error_code": error_code,
+        "reason_phrase": &reason_phrase.0,
+    })
+}
+
+/// Helper to convert SUBSCRIBE_UPDATE message to JSON
+fn subscribe_update_to_json(msg: &message::SubscribeUpdate) -> JsonValue {
     json!({
         "request_id": msg.id,
-        "track_namespace": msg.track_namespace.to_string(),
+        "subscription_request_id": msg.subscription_request_id,
+        "start_group": msg.start_location.group_id,
+        "start_object": msg.start_location.object_id,
+        "end_group": msg.end_group_id,
+        "subscriber_priority": msg.subscriber_priority,
+        "forward": msg.forward,
         "parameters": key_value_pairs_to_vec(&msg.params.0),
     })
 }
 
-/// Create a control_message_parsed event for PUBLISH_NAMESPACE (was ANNOUNCE in earlier drafts)
-pub fn publish_namespace_parsed(
-    time: f64,
-    stream_id: u64,
-    msg: &message::PublishNamespace,
-) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "publish_namespace",
-        publish_namespace_to_json(msg),
-    )
+/// Helper to convert PUBLISH_NAMESPACE_DONE message to JSON
+fn publish_namespace_done_to_json(msg: &message::PublishNamespaceDone) -> JsonValue {
+    json!({
+        "track_namespace": msg.track_namespace.to_string(),
+    })
 }
 
-/// Create a control_message_created event for PUBLISH_NAMESPACE
-pub fn publish_namespace_created(
-    time: f64,
-    stream_id: u64,
-    msg: &message::PublishNamespace,
-) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "publish_namespace",
-        publish_namespace_to_json(msg),
-    )
+/// Helper to convert PUBLISH_NAMESPACE_CANCEL message to JSON
+fn publish_namespace_cancel_to_json(msg: &message::PublishNamespaceCancel) -> JsonValue {
+    json!({
+        "track_namespace": msg.track_namespace.to_string(),
+        "error_code": msg.error_code,
+        "reason_phrase": &msg.reason_phrase.0,
+    })
 }
 
-/// Helper to convert PUBLISH_NAMESPACE_OK message to JSON
-fn publish_namespace_ok_to_json(msg: &message::PublishNamespaceOk) -> JsonValue {
+/// Helper to convert TRACK_STATUS message to JSON
+fn track_status_to_json(msg: &message::TrackStatus) -> JsonValue {
+    let mut json = json!({
+        "request_id": msg.id,
+        "track_namespace": msg.track_namespace.to_string(),
+        "track_name": &msg.track_name,
+        "subscriber_priority": msg.subscriber_priority,
+        "group_order": format!("{:?}", msg.group_order),
+        "forward": msg.forward,
+        "filter_type": format!("{:?}", msg.filter_type),
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    });
+
+    add_subscription_range(&mut json, msg.start_location.as_ref(), msg.end_group_id);
+
+    json
+}
+
+/// Helper to convert TRACK_STATUS_OK message to JSON
+fn track_status_ok_to_json(msg: &message::TrackStatusOk) -> JsonValue {
+    let mut json = json!({
+        "request_id": msg.id,
+        "track_alias": msg.track_alias,
+        "expires": msg.expires,
+        "group_order": format!("{:?}", msg.group_order),
+        "content_exists": msg.content_exists,
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    });
+
+    add_largest_location(&mut json, msg.content_exists, msg.largest_location.as_ref());
+
+    json
+}
+
+/// Helper to convert SUBSCRIBE_NAMESPACE message to JSON
+fn subscribe_namespace_to_json(msg: &message::SubscribeNamespace) -> JsonValue {
     json!({
         "request_id": msg.id,
+        "track_namespace_prefix": msg.track_namespace_prefix.to_string(),
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
     })
 }
 
-/// Create a control_message_parsed event for PUBLISH_NAMESPACE_OK (was ANNOUNCE_OK)
-pub fn publish_namespace_ok_parsed(
-    time: f64,
-    stream_id: u64,
-    msg: &message::PublishNamespaceOk,
-) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "publish_namespace_ok",
-        publish_namespace_ok_to_json(msg),
-    )
+/// Helper to convert UNSUBSCRIBE_NAMESPACE message to JSON
+fn unsubscribe_namespace_to_json(msg: &message::UnsubscribeNamespace) -> JsonValue {
+    json!({
+        "track_namespace_prefix": msg.track_namespace_prefix.to_string(),
+    })
 }
 
-/// Create a control_message_created event for PUBLISH_NAMESPACE_OK
-pub fn publish_namespace_ok_created(
-    time: f64,
-    stream_id: u64,
-    msg: &message::PublishNamespaceOk,
-) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "publish_namespace_ok",
-        publish_namespace_ok_to_json(msg),
-    )
+/// Helper to convert FETCH message to JSON
+fn fetch_to_json(msg: &message::Fetch) -> JsonValue {
+    let mut json = json!({
+        "request_id": msg.id,
+        "subscriber_priority": msg.subscriber_priority,
+        "group_order": format!("{:?}", msg.group_order),
+        "fetch_type": format!("{:?}", msg.fetch_type),
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    });
+
+    if let Some(standalone) = &msg.standalone_fetch {
+        json["track_namespace"] = json!(standalone.track_namespace.to_string());
+        json["track_name"] = json!(&standalone.track_name);
+        json["start_group"] = json!(standalone.start_location.group_id);
+        json["start_object"] = json!(standalone.start_location.object_id);
+        json["end_group"] = json!(standalone.end_location.group_id);
+        json["end_object"] = json!(standalone.end_location.object_id);
+    }
+    if let Some(joining) = &msg.joining_fetch {
+        json["joining_request_id"] = json!(joining.joining_request_id);
+        json["joining_start"] = json!(joining.joining_start);
+    }
+
+    json
 }
 
-/// Helper to convert PUBLISH_NAMESPACE_ERROR message to JSON
-fn publish_namespace_error_to_json(msg: &message::PublishNamespaceError) -> JsonValue {
+/// Helper to convert FETCH_OK message to JSON
+fn fetch_ok_to_json(msg: &message::FetchOk) -> JsonValue {
     json!({
         "request_id": msg.id,
-        "error_code": msg.error_code,
-        "reason_phrase": &msg.reason_phrase.0,
+        "group_order": format!("{:?}", msg.group_order),
+        "end_of_track": msg.end_of_track,
+        "end_group": msg.end_location.group_id,
+        "end_object": msg.end_location.object_id,
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
     })
 }
 
-/// Create a control_message_parsed event for PUBLISH_NAMESPACE_ERROR (was ANNOUNCE_ERROR)
-pub fn publish_namespace_error_parsed(
-    time: f64,
-    stream_id: u64,
-    msg: &message::PublishNamespaceError,
-) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "publish_namespace_error",
-        publish_namespace_error_to_json(msg),
-    )
+/// Helper to convert PUBLISH message to JSON
+fn publish_to_json(msg: &message::Publish) -> JsonValue {
+    let mut json = json!({
+        "request_id": msg.id,
+        "track_namespace": msg.track_namespace.to_string(),
+        "track_name": &msg.track_name,
+        "track_alias": msg.track_alias,
+        "group_order": format!("{:?}", msg.group_order),
+        "content_exists": msg.content_exists,
+        "forward": msg.forward,
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    });
+
+    add_largest_location(&mut json, msg.content_exists, msg.largest_location.as_ref());
+
+    json
 }
 
-/// Create a control_message_created event for PUBLISH_NAMESPACE_ERROR
-pub fn publish_namespace_error_created(
-    time: f64,
-    stream_id: u64,
-    msg: &message::PublishNamespaceError,
-) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "publish_namespace_error",
-        publish_namespace_error_to_json(msg),
-    )
+/// Helper to convert PUBLISH_OK message to JSON
+fn publish_ok_to_json(msg: &message::PublishOk) -> JsonValue {
+    let mut json = json!({
+        "request_id": msg.id,
+        "forward": msg.forward,
+        "subscriber_priority": msg.subscriber_priority,
+        "group_order": format!("{:?}", msg.group_order),
+        "filter_type": format!("{:?}", msg.filter_type),
+        "parameters": key_value_pairs_to_vec(&msg.params.0),
+    });
+
+    add_subscription_range(&mut json, msg.start_location.as_ref(), msg.end_group_id);
+
+    json
 }
 
-/// Create a control_message_parsed event for UNSUBSCRIBE
-pub fn unsubscribe_parsed(time: f64, stream_id: u64, msg: &message::Unsubscribe) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "unsubscribe",
-        json!({
-            "subscribe_id": msg.id,
-        }),
-    )
+/// Helper to convert PUBLISH_DONE message to JSON
+fn publish_done_to_json(msg: &message::PublishDone) -> JsonValue {
+    json!({
+        "request_id": msg.id,
+        "status_code": msg.status_code,
+        "stream_count": msg.stream_count,
+        "reason_phrase": &msg.reason.0,
+    })
 }
 
-/// Create a control_message_created event for UNSUBSCRIBE
-pub fn unsubscribe_created(time: f64, stream_id: u64, msg: &message::Unsubscribe) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "unsubscribe",
-        json!({
-            "subscribe_id": msg.id,
-        }),
-    )
+/// Helper to convert any control message to its message type and JSON
+fn control_message_to_json(msg: &Message) -> (&'static str, JsonValue) {
+    match msg {
+        Message::GoAway(m) => ("goaway", go_away_to_json(m)),
+        Message::MaxRequestId(m) => ("max_request_id", json!({ "request_id": m.request_id })),
+        Message::RequestsBlocked(m) => (
+            "requests_blocked",
+            json!({ "maximum_request_id": m.max_request_id }),
+        ),
+        Message::SubscribeUpdate(m) => ("subscribe_update", subscribe_update_to_json(m)),
+        Message::Subscribe(m) => ("subscribe", subscribe_to_json(m)),
+        Message::Unsubscribe(m) => ("unsubscribe", unsubscribe_to_json(m)),
+        Message::SubscribeOk(m) => ("subscribe_ok", subscribe_ok_to_json(m)),
+        Message::SubscribeError(m) => ("subscribe_error", subscribe_error_to_json(m)),
+        Message::PublishNamespace(m) => ("publish_namespace", publish_namespace_to_json(m)),
+        Message::PublishNamespaceDone(m) => {
+            ("publish_namespace_done", publish_namespace_done_to_json(m))
+        }
+        Message::PublishNamespaceOk(m) => ("publish_namespace_ok", publish_namespace_ok_to_json(m)),
+        Message::PublishNamespaceError(m) => (
+            "publish_namespace_error",
+            publish_namespace_error_to_json(m),
+        ),
+        Message::PublishNamespaceCancel(m) => (
+            "publish_namespace_cancel",
+            publish_namespace_cancel_to_json(m),
+        ),
+        Message::TrackStatus(m) => ("track_status", track_status_to_json(m)),
+        Message::TrackStatusOk(m) => ("track_status_ok", track_status_ok_to_json(m)),
+        Message::TrackStatusError(m) => (
+            "track_status_error",
+            request_error_to_json(m.id, m.error_code, &m.reason_phrase),
+        ),
+        Message::SubscribeNamespace(m) => ("subscribe_namespace", subscribe_namespace_to_json(m)),
+        Message::UnsubscribeNamespace(m) => {
+            ("unsubscribe_namespace", unsubscribe_namespace_to_json(m))
+        }
+        Message::SubscribeNamespaceOk(m) => {
+            ("subscribe_namespace_ok", json!({ "request_id": m.id }))
+        }
+        Message::SubscribeNamespaceError(m) => (
+            "subscribe_namespace_error",
+            request_error_to_json(m.id, m.error_code, &m.reason_phrase),
+        ),
+        Message::Fetch(m) => ("fetch", fetch_to_json(m)),
+        Message::FetchCancel(m) => ("fetch_cancel", json!({ "request_id": m.id })),
+        Message::FetchOk(m) => ("fetch_ok", fetch_ok_to_json(m)),
+        Message::FetchError(m) => (
+            "fetch_error",
+            request_error_to_json(m.id, m.error_code, &m.reason_phrase),
+        ),
+        Message::Publish(m) => ("publish", publish_to_json(m)),
+        Message::PublishDone(m) => ("publish_done", publish_done_to_json(m)),
+        Message::PublishOk(m) => ("publish_ok", publish_ok_to_json(m)),
+        Message::PublishError(m) => (
+            "publish_error",
+            request_error_to_json(m.id, m.error_code, &m.reason_phrase),
+        ),
+    }
 }
 
-/// Create a control_message_parsed event for GOAWAY
-pub fn go_away_parsed(time: f64, stream_id: u64, msg: &message::GoAway) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        true,
-        "goaway",
-        json!({
-                    "new_session_uri": &msg.uri.0,
-        }),
-    )
+/// Create a control_message_parsed event for any control message
+pub fn control_message_parsed(time: f64, stream_id: u64, msg: &Message) -> Event {
+    let (msg_type, json) = control_message_to_json(msg);
+    create_control_message_event(time, stream_id, true, msg_type, json)
 }
 
-/// Create a control_message_created event for GOAWAY
-pub fn go_away_created(time: f64, stream_id: u64, msg: &message::GoAway) -> Event {
-    create_control_message_event(
-        time,
-        stream_id,
-        false,
-        "goaway",
-        json!({
-                    "new_session_uri": &msg.uri.0,
-        }),
-    )
+/// Create a control_message_created event for any control message
+pub fn control_message_created(time: f64, stream_id: u64, msg: &Message) -> Event {
+    let (msg_type, json) = control_message_to_json(msg);
+    create_control_message_event(time, stream_id, false, msg_type, json)
 }
 
 // Data plane events
@@ -723,6 +916,185 @@ pub fn object_datagram_created(time: f64, stream_id: u64, datagram: &data::Datag
     }
 }
 
+/// Create a object_datagram_status_parsed event
+pub fn object_datagram_status_parsed(
+    time: f64,
+    stream_id: u64,
+    datagram: &data::Datagram,
+) -> Event {
+    Event {
+        time,
+        name: "moqt:object_datagram_status_parsed".to_string(),
+        data: EventData::ObjectDatagramStatusParsed(ObjectDatagramStatusParsed {
+            stream_id,
+            object: object_datagram_to_json(datagram),
+        }),
+    }
+}
+
+/// Create a object_datagram_status_created event
+pub fn object_datagram_status_created(
+    time: f64,
+    stream_id: u64,
+    datagram: &data::Datagram,
+) -> Event {
+    Event {
+        time,
+        name: "moqt:object_datagram_status_created".to_string(),
+        data: EventData::ObjectDatagramStatusCreated(ObjectDatagramStatusCreated {
+            stream_id,
+            object: object_datagram_to_json(datagram),
+        }),
+    }
+}
+
+/// Create an object_datagram_parsed event, or object_datagram_status_parsed for status datagrams
+pub fn datagram_parsed(time: f64, stream_id: u64, datagram: &data::Datagram) -> Event {
+    match datagram.status {
+        Some(_) => object_datagram_status_parsed(time, stream_id, datagram),
+        None => object_datagram_parsed(time, stream_id, datagram),
+    }
+}
+
+/// Create an object_datagram_created event, or object_datagram_status_created for status datagrams
+pub fn datagram_created(time: f64, stream_id: u64, datagram: &data::Datagram) -> Event {
+    match datagram.status {
+        Some(_) => object_datagram_status_created(time, stream_id, datagram),
+        None => object_datagram_created(time, stream_id, datagram),
+    }
+}
+
+/// Helper to convert FetchHeader to JSON
+fn fetch_header_to_json(header: &data::FetchHeader) -> JsonValue {
+    json!({
+        "request_id": header.request_id,
+    })
+}
+
+/// Create a fetch_header_parsed event
+pub fn fetch_header_parsed(time: f64, stream_id: u64, header: &data::FetchHeader) -> Event {
+    Event {
+        time,
+        name: "moqt:fetch_header_parsed".to_string(),
+        data: EventData::FetchHeaderParsed(FetchHeaderParsed {
+            stream_id,
+            header: fetch_header_to_json(header),
+        }),
+    }
+}
+
+/// Create a fetch_header_created event
+pub fn fetch_header_created(time: f64, stream_id: u64, header: &data::FetchHeader) -> Event {
+    Event {
+        time,
+        name: "moqt:fetch_header_created".to_string(),
+        data: EventData::FetchHeaderCreated(FetchHeaderCreated {
+            stream_id,
+            header: fetch_header_to_json(header),
+        }),
+    }
+}
+
+/// Helper to convert FetchObject to JSON
+fn fetch_object_to_json(object: &data::FetchObject) -> JsonValue {
+    let mut json = json!({
+        "group_id": object.group_id,
+        "subgroup_id": object.subgroup_id,
+        "object_id": object.object_id,
+        "publisher_priority": object.publisher_priority,
+        "extension_headers": key_value_pairs_to_vec(&object.extension_headers.0),
+        // TODO send object_playload itself
+        "object_payload_length": object.payload_length,
+    });
+
+    if let Some(status) = object.status {
+        json["object_status"] = json!(format!("{:?}", status));
+    }
+
+    json
+}
+
+/// Create a fetch_object_parsed event
+pub fn fetch_object_parsed(time: f64, stream_id: u64, object: &data::FetchObject) -> Event {
+    Event {
+        time,
+        name: "moqt:fetch_object_parsed".to_string(),
+        data: EventData::FetchObjectParsed(FetchObjectParsed {
+            stream_id,
+            object: fetch_object_to_json(object),
+        }),
+    }
+}
+
+/// Create a fetch_object_created event
+pub fn fetch_object_created(time: f64, stream_id: u64, object: &data::FetchObject) -> Event {
+    Event {
+        time,
+        name: "moqt:fetch_object_created".to_string(),
+        data: EventData::FetchObjectCreated(FetchObjectCreated {
+            stream_id,
+            object: fetch_object_to_json(object),
+        }),
+    }
+}
+
+/// Which endpoint opened a stream
+#[derive(Debug, Clone, Copy)]
+pub enum StreamOwner {
+    Local,
+    Remote,
+}
+
+/// The type of a stream, once known
+#[derive(Debug, Clone, Copy)]
+pub enum StreamType {
+    Control,
+    Subgroup,
+    Fetch,
+}
+
+impl StreamType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            StreamType::Control => "control",
+            StreamType::Subgroup => "subgroup_header",
+            StreamType::Fetch => "fetch_header",
+        }
+    }
+}
+
+impl From<data::StreamHeaderType> for StreamType {
+    fn from(header_type: data::StreamHeaderType) -> Self {
+        match header_type {
+            data::StreamHeaderType::Fetch => StreamType::Fetch,
+            _ => StreamType::Subgroup,
+        }
+    }
+}
+
+/// Create a stream_type_set event
+pub fn stream_type_set(
+    time: f64,
+    stream_id: u64,
+    owner: StreamOwner,
+    stream_type: StreamType,
+) -> Event {
+    let owner = match owner {
+        StreamOwner::Local => "local",
+        StreamOwner::Remote => "remote",
+    };
+
+    Event {
+        time,
+        name: "moqt:stream_type_set".to_string(),
+        data: EventData::StreamTypeSet(StreamTypeSet {
+            owner: Some(owner.to_string()),
+            stream_id,
+            stream_type: stream_type.as_str().to_string(),
+        }),
+    }
+}
+
 // LogLevel events (generic logging)
 
 /// Log levels for qlog loglevel events
@@ -771,3 +1143,73 @@ pub fn loglevel_event(time: f64, level: LogLevel, message: String) -> Event {
         data: EventData::LogLevel(LogLevelEvent { message }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_json(event: &Event) -> JsonValue {
+        serde_json::to_value(event).unwrap()
+    }
+
+    #[test]
+    fn control_messages() {
+        let msg = Message::MaxRequestId(message::MaxRequestId { request_id: 5 });
+        let json = to_json(&control_message_parsed(1.0, 0, &msg));
+        assert_eq!(json["name"], "moqt:control_message_parsed");
+        assert_eq!(json["data"]["message_type"], "max_request_id");
+        assert_eq!(json["data"]["request_id"], 5);
+
+        let msg = Message::PublishNamespaceDone(message::PublishNamespaceDone {
+            track_namespace: coding::TrackNamespace::from_utf8_path("live/bbb"),
+        });
+        let json = to_json(&control_message_created(1.0, 0, &msg));
+        assert_eq!(json["name"], "moqt:control_message_created");
+        assert_eq!(json["data"]["message_type"], "publish_namespace_done");
+        assert!(json["data"]["track_namespace"].is_string());
+    }
+
+    #[test]
+    fn data_events() {
+        let datagram = data::Datagram {
+            datagram_type: data::DatagramType::ObjectIdStatus,
+            track_alias: 1,
+            group_id: 2,
+            object_id: Some(3),
+            publisher_priority: 0,
+            extension_headers: None,
+            status: Some(data::ObjectStatus::EndOfGroup),
+            payload: None,
+        };
+        let json = to_json(&datagram_parsed(1.0, 0, &datagram));
+        assert_eq!(json["name"], "moqt:object_datagram_status_parsed");
+        assert_eq!(json["data"]["event_type"], "object_datagram_status_parsed");
+        assert_eq!(json["data"]["object_status"], "EndOfGroup");
+
+        let json = to_json(&stream_type_set(
+            1.0,
+            2,
+            StreamOwner::Remote,
+            data::StreamHeaderType::Fetch.into(),
+        ));
+        assert_eq!(json["data"]["owner"], "remote");
+        assert_eq!(json["data"]["stream_type"], "fetch_header");
+
+        let object = data::FetchObject {
+            group_id: 1,
+            subgroup_id: 0,
+            object_id: 4,
+            publisher_priority: 0,
+            extension_headers: Default::default(),
+            payload_length: 10,
+            status: None,
+        };
+        let json = to_json(&fetch_object_parsed(1.0, 3, &object));
+        assert_eq!(json["data"]["event_type"], "fetch_object_parsed");
+        assert_eq!(json["data"]["object_id"], 4);
+
+        // Traces read the events back, so they must deserialize into the same variant.
+        let event: Event = serde_json::from_value(json).unwrap();
+        assert!(matches!(event.data, EventData::FetchObjectParsed(_)));
+    }
+}
@@ -3,8 +3,10 @@
 //! Based on draft-pardue-moq-qlog-moq-events but adapted for MoQ Transport draft-14
 //! This creates qlog-compatible JSON-SEQ files that can be aggregated with QUIC qlog files
 
+mod sink;
 mod writer;
-pub use writer::MlogWriter;
+pub use sink::{ChannelSink, FileSink, MlogSink, MlogThread, Rotation, Sampler};
+pub use writer::{Header, MlogWriter, VantagePoint};
 
 pub mod events;
 pub use events::{
@@ -0,0 +1,472 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Event, Header};
+
+/// A destination for mlog events.
+pub trait MlogSink: Send {
+    /// Write a single event, which may be buffered.
+    fn write(&mut self, event: Event) -> io::Result<()>;
+
+    /// Flush any buffered events.
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+impl<S: MlogSink + ?Sized> MlogSink for Box<S> {
+    fn write(&mut self, event: Event) -> io::Result<()> {
+        (**self).write(event)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+/// When to start a new log file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rotation {
+    /// Start a new file once the current one is at least this many bytes.
+    pub max_size: Option<u64>,
+
+    /// Start a new file once the current one is at least this old.
+    pub max_age: Option<Duration>,
+}
+
+/// Writes events to a file as JSON-SEQ, optionally rotating to a new file.
+///
+/// The first file uses the given path, and rotated files append a counter, ex. `{cid}_server.mlog.1`.
+/// Every file starts with the header, so each can be parsed on its own.
+pub struct FileSink {
+    path: PathBuf,
+    header: Header,
+    rotation: Rotation,
+
+    writer: BufWriter<File>,
+
+    // The number of rotated files, used for the next file name.
+    index: u64,
+
+    // The size of the current file, and when it was created.
+    size: u64,
+    created: Instant,
+
+    // The number of events in the current file, so a file is never rotated before it contains any.
+    events: u64,
+}
+
+impl FileSink {
+    pub fn create(
+        path: impl Into<PathBuf>,
+        header: Header,
+        rotation: Rotation,
+    ) -> io::Result<Self> {
+        let path = path.into();
+        let writer = BufWriter::new(File::create(&path)?);
+
+        let mut sink = Self {
+            path,
+            header,
+            rotation,
+            writer,
+            index: 0,
+            size: 0,
+            created: Instant::now(),
+            events: 0,
+        };
+
+        sink.write_header()?;
+        Ok(sink)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let header = self.header.to_json();
+        self.write_record(&header)?;
+        self.writer.flush()
+    }
+
+    fn write_record<T: serde::Serialize>(&mut self, record: &T) -> io::Result<()> {
+        let mut buf = serde_json::to_vec(record)?;
+        buf.push(b'\n');
+
+        self.writer.write_all(&buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    fn should_rotate(&self) -> bool {
+        if self.events == 0 {
+            return false;
+        }
+
+        let size = self.rotation.max_size.is_some_and(|max| self.size >= max);
+        let age = self
+            .rotation
+            .max_age
+            .is_some_and(|max| self.created.elapsed() >= max);
+
+        size || age
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        self.index += 1;
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", self.index));
+
+        self.writer = BufWriter::new(File::create(path)?);
+        self.size = 0;
+        self.created = Instant::now();
+        self.events = 0;
+
+        self.write_header()
+    }
+}
+
+impl MlogSink for FileSink {
+    fn write(&mut self, event: Event) -> io::Result<()> {
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+
+        self.write_record(&event)?;
+        self.events += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+enum Command {
+    Open(u64, Box<dyn MlogSink>),
+    Event(u64, Event),
+    Close(u64),
+}
+
+/// A background thread that writes the mlogs of every connection, so serialization and I/O are off the hot path.
+///
+/// The thread exits once every handle and [ChannelSink] has been dropped.
+#[derive(Clone)]
+pub struct MlogThread {
+    sender: mpsc::SyncSender<Command>,
+    next: Arc<AtomicU64>,
+}
+
+impl MlogThread {
+    /// Spawn the thread, buffering up to `capacity` events across all sinks.
+    pub fn spawn(capacity: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+
+        thread::Builder::new()
+            .name("mlog".to_string())
+            .spawn(move || Self::run(receiver))?;
+
+        Ok(Self {
+            sender,
+            next: Default::default(),
+        })
+    }
+
+    fn run(receiver: mpsc::Receiver<Command>) {
+        let mut sinks: HashMap<u64, Box<dyn MlogSink>> = HashMap::new();
+        let mut dirty = HashSet::new();
+
+        // Flush whenever the queue is empty, rather than after every event.
+        while let Ok(mut command) = receiver.recv() {
+            loop {
+                match command {
+                    Command::Open(id, sink) => {
+                        sinks.insert(id, sink);
+                    }
+                    Command::Event(id, event) => {
+                        // The sink is missing if it previously failed.
+                        if let Some(sink) = sinks.get_mut(&id) {
+                            match sink.write(event) {
+                                Ok(()) => {
+                                    dirty.insert(id);
+                                }
+                                Err(err) => {
+                                    log::warn!("failed to write mlog: {}", err);
+                                    sinks.remove(&id);
+                                }
+                            }
+                        }
+                    }
+                    Command::Close(id) => {
+                        dirty.remove(&id);
+                        if let Some(mut sink) = sinks.remove(&id) {
+                            if let Err(err) = sink.flush() {
+                                log::warn!("failed to flush mlog: {}", err);
+                            }
+                        }
+                    }
+                }
+
+                command = match receiver.try_recv() {
+                    Ok(command) => command,
+                    Err(_) => break,
+                };
+            }
+
+            for id in dirty.drain() {
+                let Some(sink) = sinks.get_mut(&id) else {
+                    continue;
+                };
+
+                if let Err(err) = sink.flush() {
+                    log::warn!("failed to flush mlog: {}", err);
+                    sinks.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// Start writing events to the given sink on this thread.
+    pub fn open(&self, sink: impl MlogSink + 'static) -> io::Result<ChannelSink> {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+
+        // If the channel is full, any events queued before the sink is opened are dropped.
+        send_later(&self.sender, Command::Open(id, Box::new(sink)))?;
+
+        Ok(ChannelSink(Arc::new(ChannelState {
+            id,
+            sender: self.sender.clone(),
+            dropped: Default::default(),
+        })))
+    }
+}
+
+struct ChannelState {
+    id: u64,
+    sender: mpsc::SyncSender<Command>,
+    dropped: AtomicU64,
+}
+
+impl Drop for ChannelState {
+    fn drop(&mut self) {
+        // The close can't be dropped, otherwise the sink would never be flushed or closed.
+        let _ = send_later(&self.sender, Command::Close(self.id));
+    }
+}
+
+// Send a command that must not be dropped, without blocking the caller if the channel is full.
+// Callers are usually async tasks, so a full channel is waited on by a separate thread instead.
+fn send_later(sender: &mpsc::SyncSender<Command>, command: Command) -> io::Result<()> {
+    let command = match sender.try_send(command) {
+        Ok(()) => return Ok(()),
+        Err(mpsc::TrySendError::Full(command)) => command,
+        Err(mpsc::TrySendError::Disconnected(_)) => return Err(io::ErrorKind::BrokenPipe.into()),
+    };
+
+    let sender = sender.clone();
+    thread::Builder::new()
+        .name("mlog-send".to_string())
+        .spawn(move || {
+            let _ = sender.send(command);
+        })?;
+
+    Ok(())
+}
+
+/// Queues events for a sink owned by a [MlogThread].
+///
+/// Events are dropped rather than blocking the caller when the channel is full.
+/// Clones write to the same sink, which is flushed and closed once they are all dropped.
+#[derive(Clone)]
+pub struct ChannelSink(Arc<ChannelState>);
+
+impl ChannelSink {
+    /// Queue an event to be written.
+    pub fn write(&self, event: Event) -> io::Result<()> {
+        match self.0.sender.try_send(Command::Event(self.0.id, event)) {
+            Ok(()) => Ok(()),
+            Err(mpsc::TrySendError::Full(_)) => {
+                let dropped = self.0.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped.is_power_of_two() {
+                    log::warn!("mlog channel full, dropped {} events", dropped);
+                }
+                Ok(())
+            }
+            Err(mpsc::TrySendError::Disconnected(_)) => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+
+    /// The number of events dropped because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.0.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Only keeps one in every N events of the configured types.
+///
+/// The first event of each type is always kept, and a rate of 0 drops every event of that type.
+/// Events of other types are always kept.
+#[derive(Default)]
+pub struct Sampler {
+    // The rate and the number of events seen so far, for each type.
+    rates: HashMap<String, (u64, AtomicU64)>,
+}
+
+impl Sampler {
+    /// Keep one in every `every` events with the given name, ex. `moqt:subgroup_object_parsed`.
+    pub fn rate(mut self, name: impl Into<String>, every: u64) -> Self {
+        self.rates.insert(name.into(), (every, AtomicU64::new(0)));
+        self
+    }
+
+    /// Returns true if the next event with the given name should be kept.
+    pub fn keep(&self, name: &str) -> bool {
+        let Some((every, count)) = self.rates.get(name) else {
+            return true;
+        };
+
+        if *every == 0 {
+            return false;
+        }
+
+        count.fetch_add(1, Ordering::Relaxed).is_multiple_of(*every)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::mlog::{loglevel_event, LogLevel, MlogWriter, VantagePoint};
+
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    impl MlogSink for Collect {
+        fn write(&mut self, event: Event) -> io::Result<()> {
+            self.0.lock().unwrap().push(event.name);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn event(name: &str) -> Event {
+        let mut event = loglevel_event(0.0, LogLevel::Info, "test".to_string());
+        event.name = name.to_string();
+        event
+    }
+
+    #[test]
+    fn sampling() {
+        let sampler = Sampler::default()
+            .rate("moqt:object", 3)
+            .rate("moqt:noisy", 0);
+
+        let count = |name: &str| (0..7).filter(|_| sampler.keep(name)).count();
+        assert_eq!(count("moqt:object"), 3);
+        assert_eq!(count("moqt:noisy"), 0);
+        assert_eq!(count("moqt:other"), 7);
+    }
+
+    // Wait for the background thread to write the expected number of events.
+    fn wait(collect: &Collect, len: usize) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while collect.0.lock().unwrap().len() < len {
+            assert!(Instant::now() < deadline, "timed out waiting for events");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn writer() {
+        // The channel only has room for the events that are kept.
+        let thread = MlogThread::spawn(4).unwrap();
+        let collect = Collect::default();
+
+        let sampler = Sampler::default().rate("moqt:noisy", 0);
+        let writer = MlogWriter::new(thread.open(collect.clone()).unwrap()).sampler(sampler);
+
+        for _ in 0..100 {
+            writer.add_event(event("moqt:noisy")).unwrap();
+        }
+        writer.add_event(event("moqt:other")).unwrap();
+        drop(writer);
+
+        wait(&collect, 1);
+        assert_eq!(*collect.0.lock().unwrap(), ["moqt:other"]);
+    }
+
+    #[test]
+    fn channel() {
+        let thread = MlogThread::spawn(16).unwrap();
+
+        let first = Collect::default();
+        let second = Collect::default();
+        let first_sink = thread.open(first.clone()).unwrap();
+        let second_sink = thread.open(second.clone()).unwrap();
+
+        for i in 0..6 {
+            first_sink.write(event(&format!("moqt:{i}"))).unwrap();
+        }
+        for i in 0..4 {
+            second_sink
+                .clone()
+                .write(event(&format!("moqt:{i}")))
+                .unwrap();
+        }
+
+        // Each sink is closed once all of its handles are dropped.
+        drop(first_sink);
+        drop(second_sink);
+
+        wait(&first, 6);
+        wait(&second, 4);
+
+        assert_eq!(first.0.lock().unwrap().len(), 6);
+        assert_eq!(second.0.lock().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn rotation() {
+        let dir = std::env::temp_dir().join(format!("mlog-rotation-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test_client.mlog");
+
+        let header = Header::new("test", VantagePoint::Client);
+        let rotation = Rotation {
+            max_size: Some(1),
+            max_age: None,
+        };
+
+        let mut sink = FileSink::create(&path, header, rotation).unwrap();
+        for _ in 0..3 {
+            sink.write(event("moqt:object")).unwrap();
+        }
+        sink.flush().unwrap();
+
+        // The header always exceeds the size, so every file contains a single event.
+        for name in [
+            "test_client.mlog",
+            "test_client.mlog.1",
+            "test_client.mlog.2",
+        ] {
+            let contents = std::fs::read_to_string(dir.join(name)).unwrap();
+            let lines: Vec<_> = contents.lines().collect();
+            assert_eq!(lines.len(), 2, "{name}");
+
+            let header: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+            assert_eq!(header["trace"]["vantage_point"]["type"], "client");
+            assert_eq!(header["title"], "test");
+        }
+        assert!(!dir.join("test_client.mlog.3").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,48 +1,101 @@
-use std::fs::File;
-use std::io::{self, BufWriter, Write};
-use std::path::Path;
-use std::time::Instant;
+use std::io;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use super::Event;
+use serde::{Deserialize, Serialize};
 
-/// Writer for MoQ Transport logs (mlog)
-/// Writes JSON-SEQ format compatible with qlog aggregation
-pub struct MlogWriter {
-    writer: BufWriter<File>,
-    start_time: Instant,
+use super::{ChannelSink, Event, Sampler};
+
+/// Which side of the connection wrote the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VantagePoint {
+    Client,
+    Server,
 }
 
-impl MlogWriter {
-    /// Create a new mlog writer for the given file path
-    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
-        let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
+impl VantagePoint {
+    /// The suffix used for log file names, ex. `{cid}_server.mlog`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Client => "client",
+            Self::Server => "server",
+        }
+    }
+}
+
+/// The qlog-compatible header written as the first record of every log file.
+#[derive(Debug, Clone)]
+pub struct Header {
+    /// The application writing the log, ex. `moq-relay`.
+    pub title: String,
+    pub vantage_point: VantagePoint,
+
+    /// The wall clock time that event times are relative to, in milliseconds since the UNIX epoch.
+    pub reference_time: f64,
+}
+
+impl Header {
+    pub fn new(title: impl Into<String>, vantage_point: VantagePoint) -> Self {
+        let reference_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
 
-        let start_time = Instant::now();
+        Self {
+            title: title.into(),
+            vantage_point,
+            reference_time,
+        }
+    }
 
-        // Write qlog-compatible header as first record
-        // This follows qlog JSON-SEQ format (RFC 7464)
-        let header = serde_json::json!({
+    /// Serialize the header, following the qlog JSON-SEQ format (RFC 7464).
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
             "qlog_version": "0.3",
             "qlog_format": "JSON-SEQ",
-            "title": "moq-relay",
+            "title": self.title,
             "description": "MoQ Transport events",
             "trace": {
                 "vantage_point": {
-                    "type": "server"
+                    "type": self.vantage_point
+                },
+                "common_fields": {
+                    "reference_time": self.reference_time
                 },
                 "event_schemas": [
                     "urn:ietf:params:qlog:events:loglevel",
                     "urn:ietf:params:qlog:events:moqt"
                 ]
             }
-        });
+        })
+    }
+}
+
+/// Writer for MoQ Transport logs (mlog)
+/// Timestamps events and queues them on a [ChannelSink], so clones can log without locking.
+#[derive(Clone)]
+pub struct MlogWriter {
+    sink: ChannelSink,
+    sampler: Option<Arc<Sampler>>,
+    start_time: Instant,
+}
 
-        serde_json::to_writer(&mut writer, &header)?;
-        writer.write_all(b"\n")?;
-        writer.flush()?;
+impl MlogWriter {
+    /// Create a new mlog writer using the given sink
+    pub fn new(sink: ChannelSink) -> Self {
+        Self {
+            sink,
+            sampler: None,
+            start_time: Instant::now(),
+        }
+    }
 
-        Ok(Self { writer, start_time })
+    /// Only queue the events kept by the sampler, so dropped events don't use space in the channel.
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(Arc::new(sampler));
+        self
     }
 
     /// Get elapsed time in milliseconds since connection start
@@ -51,15 +104,13 @@ impl MlogWriter {
     }
 
     /// Add an event to the log
-    pub fn add_event(&mut self, event: Event) -> io::Result<()> {
-        serde_json::to_writer(&mut self.writer, &event)?;
-        self.writer.write_all(b"\n")?;
-        self.writer.flush()?;
-        Ok(())
-    }
+    pub fn add_event(&self, event: Event) -> io::Result<()> {
+        if let Some(sampler) = &self.sampler {
+            if !sampler.keep(&event.name) {
+                return Ok(());
+            }
+        }
 
-    /// Flush and close the log
-    pub fn finish(mut self) -> io::Result<()> {
-        self.writer.flush()
+        self.sink.write(event)
     }
 }
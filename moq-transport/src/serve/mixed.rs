@@ -0,0 +1,284 @@
+//! A mixed track delivers each object either reliably on a subgroup stream or unreliably as a datagram,
+//! split into a [MixedWriter] and [MixedReader] handle.
+//!
+//! The draft allows a publisher to choose the delivery of each object within a single track, ie: keyframes
+//! on streams so they are never lost, while the delta frames that follow are sent as datagrams.
+//! A [MixedWriter] creates groups exactly like a [SubgroupsWriter], and can also write individual objects
+//! with a chosen [Delivery].
+//!
+//! A [MixedReader] is a pair of [SubgroupsReader] and [DatagramsReader] that can be consumed independently.
+//!
+//! The track is closed with [ServeError::Closed] when all writers or readers are dropped.
+use std::{cmp, ops::Deref, sync::Arc};
+
+use super::{
+    Datagram, Datagrams, DatagramsReader, DatagramsWriter, GroupBuilder, ServeError, Subgroup,
+    SubgroupWriter, Subgroups, SubgroupsReader, SubgroupsWriter, Track,
+};
+
+pub struct Mixed {
+    pub track: Arc<Track>,
+}
+
+impl Mixed {
+    pub fn produce(self) -> (MixedWriter, MixedReader) {
+        let (subgroups_writer, subgroups_reader) = Subgroups {
+            track: self.track.clone(),
+        }
+        .produce();
+
+        let (datagrams_writer, datagrams_reader) = Datagrams {
+            track: self.track.clone(),
+        }
+        .produce();
+
+        let writer = MixedWriter {
+            info: self.track.clone(),
+            subgroups: subgroups_writer,
+            datagrams: datagrams_writer,
+        };
+
+        let reader = MixedReader {
+            info: self.track,
+            subgroups: subgroups_reader,
+            datagrams: datagrams_reader,
+        };
+
+        (writer, reader)
+    }
+}
+
+impl Deref for Mixed {
+    type Target = Track;
+
+    fn deref(&self) -> &Self::Target {
+        &self.track
+    }
+}
+
+/// How a single object is delivered to subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Reliably, on a subgroup stream of its own.
+    Stream,
+
+    /// Unreliably, as a datagram.
+    Datagram,
+}
+
+/// Used to write groups and individual objects to a mixed track.
+pub struct MixedWriter {
+    pub info: Arc<Track>,
+    subgroups: SubgroupsWriter,
+    datagrams: DatagramsWriter,
+}
+
+impl MixedWriter {
+    /// Start the next group, returning a builder used to create its subgroups.
+    pub fn group(&mut self) -> GroupBuilder {
+        self.subgroups.group()
+    }
+
+    /// Helper to create the next group, containing a single subgroup with the given priority.
+    pub fn append(&mut self, priority: u8) -> Result<SubgroupWriter, ServeError> {
+        self.subgroups.append(priority)
+    }
+
+    /// Create a new subgroup with the given parameters, see [SubgroupsWriter::create].
+    pub fn create(&mut self, subgroup: Subgroup) -> Result<SubgroupWriter, ServeError> {
+        self.subgroups.create(subgroup)
+    }
+
+    /// Write a single object as a datagram.
+    pub fn datagram(&mut self, datagram: Datagram) -> Result<(), ServeError> {
+        self.subgroups.reserve_group(datagram.group_id);
+        self.datagrams.write(datagram)
+    }
+
+    /// Write a single object with the given delivery.
+    ///
+    /// Objects delivered on a stream use a subgroup containing only that object, with the next subgroup ID
+    /// that isn't in use by the group, so they don't conflict with subgroups created by a [GroupBuilder].
+    pub fn write(&mut self, delivery: Delivery, object: Datagram) -> Result<(), ServeError> {
+        match delivery {
+            Delivery::Datagram => self.datagram(object),
+            Delivery::Stream => {
+                let mut subgroup = self
+                    .subgroups
+                    .create_next(object.group_id, object.priority)?;
+
+                let extension_headers = match object.extension_headers.is_empty() {
+                    true => None,
+                    false => Some(object.extension_headers),
+                };
+
+                let mut writer = subgroup.create_at(
+                    object.object_id,
+                    object.payload.len(),
+                    extension_headers,
+                )?;
+                writer.write(object.payload)?;
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Close the track with an error.
+    pub fn close(self, err: ServeError) -> Result<(), ServeError> {
+        let subgroups = self.subgroups.close(err.clone());
+        let datagrams = self.datagrams.close(err);
+        subgroups.and(datagrams)
+    }
+}
+
+impl Deref for MixedWriter {
+    type Target = Track;
+
+    fn deref(&self) -> &Self::Target {
+        &self.info
+    }
+}
+
+/// Receives the subgroups and datagrams of a mixed track.
+#[derive(Clone)]
+pub struct MixedReader {
+    pub info: Arc<Track>,
+
+    /// Objects delivered on subgroup streams.
+    pub subgroups: SubgroupsReader,
+
+    /// Objects delivered as datagrams.
+    pub datagrams: DatagramsReader,
+}
+
+impl MixedReader {
+    // Returns the largest group/sequence across both deliveries
+    pub fn latest(&self) -> Option<(u64, u64)> {
+        cmp::max(self.subgroups.latest(), self.datagrams.latest())
+    }
+}
+
+impl Deref for MixedReader {
+    type Target = Track;
+
+    fn deref(&self) -> &Self::Target {
+        &self.info
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coding::TrackNamespace;
+    use futures::FutureExt;
+
+    fn datagram(group_id: u64, object_id: u64, payload: &'static [u8]) -> Datagram {
+        Datagram {
+            group_id,
+            object_id,
+            priority: 0,
+            payload: bytes::Bytes::from_static(payload),
+            extension_headers: Default::default(),
+        }
+    }
+
+    #[test]
+    fn keyframe_stream_deltas_datagrams() {
+        let track = Arc::new(Track::new(
+            TrackNamespace::from_utf8_path("test"),
+            "video".into(),
+        ));
+        let (mut writer, mut reader) = Mixed { track }.produce();
+
+        let mut keyframe = writer.append(0).unwrap();
+        keyframe.write(bytes::Bytes::from_static(b"key")).unwrap();
+        writer.datagram(datagram(0, 1, b"delta")).unwrap();
+
+        let mut subgroup = reader
+            .subgroups
+            .next()
+            .now_or_never()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(subgroup.group_id, 0);
+        let object = subgroup.next().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(object.object_id, 0);
+
+        let delta = reader
+            .datagrams
+            .read()
+            .now_or_never()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!((delta.group_id, delta.object_id), (0, 1));
+
+        // Datagrams also advance the group sequence used by the next appended group.
+        writer.datagram(datagram(4, 0, b"delta")).unwrap();
+        assert_eq!(writer.group().group_id(), 5);
+    }
+
+    #[test]
+    fn write_object_on_stream() {
+        let track = Arc::new(Track::new(
+            TrackNamespace::from_utf8_path("test"),
+            "video".into(),
+        ));
+        let (mut writer, mut reader) = Mixed { track }.produce();
+
+        writer
+            .write(Delivery::Stream, datagram(2, 7, b"large"))
+            .unwrap();
+
+        let mut subgroup = reader
+            .subgroups
+            .next()
+            .now_or_never()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!((subgroup.group_id, subgroup.subgroup_id), (2, 0));
+        assert!(!subgroup.end_of_group);
+
+        let mut object = subgroup.next().now_or_never().unwrap().unwrap().unwrap();
+        assert_eq!(object.object_id, 7);
+        let payload = object.read_all().now_or_never().unwrap().unwrap();
+        assert_eq!(payload.as_ref(), b"large");
+
+        assert_eq!(reader.latest(), Some((2, 7)));
+    }
+
+    #[test]
+    fn stream_objects_share_group_subgroups() {
+        let track = Arc::new(Track::new(
+            TrackNamespace::from_utf8_path("test"),
+            "video".into(),
+        ));
+        let (mut writer, mut reader) = Mixed { track }.produce();
+
+        let mut group = writer.group();
+        let base = group.subgroup(0).unwrap();
+        let enhancement = group.subgroup(1).unwrap();
+        assert_eq!((base.subgroup_id, enhancement.subgroup_id), (0, 1));
+
+        // Object 0 would collide with the base layer if it used the object ID as the subgroup ID.
+        writer
+            .write(Delivery::Stream, datagram(0, 0, b"large"))
+            .unwrap();
+        writer
+            .write(Delivery::Stream, datagram(0, 1, b"large"))
+            .unwrap();
+
+        // Subgroups created afterwards skip the IDs used by the objects.
+        let last = group.last_subgroup(2).unwrap();
+        assert_eq!(last.subgroup_id, 4);
+
+        let mut subgroups = Vec::new();
+        while let Some(Ok(Some(subgroup))) = reader.subgroups.next().now_or_never() {
+            subgroups.push((subgroup.group_id, subgroup.subgroup_id));
+        }
+        assert_eq!(subgroups, [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4)]);
+    }
+}
@@ -1,5 +1,6 @@
 mod datagram;
 mod error;
+mod mixed;
 mod object;
 mod stream;
 mod subgroup;
@@ -8,6 +9,7 @@ mod tracks;
 
 pub use datagram::*;
 pub use error::*;
+pub use mixed::*;
 pub use object::*;
 pub use stream::*;
 pub use subgroup::*;
@@ -1,13 +1,15 @@
 //! A stream is a stream of objects with a header, split into a [Writer] and [Reader] handle.
 //!
 //! A [Writer] writes an ordered stream of objects.
-//! Each object can have a sequence number, allowing the reader to detect gaps objects.
+//! Each object has an increasing object ID, allowing the reader to detect gaps.
+//! Objects may also carry an [ObjectStatus] instead of a payload, ie: to signal that an object does
+//! not exist, or that the group or track has ended.
 //!
 //! A [Reader] reads an ordered stream of objects.
 //! The reader can be cloned, in which case each reader receives a copy of each object. (fanout)
 //!
 //! The stream is closed with [ServeError::Closed] when all writers or readers are dropped.
-use std::{cmp, ops::Deref, sync::Arc};
+use std::{cmp, collections::VecDeque, ops::Deref, sync::Arc};
 
 use bytes::Bytes;
 
@@ -39,29 +41,115 @@ impl Deref for Subgroups {
     }
 }
 
+// The number of recent groups whose subgroups are delivered, including the latest group.
+// Lower priority subgroups, ie: enhancement layers, often arrive after the next group has started.
+const GROUP_WINDOW: u64 = 2;
+
 // State shared between the writer and reader.
 struct SubgroupsState {
-    latest_subgroup_reader: Option<SubgroupReader>,
-    epoch: u64, // Updated each time latest changes
+    // The subgroups of the recent groups, in the order they were created.
+    // Subgroups are removed from the front once their group falls out of the window.
+    subgroups: VecDeque<SubgroupReader>,
+
+    // The number of subgroups that have been removed from the front of the queue.
+    // Readers use this to translate their position into an index.
+    offset: usize,
+
+    // The largest group ID of any subgroup.
+    latest: Option<u64>,
+
     closed: Result<(), ServeError>,
 }
 
 impl Default for SubgroupsState {
     fn default() -> Self {
         Self {
-            latest_subgroup_reader: None,
-            epoch: 0,
+            subgroups: VecDeque::new(),
+            offset: 0,
+            latest: None,
             closed: Ok(()),
         }
     }
 }
 
+impl SubgroupsState {
+    fn latest_group_id(&self) -> Option<u64> {
+        self.latest
+    }
+
+    // Returns true if the group is too old to be delivered, as readers have moved past it.
+    fn expired(&self, group_id: u64) -> bool {
+        self.latest
+            .is_some_and(|latest| group_id.saturating_add(GROUP_WINDOW) <= latest)
+    }
+
+    fn insert(&mut self, reader: SubgroupReader) -> Result<(), ServeError> {
+        if self.expired(reader.group_id) {
+            return Ok(());
+        }
+
+        // Subgroups of the recent groups may be created in any order, but only once.
+        if self.subgroups.iter().any(|subgroup| {
+            subgroup.group_id == reader.group_id && subgroup.subgroup_id == reader.subgroup_id
+        }) {
+            return Err(ServeError::Duplicate);
+        }
+
+        if self.latest.is_none_or(|latest| reader.group_id > latest) {
+            self.latest = Some(reader.group_id);
+
+            // Remove the subgroups of groups that are no longer in the window.
+            // Any others that expired are skipped by readers, and removed once they reach the front.
+            while let Some(subgroup) = self.subgroups.front() {
+                if !self.expired(subgroup.group_id) {
+                    break;
+                }
+
+                self.subgroups.pop_front();
+                self.offset += 1;
+            }
+        }
+
+        self.subgroups.push_back(reader);
+        Ok(())
+    }
+
+    // The next subgroup ID in the group that isn't in use, after any subgroup that already exists.
+    fn next_subgroup_id(&self, group_id: u64) -> u64 {
+        self.subgroups
+            .iter()
+            .filter(|subgroup| subgroup.group_id == group_id)
+            .map(|subgroup| subgroup.subgroup_id + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn create(
+        &mut self,
+        track: Arc<Track>,
+        subgroup: Subgroup,
+    ) -> Result<SubgroupWriter, ServeError> {
+        let subgroup = SubgroupInfo {
+            track,
+            group_id: subgroup.group_id,
+            subgroup_id: subgroup.subgroup_id,
+            priority: subgroup.priority,
+            end_of_group: subgroup.end_of_group,
+        };
+        let (writer, reader) = subgroup.produce();
+
+        // Subgroups of groups outside the window aren't inserted, so readers never see them and the
+        // returned writer is unused.
+        self.insert(reader)?;
+
+        Ok(writer)
+    }
+}
+
 pub struct SubgroupsWriter {
     pub info: Arc<Track>,
     state: State<SubgroupsState>,
-    next_subgroup_id: u64, // Not in the state to avoid a lock
-    next_group_id: u64,    // Not in the state to avoid a lock
-    last_group_id: u64,    // Not in the state to avoid a lock
+    next_group_id: u64, // Not in the state to avoid a lock
 }
 
 impl SubgroupsWriter {
@@ -69,70 +157,49 @@ impl SubgroupsWriter {
         Self {
             info: track,
             state,
-            next_subgroup_id: 0,
             next_group_id: 0,
-            last_group_id: 0,
         }
     }
 
-    // Helper to increment the group by one.
-    pub fn append(&mut self, priority: u8) -> Result<SubgroupWriter, ServeError> {
-        let group_id;
-        let subgroup_id;
-
-        // TODO: refactor here... For now, every subgroup is mapped to a new group...
-        let start_new_group = true;
-
-        if start_new_group {
-            group_id = self.next_group_id;
-            subgroup_id = 0;
-        } else {
-            group_id = self.last_group_id;
-            subgroup_id = self.next_subgroup_id;
-        }
+    /// Start the next group, returning a builder used to create its subgroups.
+    pub fn group(&mut self) -> GroupBuilder {
+        let group_id = self.next_group_id;
+        self.next_group_id += 1;
 
-        self.create(Subgroup {
+        GroupBuilder {
+            info: self.info.clone(),
+            state: self.state.clone(),
             group_id,
-            subgroup_id,
-            priority,
-        })
+        }
+    }
+
+    /// Helper to create the next group, containing a single subgroup with the given priority.
+    pub fn append(&mut self, priority: u8) -> Result<SubgroupWriter, ServeError> {
+        self.group().last_subgroup(priority)
     }
 
     /// Create a new subgroup with the given parameters, inserting it into the track.
+    ///
+    /// Subgroups for the latest and previous group may be created in any order.  Subgroups for an
+    /// older group are returned but not delivered to readers, since they have already moved on.
     pub fn create(&mut self, subgroup: Subgroup) -> Result<SubgroupWriter, ServeError> {
-        let subgroup = SubgroupInfo {
-            track: self.info.clone(),
-            group_id: subgroup.group_id,
-            subgroup_id: subgroup.subgroup_id,
-            priority: subgroup.priority,
-        };
-        let (writer, reader) = subgroup.produce();
-
-        let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
-
-        if let Some(latest) = &state.latest_subgroup_reader {
-            // TODO: Check this logic again
-            if writer.group_id.cmp(&latest.group_id) == cmp::Ordering::Equal {
-                match writer.subgroup_id.cmp(&latest.subgroup_id) {
-                    cmp::Ordering::Less => return Ok(writer), // dropped immediately, lul
-                    cmp::Ordering::Equal => return Err(ServeError::Duplicate),
-                    cmp::Ordering::Greater => state.latest_subgroup_reader = Some(reader),
-                }
-            } else if writer.group_id.cmp(&latest.group_id) == cmp::Ordering::Greater {
-                state.latest_subgroup_reader = Some(reader);
-            } else {
-                return Ok(writer); // drop here as well
-            }
-        } else {
-            state.latest_subgroup_reader = Some(reader);
-        }
+        self.next_group_id = cmp::max(self.next_group_id, subgroup.group_id + 1);
+        create_subgroup(&self.state, self.info.clone(), subgroup)
+    }
 
-        self.next_subgroup_id = state.latest_subgroup_reader.as_ref().unwrap().subgroup_id + 1;
-        self.next_group_id = state.latest_subgroup_reader.as_ref().unwrap().group_id + 1;
-        self.last_group_id = state.latest_subgroup_reader.as_ref().unwrap().group_id;
-        state.epoch += 1;
+    // Create a subgroup in the given group, using the next subgroup ID that isn't in use.
+    pub(super) fn create_next(
+        &mut self,
+        group_id: u64,
+        priority: u8,
+    ) -> Result<SubgroupWriter, ServeError> {
+        self.reserve_group(group_id);
+        create_next_subgroup(&self.state, self.info.clone(), group_id, priority, false)
+    }
 
-        Ok(writer)
+    // Ensure the next group is after the given group, ie: one that was delivered as datagrams.
+    pub(super) fn reserve_group(&mut self, group_id: u64) {
+        self.next_group_id = cmp::max(self.next_group_id, group_id + 1);
     }
 
     /// Close the segment with an error.
@@ -155,11 +222,77 @@ impl Deref for SubgroupsWriter {
     }
 }
 
+fn create_subgroup(
+    state: &State<SubgroupsState>,
+    track: Arc<Track>,
+    subgroup: Subgroup,
+) -> Result<SubgroupWriter, ServeError> {
+    let mut state = state.lock_mut().ok_or(ServeError::Cancel)?;
+    state.create(track, subgroup)
+}
+
+// The subgroup ID is allocated while the state is locked, so it can't race with another writer.
+fn create_next_subgroup(
+    state: &State<SubgroupsState>,
+    track: Arc<Track>,
+    group_id: u64,
+    priority: u8,
+    end_of_group: bool,
+) -> Result<SubgroupWriter, ServeError> {
+    let mut state = state.lock_mut().ok_or(ServeError::Cancel)?;
+    let subgroup = Subgroup {
+        group_id,
+        subgroup_id: state.next_subgroup_id(group_id),
+        priority,
+        end_of_group,
+    };
+    state.create(track, subgroup)
+}
+
+/// Creates the subgroups of a single group, ie: one subgroup per temporal or spatial layer,
+/// each delivered on a separate stream with its own priority.
+pub struct GroupBuilder {
+    pub info: Arc<Track>,
+    state: State<SubgroupsState>,
+    group_id: u64,
+}
+
+impl GroupBuilder {
+    /// The sequence number of the group within the track.
+    pub fn group_id(&self) -> u64 {
+        self.group_id
+    }
+
+    /// Create the next subgroup in the group with the given priority.
+    pub fn subgroup(&mut self, priority: u8) -> Result<SubgroupWriter, ServeError> {
+        self.create(priority, false)
+    }
+
+    /// Create the final subgroup in the group with the given priority.
+    /// It contains the last object of the group, so the group ends when this subgroup ends.
+    pub fn last_subgroup(mut self, priority: u8) -> Result<SubgroupWriter, ServeError> {
+        self.create(priority, true)
+    }
+
+    // Subgroup IDs are shared with any other subgroup created in the same group, ie: by a [super::MixedWriter].
+    fn create(&mut self, priority: u8, end_of_group: bool) -> Result<SubgroupWriter, ServeError> {
+        create_next_subgroup(
+            &self.state,
+            self.info.clone(),
+            self.group_id,
+            priority,
+            end_of_group,
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct SubgroupsReader {
     pub info: Arc<Track>,
     state: State<SubgroupsState>,
-    epoch: u64,
+
+    // The position of the next subgroup to return, including subgroups removed from the state.
+    index: usize,
 }
 
 impl SubgroupsReader {
@@ -167,18 +300,25 @@ impl SubgroupsReader {
         Self {
             info: track_info,
             state,
-            epoch: 0,
+            index: 0,
         }
     }
 
+    /// Returns the next subgroup, skipping any subgroups of groups older than the previous group.
+    /// Every subgroup of the recent groups is returned, in the order they were created.
     pub async fn next(&mut self) -> Result<Option<SubgroupReader>, ServeError> {
         loop {
             {
                 let state = self.state.lock();
 
-                if self.epoch != state.epoch {
-                    self.epoch = state.epoch;
-                    return Ok(state.latest_subgroup_reader.clone());
+                let mut index = cmp::max(self.index, state.offset);
+                while let Some(subgroup) = state.subgroups.get(index - state.offset) {
+                    index += 1;
+                    self.index = index;
+
+                    if !state.expired(subgroup.group_id) {
+                        return Ok(Some(subgroup.clone()));
+                    }
                 }
 
                 state.closed.clone()?;
@@ -194,10 +334,16 @@ impl SubgroupsReader {
     // Returns the largest group/sequence
     pub fn latest(&self) -> Option<(u64, u64)> {
         let state = self.state.lock();
-        state
-            .latest_subgroup_reader
-            .as_ref()
-            .map(|group| (group.group_id, group.latest()))
+        let group_id = state.latest_group_id()?;
+        let object_id = state
+            .subgroups
+            .iter()
+            .filter(|subgroup| subgroup.group_id == group_id)
+            .map(|subgroup| subgroup.latest())
+            .max()
+            .unwrap_or_default();
+
+        Some((group_id, object_id))
     }
 }
 
@@ -222,6 +368,10 @@ pub struct Subgroup {
 
     // The priority of the group within the track.
     pub priority: u8,
+
+    // Set when this subgroup contains the last object of the group, so the group ends with the subgroup.
+    // Signalled on the wire using the *EndOfGroup subgroup header types.
+    pub end_of_group: bool,
 }
 
 /// Static information about the group
@@ -239,6 +389,10 @@ pub struct SubgroupInfo {
 
     // The priority of the group within the track.
     pub priority: u8,
+
+    // Set when this subgroup contains the last object of the group, so the group ends with the subgroup.
+    // Signalled on the wire using the *EndOfGroup subgroup header types.
+    pub end_of_group: bool,
 }
 
 impl SubgroupInfo {
@@ -288,6 +442,9 @@ pub struct SubgroupWriter {
 
     // The next object sequence number to use.
     next_object_id: u64,
+
+    // Set once an EndOfGroup or EndOfTrack status has been written, after which no more objects are allowed.
+    ended: bool,
 }
 
 impl SubgroupWriter {
@@ -296,6 +453,7 @@ impl SubgroupWriter {
             state,
             info: group,
             next_object_id: 0,
+            ended: false,
         }
     }
 
@@ -314,16 +472,78 @@ impl SubgroupWriter {
         size: usize,
         extension_headers: Option<crate::data::ExtensionHeaders>,
     ) -> Result<SubgroupObjectWriter, ServeError> {
+        self.create_at(self.next_object_id, size, extension_headers)
+    }
+
+    /// Write an object with the given object ID over multiple writes.
+    ///
+    /// The object ID must be larger than any previous object ID in the subgroup.
+    /// Any skipped object IDs are a gap, which readers will observe as such.
+    pub fn create_at(
+        &mut self,
+        object_id: u64,
+        size: usize,
+        extension_headers: Option<crate::data::ExtensionHeaders>,
+    ) -> Result<SubgroupObjectWriter, ServeError> {
+        self.insert(
+            object_id,
+            ObjectStatus::NormalObject,
+            size,
+            extension_headers,
+        )
+    }
+
+    /// Write an object with no payload and the given status, ie: to signal that the object does not exist.
+    pub fn status(
+        &mut self,
+        object_id: u64,
+        status: ObjectStatus,
+        extension_headers: Option<crate::data::ExtensionHeaders>,
+    ) -> Result<(), ServeError> {
+        self.insert(object_id, status, 0, extension_headers)?;
+        Ok(())
+    }
+
+    /// Signal the end of the group, using the next object ID.
+    pub fn end_of_group(&mut self) -> Result<(), ServeError> {
+        self.status(self.next_object_id, ObjectStatus::EndOfGroup, None)
+    }
+
+    /// Signal the end of the track, using the next object ID.
+    pub fn end_of_track(&mut self) -> Result<(), ServeError> {
+        self.status(self.next_object_id, ObjectStatus::EndOfTrack, None)
+    }
+
+    fn insert(
+        &mut self,
+        object_id: u64,
+        status: ObjectStatus,
+        size: usize,
+        extension_headers: Option<crate::data::ExtensionHeaders>,
+    ) -> Result<SubgroupObjectWriter, ServeError> {
+        if self.ended {
+            return Err(ServeError::Done);
+        }
+
+        if object_id < self.next_object_id {
+            return Err(ServeError::Duplicate);
+        }
+
+        if status != ObjectStatus::NormalObject && size != 0 {
+            return Err(ServeError::Size);
+        }
+
         let (writer, reader) = SubgroupObject {
             group: self.info.clone(),
-            object_id: self.next_object_id,
-            status: ObjectStatus::NormalObject,
+            object_id,
+            status,
             size,
             extension_headers: extension_headers.unwrap_or_default(),
         }
         .produce();
 
-        self.next_object_id += 1;
+        self.next_object_id = object_id + 1;
+        self.ended = matches!(status, ObjectStatus::EndOfGroup | ObjectStatus::EndOfTrack);
 
         let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
         state.objects.push(reader);
@@ -390,14 +610,19 @@ impl SubgroupReader {
             .unwrap_or_default()
     }
 
+    /// Read the payload of the next object, skipping over any objects that only carry a status.
     pub async fn read_next(&mut self) -> Result<Option<Bytes>, ServeError> {
-        let object = self.next().await?;
-        match object {
-            Some(mut object) => Ok(Some(object.read_all().await?)),
-            None => Ok(None),
+        loop {
+            match self.next().await? {
+                Some(object) if object.status != ObjectStatus::NormalObject => continue,
+                Some(mut object) => return Ok(Some(object.read_all().await?)),
+                None => return Ok(None),
+            }
         }
     }
 
+    /// Return the next object, including objects that only carry a status.
+    /// Object IDs are increasing but not necessarily contiguous; a gap means the objects were never sent.
     pub async fn next(&mut self) -> Result<Option<SubgroupObjectReader>, ServeError> {
         loop {
             {
@@ -627,3 +852,135 @@ impl Deref for SubgroupObjectReader {
         &self.info
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coding::TrackNamespace;
+    use futures::FutureExt;
+
+    fn subgroups() -> (SubgroupsWriter, SubgroupsReader) {
+        Subgroups {
+            track: Arc::new(Track::new(
+                TrackNamespace::from_utf8_path("test"),
+                "video".to_string(),
+            )),
+        }
+        .produce()
+    }
+
+    fn next_ids(reader: &mut SubgroupsReader) -> Option<(u64, u64)> {
+        let subgroup = reader.next().now_or_never()?.unwrap()?;
+        Some((subgroup.group_id, subgroup.subgroup_id))
+    }
+
+    #[test]
+    fn multiple_subgroups_per_group() {
+        let (mut writer, mut reader) = subgroups();
+
+        let mut group = writer.group();
+        let _base = group.subgroup(0).unwrap();
+        let _enhancement = group.last_subgroup(1).unwrap();
+
+        assert_eq!(next_ids(&mut reader), Some((0, 0)));
+        assert_eq!(next_ids(&mut reader), Some((0, 1)));
+        assert_eq!(next_ids(&mut reader), None);
+
+        // Lower subgroup ids within the latest group are still delivered
+        let _late = writer
+            .create(Subgroup {
+                group_id: 1,
+                subgroup_id: 3,
+                priority: 0,
+                end_of_group: false,
+            })
+            .unwrap();
+        let _early = writer
+            .create(Subgroup {
+                group_id: 1,
+                subgroup_id: 2,
+                priority: 0,
+                end_of_group: false,
+            })
+            .unwrap();
+
+        assert_eq!(next_ids(&mut reader), Some((1, 3)));
+        assert_eq!(next_ids(&mut reader), Some((1, 2)));
+        assert_eq!(writer.group().group_id(), 2);
+    }
+
+    #[test]
+    fn previous_group() {
+        let (mut writer, mut reader) = subgroups();
+
+        let create = |writer: &mut SubgroupsWriter, group_id, subgroup_id| {
+            writer
+                .create(Subgroup {
+                    group_id,
+                    subgroup_id,
+                    priority: subgroup_id as u8,
+                    end_of_group: false,
+                })
+                .unwrap()
+        };
+
+        // The enhancement layer of a group arrives after the base layer of the next group.
+        let _base = create(&mut writer, 5, 0);
+        let _next = create(&mut writer, 6, 0);
+        let _enhancement = create(&mut writer, 5, 1);
+
+        assert_eq!(next_ids(&mut reader), Some((5, 0)));
+        assert_eq!(next_ids(&mut reader), Some((6, 0)));
+        assert_eq!(next_ids(&mut reader), Some((5, 1)));
+        assert_eq!(next_ids(&mut reader), None);
+
+        // A reader that falls behind skips groups once they leave the window.
+        let mut late = reader.clone();
+        let _latest = create(&mut writer, 7, 0);
+        let _stale = create(&mut writer, 5, 2);
+        assert_eq!(next_ids(&mut reader), Some((7, 0)));
+        assert_eq!(next_ids(&mut reader), None);
+        assert_eq!(next_ids(&mut late), Some((7, 0)));
+        assert_eq!(reader.latest(), Some((7, 0)));
+    }
+
+    #[test]
+    fn skip_older_groups() {
+        let (mut writer, mut reader) = subgroups();
+
+        let _first = writer.append(0).unwrap();
+        let _second = writer.append(0).unwrap();
+        let _third = writer.append(0).unwrap();
+        assert_eq!(next_ids(&mut reader), Some((1, 0)));
+        assert_eq!(next_ids(&mut reader), Some((2, 0)));
+
+        // Subgroups for older groups and duplicates are not delivered
+        let _old = writer
+            .create(Subgroup {
+                group_id: 0,
+                subgroup_id: 1,
+                priority: 0,
+                end_of_group: false,
+            })
+            .unwrap();
+        assert!(matches!(
+            writer.create(Subgroup {
+                group_id: 2,
+                subgroup_id: 0,
+                priority: 0,
+                end_of_group: false,
+            }),
+            Err(ServeError::Duplicate)
+        ));
+        assert!(matches!(
+            writer.create(Subgroup {
+                group_id: 1,
+                subgroup_id: 0,
+                priority: 0,
+                end_of_group: false,
+            }),
+            Err(ServeError::Duplicate)
+        ));
+        assert_eq!(next_ids(&mut reader), None);
+    }
+}
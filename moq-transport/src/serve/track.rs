@@ -15,10 +15,11 @@
 use crate::watch::State;
 
 use super::{
-    Datagrams, DatagramsReader, DatagramsWriter, ObjectsWriter, ServeError, Stream, StreamReader,
-    StreamWriter, Subgroups, SubgroupsReader, SubgroupsWriter,
+    Datagrams, DatagramsReader, DatagramsWriter, Mixed, MixedReader, MixedWriter, ObjectsWriter,
+    ServeError, Stream, StreamWriter, Subgroups, SubgroupsReader, SubgroupsWriter,
 };
 use crate::coding::{Location, TrackNamespace};
+use crate::message::GroupOrder;
 use paste::paste;
 use std::{ops::Deref, sync::Arc};
 
@@ -50,6 +51,8 @@ impl Track {
 struct TrackState {
     /// The ReaderMode for this track. Set to None on creation.
     reader_mode: Option<TrackReaderMode>,
+    /// The publisher's preferred group order, ie: from the upstream SUBSCRIBE_OK. None if there is no preference.
+    group_order: Option<GroupOrder>,
     /// Watchable closed state
     closed: Result<(), ServeError>,
 }
@@ -58,6 +61,7 @@ impl Default for TrackState {
     fn default() -> Self {
         Self {
             reader_mode: None,
+            group_order: None,
             closed: Ok(()),
         }
     }
@@ -75,9 +79,18 @@ impl TrackWriter {
         Self { state, info }
     }
 
+    /// Set the publisher's preferred group order, used by subscriptions that defer to the publisher.
+    /// This must be called before the mode of the track is chosen, since readers wait for either.
+    pub fn set_group_order(&mut self, group_order: GroupOrder) -> Result<(), ServeError> {
+        let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
+        state.group_order = Some(group_order);
+        Ok(())
+    }
+
     /// Create a new stream with the given priority, inserting it into the track.
+    /// Each group of the stream is delivered as a single subgroup, see [Stream].
     pub fn stream(self, priority: u8) -> Result<StreamWriter, ServeError> {
-        // Create new StreamWriter/StreamReader pair
+        // Create new StreamWriter/SubgroupsReader pair
         let (writer, reader) = Stream {
             track: self.info.clone(),
             priority,
@@ -87,7 +100,7 @@ impl TrackWriter {
         // Lock state to modify it
         let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
 
-        // Set the Stream mode to TrackReaderMode::Stream
+        // Streams are served as subgroups, so set the mode to TrackReaderMode::Subgroups
         state.reader_mode = Some(reader.into());
         Ok(writer)
     }
@@ -122,6 +135,21 @@ impl TrackWriter {
         Ok(writer)
     }
 
+    /// Create a mixed track, where each object or group may be delivered on a stream or as a datagram.
+    pub fn mixed(self) -> Result<MixedWriter, ServeError> {
+        let (writer, reader) = Mixed {
+            track: self.info.clone(),
+        }
+        .produce();
+
+        // Lock state to modify it
+        let mut state = self.state.lock_mut().ok_or(ServeError::Cancel)?;
+
+        // Set the Stream mode to TrackReaderMode::Mixed
+        state.reader_mode = Some(reader.into());
+        Ok(writer)
+    }
+
     /// Close the track with an error.
     pub fn close(self, err: ServeError) -> Result<(), ServeError> {
         let state = self.state.lock();
@@ -172,6 +200,25 @@ impl TrackReader {
         }
     }
 
+    /// Get the publisher's preferred group order, waiting until it is known or the track has started without one.
+    pub async fn group_order(&self) -> Result<Option<GroupOrder>, ServeError> {
+        loop {
+            {
+                let state = self.state.lock();
+                if state.group_order.is_some() || state.reader_mode.is_some() {
+                    return Ok(state.group_order);
+                }
+
+                state.closed.clone()?;
+                match state.modified() {
+                    Some(notify) => notify,
+                    None => return Err(ServeError::Done),
+                }
+            }
+            .await;
+        }
+    }
+
     // Returns the largest group/sequence
     pub fn largest_location(&self) -> Option<Location> {
         // We don't even know the mode yet.
@@ -229,7 +276,8 @@ macro_rules! track_readers {
 	}
 }
 
-track_readers!(Stream, Subgroups, Datagrams,);
+// Only modes that can be represented on the wire can be read.
+track_readers!(Subgroups, Datagrams, Mixed,);
 
 macro_rules! track_writers {
     {$($name:ident,)*} => {
@@ -255,4 +303,32 @@ macro_rules! track_writers {
 	}
 }
 
-track_writers!(Track, Stream, Subgroups, Objects, Datagrams,);
+track_writers!(Track, Stream, Subgroups, Objects, Datagrams, Mixed,);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+
+    fn track() -> (TrackWriter, TrackReader) {
+        Track::new(TrackNamespace::from_utf8_path("test"), "video".into()).produce()
+    }
+
+    #[test]
+    fn group_order() {
+        let (mut writer, reader) = track();
+
+        // Nothing is known until the order is set or the track starts.
+        assert!(reader.group_order().now_or_never().is_none());
+
+        writer.set_group_order(GroupOrder::Ascending).unwrap();
+        let order = reader.group_order().now_or_never().unwrap().unwrap();
+        assert_eq!(order, Some(GroupOrder::Ascending));
+
+        // A track that starts without a preference defers to the default.
+        let (writer, reader) = track();
+        let _subgroups = writer.subgroups().unwrap();
+        let order = reader.group_order().now_or_never().unwrap().unwrap();
+        assert_eq!(order, None);
+    }
+}
@@ -3,33 +3,36 @@ mod announced;
 mod error;
 mod publisher;
 mod reader;
+mod scheduler;
 mod subscribe;
 mod subscribed;
 mod subscriber;
 mod track_status_requested;
+mod transport;
 mod writer;
 
 pub use announce::*;
 pub use announced::*;
 pub use error::*;
 pub use publisher::*;
+pub use scheduler::*;
 pub use subscribe::*;
 pub use subscribed::*;
 pub use subscriber::*;
 pub use track_status_requested::*;
+pub use transport::*;
 
 use reader::*;
 use writer::*;
 
 use futures::{stream::FuturesUnordered, StreamExt};
-use std::sync::{atomic, Arc, Mutex};
+use std::sync::{atomic, Arc};
 
 use crate::coding::KeyValuePairs;
 use crate::message::Message;
 use crate::mlog;
 use crate::watch::Queue;
 use crate::{message, setup};
-use std::path::PathBuf;
 
 /// Session object for managing all communications in a single QUIC connection.
 #[must_use = "run() must be called"]
@@ -46,9 +49,11 @@ pub struct Session {
     /// Queue used by Publisher and Subscriber for sending Control Messages
     outgoing: Queue<Message>,
 
-    /// Optional mlog writer for MoQ Transport events
-    /// Wrapped in Arc<Mutex<>> to share across send/recv tasks when enabled
-    mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+    /// Optional mlog writer for MoQ Transport events, cloned into the send/recv tasks
+    mlog: Option<mlog::MlogWriter>,
+
+    /// Assigns QUIC stream IDs to streams, for mlog events
+    stream_ids: Arc<StreamIds>,
 }
 
 impl Session {
@@ -66,23 +71,25 @@ impl Session {
         recver: Reader,
         first_requestid: u64,
         mlog: Option<mlog::MlogWriter>,
+        stream_ids: StreamIds,
     ) -> (Self, Option<Publisher>, Option<Subscriber>) {
         let next_requestid = Arc::new(atomic::AtomicU64::new(first_requestid));
         let outgoing = Queue::default().split();
 
-        // Wrap mlog in Arc<Mutex<>> for sharing across tasks
-        let mlog_shared = mlog.map(|m| Arc::new(Mutex::new(m)));
+        let stream_ids = Arc::new(stream_ids);
 
         let publisher = Some(Publisher::new(
             outgoing.0.clone(),
             webtransport.clone(),
             next_requestid.clone(),
-            mlog_shared.clone(),
+            mlog.clone(),
+            stream_ids.clone(),
         ));
         let subscriber = Some(Subscriber::new(
             outgoing.0,
             next_requestid,
-            mlog_shared.clone(),
+            mlog.clone(),
+            stream_ids.clone(),
         ));
 
         let session = Self {
@@ -92,7 +99,8 @@ impl Session {
             publisher: publisher.clone(),
             subscriber: subscriber.clone(),
             outgoing: outgoing.1,
-            mlog: mlog_shared,
+            mlog,
+            stream_ids,
         };
 
         (session, publisher, subscriber)
@@ -100,18 +108,27 @@ impl Session {
 
     /// Create an outbound/client QUIC connection, by opening a bi-directional QUIC stream for
     /// MOQT control messaging.  Performs SETUP messaging and version negotiation.
+    ///
+    /// The transport is used to label the optional mlog events with QUIC stream IDs.
     pub async fn connect(
         mut session: web_transport::Session,
-        mlog_path: Option<PathBuf>,
+        transport: Transport,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(Session, Publisher, Subscriber), SessionError> {
-        let mlog = mlog_path.and_then(|path| {
-            mlog::MlogWriter::new(path)
-                .map_err(|e| log::warn!("Failed to create mlog: {}", e))
-                .ok()
-        });
+        let stream_ids = StreamIds::new(transport, false);
         let control = session.open_bi().await?;
-        let mut sender = Writer::new(control.0);
-        let mut recver = Reader::new(control.1);
+        let mut sender = Writer::new(control.0, stream_ids.control());
+        let mut recver = Reader::new(control.1, stream_ids.control());
+
+        if let Some(ref mlog) = mlog {
+            let event = mlog::events::stream_type_set(
+                mlog.elapsed_ms(),
+                sender.stream_id(),
+                mlog::events::StreamOwner::Local,
+                mlog::events::StreamType::Control,
+            );
+            let _ = mlog.add_event(event);
+        }
 
         let versions: setup::Versions = [setup::Version::DRAFT_14].into();
 
@@ -127,39 +144,59 @@ impl Session {
         log::debug!("sending CLIENT_SETUP: {:?}", client);
         sender.encode(&client).await?;
 
-        // TODO: emit client_setup_created event when we add that
+        // Emit mlog event for CLIENT_SETUP created
+        if let Some(ref mlog) = mlog {
+            let event =
+                mlog::events::client_setup_created(mlog.elapsed_ms(), sender.stream_id(), &client);
+            let _ = mlog.add_event(event);
+        }
 
         let server: setup::Server = recver.decode().await?;
         log::debug!("received SERVER_SETUP: {:?}", server);
 
-        // TODO: emit server_setup_parsed event
+        // Emit mlog event for SERVER_SETUP parsed
+        if let Some(ref mlog) = mlog {
+            let event =
+                mlog::events::server_setup_parsed(mlog.elapsed_ms(), recver.stream_id(), &server);
+            let _ = mlog.add_event(event);
+        }
 
         // We are the client, so the first request id is 0
-        let session = Session::new(session, sender, recver, 0, mlog);
+        let session = Session::new(session, sender, recver, 0, mlog, stream_ids);
         Ok((session.0, session.1.unwrap(), session.2.unwrap()))
     }
 
     /// Accepts an inbound/server QUIC connection, by accepting a bi-directional QUIC stream for
     /// MOQT control messaging.  Performs SETUP messaging and version negotiation.
+    ///
+    /// The transport is used to label the optional mlog events with QUIC stream IDs.
     pub async fn accept(
         mut session: web_transport::Session,
-        mlog_path: Option<PathBuf>,
+        transport: Transport,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(Session, Option<Publisher>, Option<Subscriber>), SessionError> {
-        let mut mlog = mlog_path.and_then(|path| {
-            mlog::MlogWriter::new(path)
-                .map_err(|e| log::warn!("Failed to create mlog: {}", e))
-                .ok()
-        });
+        let stream_ids = StreamIds::new(transport, true);
         let control = session.accept_bi().await?;
-        let mut sender = Writer::new(control.0);
-        let mut recver = Reader::new(control.1);
+        let mut sender = Writer::new(control.0, stream_ids.control());
+        let mut recver = Reader::new(control.1, stream_ids.control());
+
+        if let Some(ref mlog) = mlog {
+            let event = mlog::events::stream_type_set(
+                mlog.elapsed_ms(),
+                recver.stream_id(),
+                mlog::events::StreamOwner::Remote,
+                mlog::events::StreamType::Control,
+            );
+            let _ = mlog.add_event(event);
+        }
 
         let client: setup::Client = recver.decode().await?;
         log::debug!("received CLIENT_SETUP: {:?}", client);
 
         // Emit mlog event for CLIENT_SETUP parsed
-        if let Some(ref mut mlog) = mlog {
-            let event = mlog::events::client_setup_parsed(mlog.elapsed_ms(), 0, &client);
+        if let Some(ref mlog) = mlog {
+            let event =
+                mlog::events::client_setup_parsed(mlog.elapsed_ms(), recver.stream_id(), &client);
             let _ = mlog.add_event(event);
         }
 
@@ -180,15 +217,19 @@ impl Session {
             log::debug!("sending SERVER_SETUP: {:?}", server);
 
             // Emit mlog event for SERVER_SETUP created
-            if let Some(ref mut mlog) = mlog {
-                let event = mlog::events::server_setup_created(mlog.elapsed_ms(), 0, &server);
+            if let Some(ref mlog) = mlog {
+                let event = mlog::events::server_setup_created(
+                    mlog.elapsed_ms(),
+                    sender.stream_id(),
+                    &server,
+                );
                 let _ = mlog.add_event(event);
             }
 
             sender.encode(&server).await?;
 
             // We are the server, so the first request id is 1
-            Ok(Session::new(session, sender, recver, 1, mlog))
+            Ok(Session::new(session, sender, recver, 1, mlog, stream_ids))
         } else {
             Err(SessionError::Version(client.versions, server_versions))
         }
@@ -201,7 +242,7 @@ impl Session {
         tokio::select! {
             res = Self::run_recv(self.recver, self.publisher, self.subscriber.clone(), self.mlog.clone()) => res,
             res = Self::run_send(self.sender, self.outgoing, self.mlog.clone()) => res,
-            res = Self::run_streams(self.webtransport.clone(), self.subscriber.clone()) => res,
+            res = Self::run_streams(self.webtransport.clone(), self.subscriber.clone(), self.stream_ids) => res,
             res = Self::run_datagrams(self.webtransport, self.subscriber) => res,
         }
     }
@@ -210,50 +251,16 @@ impl Session {
     async fn run_send(
         mut sender: Writer,
         mut outgoing: Queue<message::Message>,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(), SessionError> {
         while let Some(msg) = outgoing.pop().await {
             log::debug!("sending message: {:?}", msg);
 
             // Emit mlog event for sent control messages
             if let Some(ref mlog) = mlog {
-                if let Ok(mut mlog_guard) = mlog.lock() {
-                    let time = mlog_guard.elapsed_ms();
-                    let stream_id = 0; // Control stream is always stream 0
-
-                    // Emit events based on message type
-                    let event = match &msg {
-                        Message::Subscribe(m) => {
-                            Some(mlog::events::subscribe_created(time, stream_id, m))
-                        }
-                        Message::SubscribeOk(m) => {
-                            Some(mlog::events::subscribe_ok_created(time, stream_id, m))
-                        }
-                        Message::SubscribeError(m) => {
-                            Some(mlog::events::subscribe_error_created(time, stream_id, m))
-                        }
-                        Message::Unsubscribe(m) => {
-                            Some(mlog::events::unsubscribe_created(time, stream_id, m))
-                        }
-                        Message::PublishNamespace(m) => {
-                            Some(mlog::events::publish_namespace_created(time, stream_id, m))
-                        }
-                        Message::PublishNamespaceOk(m) => Some(
-                            mlog::events::publish_namespace_ok_created(time, stream_id, m),
-                        ),
-                        Message::PublishNamespaceError(m) => Some(
-                            mlog::events::publish_namespace_error_created(time, stream_id, m),
-                        ),
-                        Message::GoAway(m) => {
-                            Some(mlog::events::go_away_created(time, stream_id, m))
-                        }
-                        _ => None, // TODO: Add other message types
-                    };
-
-                    if let Some(event) = event {
-                        let _ = mlog_guard.add_event(event);
-                    }
-                }
+                let time = mlog.elapsed_ms();
+                let event = mlog::events::control_message_created(time, sender.stream_id(), &msg);
+                let _ = mlog.add_event(event);
             }
 
             sender.encode(&msg).await?;
@@ -271,7 +278,7 @@ impl Session {
         mut recver: Reader,
         mut publisher: Option<Publisher>,
         mut subscriber: Option<Subscriber>,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(), SessionError> {
         loop {
             let msg: message::Message = recver.decode().await?;
@@ -279,43 +286,9 @@ impl Session {
 
             // Emit mlog event for received control messages
             if let Some(ref mlog) = mlog {
-                if let Ok(mut mlog_guard) = mlog.lock() {
-                    let time = mlog_guard.elapsed_ms();
-                    let stream_id = 0; // Control stream is always stream 0
-
-                    // Emit events based on message type
-                    let event = match &msg {
-                        Message::Subscribe(m) => {
-                            Some(mlog::events::subscribe_parsed(time, stream_id, m))
-                        }
-                        Message::SubscribeOk(m) => {
-                            Some(mlog::events::subscribe_ok_parsed(time, stream_id, m))
-                        }
-                        Message::SubscribeError(m) => {
-                            Some(mlog::events::subscribe_error_parsed(time, stream_id, m))
-                        }
-                        Message::Unsubscribe(m) => {
-                            Some(mlog::events::unsubscribe_parsed(time, stream_id, m))
-                        }
-                        Message::PublishNamespace(m) => {
-                            Some(mlog::events::publish_namespace_parsed(time, stream_id, m))
-                        }
-                        Message::PublishNamespaceOk(m) => Some(
-                            mlog::events::publish_namespace_ok_parsed(time, stream_id, m),
-                        ),
-                        Message::PublishNamespaceError(m) => Some(
-                            mlog::events::publish_namespace_error_parsed(time, stream_id, m),
-                        ),
-                        Message::GoAway(m) => {
-                            Some(mlog::events::go_away_parsed(time, stream_id, m))
-                        }
-                        _ => None, // TODO: Add other message types
-                    };
-
-                    if let Some(event) = event {
-                        let _ = mlog_guard.add_event(event);
-                    }
-                }
+                let time = mlog.elapsed_ms();
+                let event = mlog::events::control_message_parsed(time, recver.stream_id(), &msg);
+                let _ = mlog.add_event(event);
             }
 
             let msg = match TryInto::<message::Publisher>::try_into(msg) {
@@ -355,6 +328,7 @@ impl Session {
     async fn run_streams(
         mut webtransport: web_transport::Session,
         subscriber: Option<Subscriber>,
+        stream_ids: Arc<StreamIds>,
     ) -> Result<(), SessionError> {
         let mut tasks = FuturesUnordered::new();
 
@@ -362,10 +336,11 @@ impl Session {
             tokio::select! {
                 res = webtransport.accept_uni() => {
                     let stream = res?;
+                    let stream_id = stream_ids.accept_uni();
                     let subscriber = subscriber.clone().ok_or(SessionError::RoleViolation)?;
 
                     tasks.push(async move {
-                        if let Err(err) = Subscriber::recv_stream(subscriber, stream).await {
+                        if let Err(err) = Subscriber::recv_stream(subscriber, stream, stream_id).await {
                             log::warn!("failed to serve stream: {}", err);
                         };
                     });
@@ -15,7 +15,8 @@ use crate::{
 use crate::watch::Queue;
 
 use super::{
-    Announce, AnnounceRecv, Session, SessionError, Subscribed, SubscribedRecv, TrackStatusRequested,
+    Announce, AnnounceRecv, Session, SessionError, StreamIds, Subscribed, SubscribedRecv,
+    TrackStatusRequested, Transport,
 };
 
 // TODO remove Clone.
@@ -50,7 +51,10 @@ pub struct Publisher {
     next_requestid: Arc<atomic::AtomicU64>,
 
     /// Optional mlog writer for logging transport events
-    mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+    mlog: Option<mlog::MlogWriter>,
+
+    /// Assigns QUIC stream IDs to opened streams, for logging
+    stream_ids: Arc<StreamIds>,
 }
 
 impl Publisher {
@@ -58,7 +62,8 @@ impl Publisher {
         outgoing: Queue<Message>,
         webtransport: web_transport::Session,
         next_requestid: Arc<atomic::AtomicU64>,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
+        stream_ids: Arc<StreamIds>,
     ) -> Self {
         Self {
             webtransport,
@@ -69,20 +74,21 @@ impl Publisher {
             outgoing,
             next_requestid,
             mlog,
+            stream_ids,
         }
     }
 
     pub async fn accept(
         session: web_transport::Session,
     ) -> Result<(Session, Publisher), SessionError> {
-        let (session, publisher, _) = Session::accept(session, None).await?;
+        let (session, publisher, _) = Session::accept(session, Transport::default(), None).await?;
         Ok((session, publisher.unwrap()))
     }
 
     pub async fn connect(
         session: web_transport::Session,
     ) -> Result<(Session, Publisher), SessionError> {
-        let (session, publisher, _) = Session::connect(session, None).await?;
+        let (session, publisher, _) = Session::connect(session, Transport::default(), None).await?;
         Ok((session, publisher))
     }
 
@@ -432,11 +438,24 @@ impl Publisher {
         self.announces.lock().unwrap().remove(namespace);
     }
 
-    pub(super) async fn open_uni(&mut self) -> Result<web_transport::SendStream, SessionError> {
-        Ok(self.webtransport.open_uni().await?)
+    /// Open a unidirectional stream, returning it along with its QUIC stream ID.
+    pub(super) async fn open_uni(
+        &mut self,
+    ) -> Result<(web_transport::SendStream, u64), SessionError> {
+        self.stream_ids.open_uni(&mut self.webtransport).await
+    }
+
+    /// The QUIC stream ID that datagrams are associated with.
+    pub(super) fn datagram_stream_id(&self) -> u64 {
+        self.stream_ids.session()
     }
 
     pub(super) async fn send_datagram(&mut self, data: bytes::Bytes) -> Result<(), SessionError> {
         Ok(self.webtransport.send_datagram(data).await?)
     }
+
+    /// The largest datagram that can currently be sent on the connection.
+    pub(super) async fn max_datagram_size(&self) -> usize {
+        self.webtransport.max_datagram_size().await
+    }
 }
@@ -9,16 +9,24 @@ use super::SessionError;
 pub struct Reader {
     stream: web_transport::RecvStream,
     buffer: BytesMut,
+
+    // The QUIC stream ID, for logging.
+    stream_id: u64,
 }
 
 impl Reader {
-    pub fn new(stream: web_transport::RecvStream) -> Self {
+    pub fn new(stream: web_transport::RecvStream, stream_id: u64) -> Self {
         Self {
             stream,
             buffer: Default::default(),
+            stream_id,
         }
     }
 
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
     pub async fn decode<T: Decode>(&mut self) -> Result<T, SessionError> {
         log::trace!(
             "[READER] decode: attempting to decode {} (buffer_len={})",
@@ -0,0 +1,121 @@
+use crate::message::GroupOrder;
+
+/// The group order used when the subscriber defers to the publisher (GroupOrder::Publisher),
+/// and the track has no preference of its own.
+/// Live media favours the newest group, so that is what we default to.
+pub const PUBLISHER_GROUP_ORDER: GroupOrder = GroupOrder::Descending;
+
+// Layout of the i32 send order handed to QUIC, where larger values are sent first:
+//   bit 31      unused, keeps the value positive
+//   bits 23-30  inverted subscriber priority
+//   bits 15-22  inverted publisher priority
+//   bits 0-14   group id, ordered according to the group order
+const GROUP_BITS: u32 = 15;
+const GROUP_MASK: u64 = (1 << GROUP_BITS) - 1;
+const PUBLISHER_PRIORITY_SHIFT: u32 = GROUP_BITS;
+const SUBSCRIBER_PRIORITY_SHIFT: u32 = GROUP_BITS + 8;
+
+/// Derives the QUIC send order for the subgroup streams of a subscription.
+///
+/// QUIC stream priorities apply to the whole connection, so by encoding subscriber priority,
+/// then publisher priority, then group order into a single value, streams from every subscription
+/// served by a session are scheduled against each other as described in the draft, ie: a high
+/// priority audio subscription preempts a video subscription under congestion.
+///
+/// Only the lower 15 bits of the group id are used, so the group ordering briefly inverts for
+/// streams that are in flight at the same time across a 32768 group boundary.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Scheduler {
+    /// Subscriber priority, where **smaller** values are sent first.
+    subscriber_priority: u8,
+
+    /// The resolved group order, never GroupOrder::Publisher.
+    group_order: GroupOrder,
+}
+
+impl Scheduler {
+    /// Create a scheduler for a subscription.  A group order of GroupOrder::Publisher is resolved
+    /// using the provided publisher preference.
+    pub fn new(
+        subscriber_priority: u8,
+        group_order: GroupOrder,
+        publisher_order: GroupOrder,
+    ) -> Self {
+        let group_order = match group_order {
+            GroupOrder::Publisher => match publisher_order {
+                GroupOrder::Publisher => PUBLISHER_GROUP_ORDER,
+                order => order,
+            },
+            order => order,
+        };
+
+        Self {
+            subscriber_priority,
+            group_order,
+        }
+    }
+
+    /// The group order that was negotiated, used in SUBSCRIBE_OK.
+    pub fn group_order(&self) -> GroupOrder {
+        self.group_order
+    }
+
+    /// Compute the send order for a subgroup stream, where larger values are sent first.
+    pub fn send_order(&self, publisher_priority: u8, group_id: u64) -> i32 {
+        let group = group_id & GROUP_MASK;
+        let group = match self.group_order {
+            GroupOrder::Ascending => GROUP_MASK - group,
+            _ => group,
+        };
+
+        let subscriber = (u8::MAX - self.subscriber_priority) as i32;
+        let publisher = (u8::MAX - publisher_priority) as i32;
+
+        (subscriber << SUBSCRIBER_PRIORITY_SHIFT)
+            | (publisher << PUBLISHER_PRIORITY_SHIFT)
+            | group as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscriber_priority_first() {
+        let audio = Scheduler::new(1, GroupOrder::Descending, PUBLISHER_GROUP_ORDER);
+        let video = Scheduler::new(2, GroupOrder::Descending, PUBLISHER_GROUP_ORDER);
+
+        // Subscriber priority wins over publisher priority and group id
+        assert!(audio.send_order(255, 0) > video.send_order(0, 1000));
+        assert!(audio.send_order(255, 0) > 0);
+    }
+
+    #[test]
+    fn publisher_priority_second() {
+        let scheduler = Scheduler::new(127, GroupOrder::Descending, PUBLISHER_GROUP_ORDER);
+
+        assert!(scheduler.send_order(1, 0) > scheduler.send_order(2, 1000));
+    }
+
+    #[test]
+    fn group_order_last() {
+        let descending = Scheduler::new(127, GroupOrder::Descending, PUBLISHER_GROUP_ORDER);
+        assert!(descending.send_order(10, 11) > descending.send_order(10, 10));
+
+        let ascending = Scheduler::new(127, GroupOrder::Ascending, PUBLISHER_GROUP_ORDER);
+        assert!(ascending.send_order(10, 10) > ascending.send_order(10, 11));
+    }
+
+    #[test]
+    fn resolve_publisher_order() {
+        let scheduler = Scheduler::new(127, GroupOrder::Publisher, GroupOrder::Ascending);
+        assert_eq!(scheduler.group_order(), GroupOrder::Ascending);
+
+        let scheduler = Scheduler::new(127, GroupOrder::Publisher, GroupOrder::Publisher);
+        assert_eq!(scheduler.group_order(), PUBLISHER_GROUP_ORDER);
+
+        let scheduler = Scheduler::new(127, GroupOrder::Descending, GroupOrder::Ascending);
+        assert_eq!(scheduler.group_order(), GroupOrder::Descending);
+    }
+}
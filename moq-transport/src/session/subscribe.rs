@@ -159,7 +159,7 @@ pub(super) struct SubscribeRecv {
 }
 
 impl SubscribeRecv {
-    pub fn ok(&mut self, alias: u64) -> Result<(), ServeError> {
+    pub fn ok(&mut self, alias: u64, group_order: GroupOrder) -> Result<(), ServeError> {
         let state = self.state.lock();
         if state.ok {
             return Err(ServeError::Duplicate);
@@ -170,6 +170,12 @@ impl SubscribeRecv {
             state.track_alias = Some(alias);
         }
 
+        // Pass the upstream group order on to anybody serving the track, ie: downstream of a relay.
+        // The mode isn't chosen until the first stream or datagram, which can't arrive before SUBSCRIBE_OK.
+        if let Some(TrackWriterMode::Track(track)) = &mut self.writer {
+            track.set_group_order(group_order)?;
+        }
+
         Ok(())
     }
 
@@ -192,59 +198,49 @@ impl SubscribeRecv {
         Ok(())
     }
 
+    /// Create a subgroup writer for a new subgroup stream.  The subgroup id is passed separately since
+    /// depending on the header type it is either in the header, implicitly zero, or the first object id.
     pub fn subgroup(
         &mut self,
         header: data::SubgroupHeader,
+        subgroup_id: u64,
     ) -> Result<serve::SubgroupWriter, ServeError> {
-        let writer = self.writer.take().ok_or(ServeError::Done)?;
-
-        let mut subgroups = match writer {
-            // TODO SLG - understand why both of these are needed, clock demo won't run if I comment out TrackWriteMode::Track
-            TrackWriterMode::Track(track) => track.subgroups()?,
-            TrackWriterMode::Subgroups(subgroups) => subgroups,
-            _ => return Err(ServeError::Mode),
-        };
+        let mut mixed = self.mixed()?;
 
-        let writer = subgroups.create(serve::Subgroup {
+        let writer = mixed.create(serve::Subgroup {
             group_id: header.group_id,
-            // When subgroup_id is not present in the header type, it implicitly means subgroup 0
-            subgroup_id: header.subgroup_id.unwrap_or(0),
+            subgroup_id,
             priority: header.publisher_priority,
-        })?;
+            end_of_group: header.header_type.is_end_of_group(),
+        });
 
-        self.writer = Some(subgroups.into());
+        self.writer = Some(mixed.into());
 
-        Ok(writer)
+        writer
     }
 
     pub fn datagram(&mut self, datagram: data::Datagram) -> Result<(), ServeError> {
-        let writer = self.writer.take().ok_or(ServeError::Done)?;
-
-        match writer {
-            TrackWriterMode::Track(track) => {
-                // convert Track -> Datagrams writer, write, then put Datagrams back
-                let mut datagrams = track.datagrams()?;
-                datagrams.write(serve::Datagram {
-                    group_id: datagram.group_id,
-                    object_id: datagram.object_id.unwrap_or(0),
-                    priority: datagram.publisher_priority,
-                    payload: datagram.payload.unwrap_or_default(),
-                    extension_headers: datagram.extension_headers.unwrap_or_default(),
-                })?;
-                self.writer = Some(TrackWriterMode::Datagrams(datagrams));
-                Ok(())
-            }
-            TrackWriterMode::Datagrams(mut datagrams) => {
-                datagrams.write(serve::Datagram {
-                    group_id: datagram.group_id,
-                    object_id: datagram.object_id.unwrap_or(0),
-                    priority: datagram.publisher_priority,
-                    payload: datagram.payload.unwrap_or_default(),
-                    extension_headers: datagram.extension_headers.unwrap_or_default(),
-                })?;
-                self.writer = Some(TrackWriterMode::Datagrams(datagrams));
-                Ok(())
-            }
+        let mut mixed = self.mixed()?;
+
+        let res = mixed.datagram(serve::Datagram {
+            group_id: datagram.group_id,
+            object_id: datagram.object_id.unwrap_or(0),
+            priority: datagram.publisher_priority,
+            payload: datagram.payload.unwrap_or_default(),
+            extension_headers: datagram.extension_headers.unwrap_or_default(),
+        });
+
+        self.writer = Some(mixed.into());
+
+        res
+    }
+
+    // A publisher may send any object of a track on a stream or as a datagram, so the track is
+    // received in mixed mode, which is created on the first stream or datagram.
+    fn mixed(&mut self) -> Result<serve::MixedWriter, ServeError> {
+        match self.writer.take().ok_or(ServeError::Done)? {
+            TrackWriterMode::Track(track) => track.mixed(),
+            TrackWriterMode::Mixed(mixed) => Ok(mixed),
             other => {
                 // preserve whatever unexpected mode was present, then report error
                 self.writer = Some(other);
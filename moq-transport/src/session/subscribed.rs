@@ -1,19 +1,24 @@
 use std::ops;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 
-use crate::coding::{Encode, Location, ReasonPhrase};
+use crate::coding::{self, Encode, Location, ReasonPhrase};
 use crate::mlog;
 use crate::serve::{ServeError, TrackReaderMode};
 use crate::watch::State;
 use crate::{data, message, serve};
 
-use super::{Publisher, SessionError, SubscribeInfo, Writer};
+use super::{Publisher, Scheduler, SessionError, SubscribeInfo, Writer, PUBLISHER_GROUP_ORDER};
 
 // This file defines Publisher handling of inbound Subscriptions
 
+// Datagrams that fall back to a stream are allocated subgroup IDs counting up from here, since the
+// publisher of a mixed track allocates its own subgroup IDs counting up from zero.  It still fits in
+// a 4 byte varint, so the subgroup headers stay small.
+const FALLBACK_SUBGROUP_ID: u64 = 1 << 29;
+
 #[derive(Debug)]
 struct SubscribedState {
     largest_location: Option<Location>,
@@ -50,6 +55,10 @@ pub struct Subscribed {
     /// The tracknamespace and trackname for the subscription.
     pub info: SubscribeInfo,
 
+    /// Derives the send order of subgroup streams from the subscriber priority,
+    /// publisher priority and negotiated group order.
+    scheduler: Scheduler,
+
     state: State<SubscribedState>,
 
     /// Tracks if SubscribeOk has been sent yet or not. Used to send
@@ -57,21 +66,27 @@ pub struct Subscribed {
     ok: bool,
 
     /// Optional mlog writer for logging transport events
-    mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+    mlog: Option<mlog::MlogWriter>,
 }
 
 impl Subscribed {
     pub(super) fn new(
         publisher: Publisher,
         msg: message::Subscribe,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
     ) -> (Self, SubscribedRecv) {
         let (send, recv) = State::default().split();
         let info = SubscribeInfo::new_from_subscribe(&msg);
+        let scheduler = Scheduler::new(
+            info.subscriber_priority,
+            info.group_order,
+            PUBLISHER_GROUP_ORDER,
+        );
         let send = Self {
             publisher,
             state: send,
             info,
+            scheduler,
             ok: false,
             mlog,
         };
@@ -92,6 +107,14 @@ impl Subscribed {
     }
 
     async fn serve_inner(&mut self, track: serve::TrackReader) -> Result<(), SessionError> {
+        // Resolve the group order using the publisher's preference, ie: the upstream SUBSCRIBE_OK.
+        let publisher_order = track.group_order().await?;
+        self.scheduler = Scheduler::new(
+            self.info.subscriber_priority,
+            self.info.group_order,
+            publisher_order.unwrap_or(PUBLISHER_GROUP_ORDER),
+        );
+
         // Update largest location before sending SubscribeOk
         let largest_location = track.largest_location();
         self.state
@@ -107,7 +130,7 @@ impl Subscribed {
                 id: self.info.id,
                 track_alias: self.info.id, // use subscription id as track alias
                 expires: 0,                // TODO SLG
-                group_order: message::GroupOrder::Descending, // TODO: resolve correct value from publisher / subscriber prefs
+                group_order: self.scheduler.group_order(),
                 content_exists: largest_location.is_some(),
                 largest_location,
                 params: Default::default(),
@@ -119,9 +142,16 @@ impl Subscribed {
         // Serve based on track mode
         match track.mode().await? {
             // TODO cancel track/datagrams on closed
-            TrackReaderMode::Stream(_stream) => panic!("deprecated"),
             TrackReaderMode::Subgroups(subgroups) => self.serve_subgroups(subgroups).await,
             TrackReaderMode::Datagrams(datagrams) => self.serve_datagrams(datagrams).await,
+            TrackReaderMode::Mixed(mixed) => {
+                // Both deliveries share the same track alias, so serve them side by side.
+                tokio::try_join!(
+                    self.serve_subgroups(mixed.subgroups),
+                    self.serve_datagrams(mixed.datagrams),
+                )?;
+                Ok(())
+            }
         }
     }
 
@@ -189,7 +219,7 @@ impl Drop for Subscribed {
 
 impl Subscribed {
     async fn serve_subgroups(
-        &mut self,
+        &self,
         mut subgroups: serve::SubgroupsReader,
     ) -> Result<(), SessionError> {
         let mut tasks = FuturesUnordered::new();
@@ -198,26 +228,7 @@ impl Subscribed {
         loop {
             tokio::select! {
                 res = subgroups.next(), if done.is_none() => match res {
-                    Ok(Some(subgroup)) => {
-                        let header = data::SubgroupHeader {
-                            header_type: data::StreamHeaderType::SubgroupIdExt,  // SubGroupId = Yes, Extensions = Yes, ContainsEndOfGroup = No
-                            track_alias: self.info.id, // use subscription id as track_alias
-                            group_id: subgroup.group_id,
-                            subgroup_id: Some(subgroup.subgroup_id),
-                            publisher_priority: subgroup.priority,
-                        };
-
-                        let publisher = self.publisher.clone();
-                        let state = self.state.clone();
-                        let info = subgroup.info.clone();
-                        let mlog = self.mlog.clone();
-
-                        tasks.push(async move {
-                            if let Err(err) = Self::serve_subgroup(header, subgroup, publisher, state, mlog).await {
-                                log::warn!("failed to serve subgroup: {:?}, error: {}", info, err);
-                            }
-                        });
-                    },
+                    Ok(Some(subgroup)) => tasks.push(self.spawn_subgroup(subgroup)),
                     Ok(None) => done = Some(Ok(())),
                     Err(err) => done = Some(Err(err)),
                 },
@@ -228,27 +239,63 @@ impl Subscribed {
         }
     }
 
+    /// Returns a future that serves the subgroup on a new stream, logging any errors.
+    fn spawn_subgroup(
+        &self,
+        subgroup: serve::SubgroupReader,
+    ) -> impl std::future::Future<Output = ()> {
+        // SubGroupId = Yes, Extensions = Yes, ContainsEndOfGroup = when the subgroup ends the group
+        let header_type = match subgroup.end_of_group {
+            true => data::StreamHeaderType::SubgroupIdExt.with_end_of_group(),
+            false => data::StreamHeaderType::SubgroupIdExt,
+        };
+        let header = data::SubgroupHeader {
+            header_type,
+            track_alias: self.info.id, // use subscription id as track_alias
+            group_id: subgroup.group_id,
+            subgroup_id: Some(subgroup.subgroup_id),
+            publisher_priority: subgroup.priority,
+        };
+
+        let send_order = self
+            .scheduler
+            .send_order(subgroup.priority, subgroup.group_id);
+        let publisher = self.publisher.clone();
+        let state = self.state.clone();
+        let info = subgroup.info.clone();
+        let mlog = self.mlog.clone();
+
+        async move {
+            if let Err(err) =
+                Self::serve_subgroup(header, subgroup, send_order, publisher, state, mlog).await
+            {
+                log::warn!("failed to serve subgroup: {:?}, error: {}", info, err);
+            }
+        }
+    }
+
     async fn serve_subgroup(
         header: data::SubgroupHeader,
         mut subgroup_reader: serve::SubgroupReader,
+        send_order: i32,
         mut publisher: Publisher,
         state: State<SubscribedState>,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(), SessionError> {
         log::debug!(
-            "[PUBLISHER] serve_subgroup: starting - group_id={}, subgroup_id={:?}, priority={}",
+            "[PUBLISHER] serve_subgroup: starting - group_id={}, subgroup_id={:?}, priority={}, send_order={}",
             subgroup_reader.group_id,
             subgroup_reader.subgroup_id,
-            subgroup_reader.priority
+            subgroup_reader.priority,
+            send_order
         );
 
-        let mut send_stream = publisher.open_uni().await?;
+        let (mut send_stream, stream_id) = publisher.open_uni().await?;
         log::trace!("[PUBLISHER] serve_subgroup: opened unidirectional stream");
 
-        // TODO figure out u32 vs u64 priority
-        send_stream.set_priority(subgroup_reader.priority as i32);
+        send_stream.set_priority(send_order);
 
-        let mut writer = Writer::new(send_stream);
+        let mut writer = Writer::new(send_stream, stream_id);
 
         log::debug!(
             "[PUBLISHER] serve_subgroup: sending header - track_alias={}, group_id={}, subgroup_id={:?}, priority={}, header_type={:?}",
@@ -263,18 +310,36 @@ impl Subscribed {
 
         // Log subgroup header created/sent
         if let Some(ref mlog) = mlog {
-            if let Ok(mut mlog_guard) = mlog.lock() {
-                let time = mlog_guard.elapsed_ms();
-                let stream_id = 0; // TODO: Placeholder, need actual QUIC stream ID
-                let event = mlog::subgroup_header_created(time, stream_id, &header);
-                let _ = mlog_guard.add_event(event);
-            }
+            let time = mlog.elapsed_ms();
+            let stream_id = writer.stream_id();
+            let _ = mlog.add_event(mlog::events::stream_type_set(
+                time,
+                stream_id,
+                mlog::events::StreamOwner::Local,
+                mlog::events::StreamType::Subgroup,
+            ));
+            let event = mlog::subgroup_header_created(time, stream_id, &header);
+            let _ = mlog.add_event(event);
         }
 
         let mut object_count = 0;
+        let mut prev_object_id: Option<u64> = None;
         while let Some(mut subgroup_object_reader) = subgroup_reader.next().await? {
+            // The first object's delta is its object id, otherwise it is the gap from the previous object minus one.
+            let object_id = subgroup_object_reader.object_id;
+            let object_id_delta = match prev_object_id {
+                Some(prev) => object_id.checked_sub(prev + 1).ok_or_else(|| {
+                    ServeError::internal_ctx(format!(
+                        "object_id={} not larger than previous object_id={}",
+                        object_id, prev
+                    ))
+                })?,
+                None => object_id,
+            };
+            prev_object_id = Some(object_id);
+
             let subgroup_object = data::SubgroupObjectExt {
-                object_id_delta: 0, // before delta logic, used to be subgroup_object_reader.object_id,
+                object_id_delta,
                 extension_headers: subgroup_object_reader.extension_headers.clone(), // Pass through extension headers
                 payload_length: subgroup_object_reader.size,
                 status: if subgroup_object_reader.size == 0 {
@@ -299,19 +364,17 @@ impl Subscribed {
 
             // Log subgroup object created/sent
             if let Some(ref mlog) = mlog {
-                if let Ok(mut mlog_guard) = mlog.lock() {
-                    let time = mlog_guard.elapsed_ms();
-                    let stream_id = 0; // TODO: Placeholder, need actual QUIC stream ID
-                    let event = mlog::subgroup_object_ext_created(
-                        time,
-                        stream_id,
-                        subgroup_reader.group_id,
-                        subgroup_reader.subgroup_id,
-                        subgroup_object_reader.object_id,
-                        &subgroup_object,
-                    );
-                    let _ = mlog_guard.add_event(event);
-                }
+                let time = mlog.elapsed_ms();
+                let stream_id = writer.stream_id();
+                let event = mlog::subgroup_object_ext_created(
+                    time,
+                    stream_id,
+                    subgroup_reader.group_id,
+                    subgroup_reader.subgroup_id,
+                    subgroup_object_reader.object_id,
+                    &subgroup_object,
+                );
+                let _ = mlog.add_event(event);
             }
 
             state
@@ -356,13 +419,27 @@ impl Subscribed {
     }
 
     async fn serve_datagrams(
-        &mut self,
+        &self,
         mut datagrams: serve::DatagramsReader,
     ) -> Result<(), SessionError> {
         log::debug!("[PUBLISHER] serve_datagrams: starting");
 
+        let mut publisher = self.publisher.clone();
+
+        // Datagrams that are too large are sent on a stream instead, served alongside the datagrams.
+        let mut fallbacks = FuturesUnordered::new();
+        let mut next_fallback_id = FALLBACK_SUBGROUP_ID;
+
         let mut datagram_count = 0;
-        while let Some(datagram) = datagrams.read().await? {
+        loop {
+            let datagram = tokio::select! {
+                res = datagrams.read() => match res? {
+                    Some(datagram) => datagram,
+                    None => break,
+                },
+                _ = fallbacks.next(), if !fallbacks.is_empty() => continue,
+            };
+
             // Determine datagram type based on extension headers presence
             let has_extension_headers = !datagram.extension_headers.is_empty();
             let datagram_type = if has_extension_headers {
@@ -383,7 +460,7 @@ impl Subscribed {
                     None
                 },
                 status: None,
-                payload: Some(datagram.payload),
+                payload: Some(datagram.payload.clone()),
             };
 
             let payload_len = encoded_datagram
@@ -406,20 +483,38 @@ impl Subscribed {
                 buffer.len()
             );
 
+            if buffer.len() > publisher.max_datagram_size().await {
+                log::debug!(
+                    "[PUBLISHER] serve_datagrams: datagram exceeds max datagram size, sending on a stream - group_id={}, object_id={}",
+                    datagram.group_id,
+                    datagram.object_id
+                );
+
+                // Each fallback gets its own subgroup, so the IDs never conflict even across groups.
+                let subgroup_id = next_fallback_id;
+                coding::VarInt::try_from(subgroup_id)?;
+                next_fallback_id += 1;
+
+                fallbacks.push(self.spawn_subgroup(Self::fallback_subgroup(
+                    datagrams.track.clone(),
+                    &datagram,
+                    subgroup_id,
+                )?));
+                continue;
+            }
+
             // Create mlog event for datagram created
             if let Some(ref mlog) = self.mlog {
-                if let Ok(mut mlog_guard) = mlog.lock() {
-                    let time = mlog_guard.elapsed_ms();
-                    let stream_id = 0; // TODO: Placeholder, need actual QUIC stream ID
-                    let _ = mlog_guard.add_event(mlog::object_datagram_created(
-                        time,
-                        stream_id,
-                        &encoded_datagram,
-                    ));
-                }
+                let time = mlog.elapsed_ms();
+                let stream_id = publisher.datagram_stream_id();
+                let _ = mlog.add_event(mlog::events::datagram_created(
+                    time,
+                    stream_id,
+                    &encoded_datagram,
+                ));
             }
 
-            self.publisher.send_datagram(buffer.into()).await?;
+            publisher.send_datagram(buffer.into()).await?;
 
             self.state
                 .lock_mut()
@@ -432,6 +527,9 @@ impl Subscribed {
             datagram_count += 1;
         }
 
+        // Finish sending any datagrams that fell back to streams.
+        while fallbacks.next().await.is_some() {}
+
         log::info!(
             "[PUBLISHER] serve_datagrams: completed ({} datagrams sent)",
             datagram_count
@@ -439,6 +537,32 @@ impl Subscribed {
 
         Ok(())
     }
+
+    /// Wrap a datagram in a subgroup containing only that object, using a subgroup id allocated
+    /// from [FALLBACK_SUBGROUP_ID] so it can't conflict with any other subgroup in the group.
+    fn fallback_subgroup(
+        track: Arc<serve::Track>,
+        datagram: &serve::Datagram,
+        subgroup_id: u64,
+    ) -> Result<serve::SubgroupReader, ServeError> {
+        let (mut writer, reader) = serve::SubgroupInfo {
+            track,
+            group_id: datagram.group_id,
+            subgroup_id,
+            priority: datagram.priority,
+            end_of_group: false,
+        }
+        .produce();
+
+        let mut object = writer.create_at(
+            datagram.object_id,
+            datagram.payload.len(),
+            Some(datagram.extension_headers.clone()),
+        )?;
+        object.write(datagram.payload.clone())?;
+
+        Ok(reader)
+    }
 }
 
 pub(super) struct SubscribedRecv {
@@ -5,7 +5,7 @@ use std::{
 };
 
 use crate::{
-    coding::{Decode, TrackNamespace},
+    coding::{self, Decode, TrackNamespace},
     data,
     message::{self, FilterType, GroupOrder, Message},
     mlog,
@@ -14,7 +14,10 @@ use crate::{
 
 use crate::watch::Queue;
 
-use super::{Announced, AnnouncedRecv, Reader, Session, SessionError, Subscribe, SubscribeRecv};
+use super::{
+    Announced, AnnouncedRecv, Reader, Session, SessionError, StreamIds, Subscribe, SubscribeRecv,
+    Transport,
+};
 
 // TODO remove Clone.
 #[derive(Clone)]
@@ -43,14 +46,18 @@ pub struct Subscriber {
     next_requestid: Arc<atomic::AtomicU64>,
 
     /// Optional mlog writer for logging transport events
-    mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+    mlog: Option<mlog::MlogWriter>,
+
+    /// Assigns QUIC stream IDs, for logging datagrams
+    stream_ids: Arc<StreamIds>,
 }
 
 impl Subscriber {
     pub(super) fn new(
         outgoing: Queue<Message>,
         next_requestid: Arc<atomic::AtomicU64>,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
+        stream_ids: Arc<StreamIds>,
     ) -> Self {
         Self {
             announced: Default::default(),
@@ -60,18 +67,20 @@ impl Subscriber {
             outgoing,
             next_requestid,
             mlog,
+            stream_ids,
         }
     }
 
     /// Create an inbound/server QUIC connection, by accepting a bi-directional QUIC stream for control messages.
     pub async fn accept(session: web_transport::Session) -> Result<(Session, Self), SessionError> {
-        let (session, _, subscriber) = Session::accept(session, None).await?;
+        let (session, _, subscriber) = Session::accept(session, Transport::default(), None).await?;
         Ok((session, subscriber.unwrap()))
     }
 
     /// Create an outbound/client QUIC connection, by opening a bi-directional QUIC stream for control messages.
     pub async fn connect(session: web_transport::Session) -> Result<(Session, Self), SessionError> {
-        let (session, _, subscriber) = Session::connect(session, None).await?;
+        let (session, _, subscriber) =
+            Session::connect(session, Transport::default(), None).await?;
         Ok((session, subscriber))
     }
 
@@ -205,7 +214,7 @@ impl Subscriber {
                 .insert(msg.track_alias, msg.id);
 
             // Notify the subscribe of the successful subscription
-            subscribe.ok(msg.track_alias)?;
+            subscribe.ok(msg.track_alias, msg.group_order)?;
         }
 
         Ok(())
@@ -271,9 +280,10 @@ impl Subscriber {
     pub(super) async fn recv_stream(
         mut self,
         stream: web_transport::RecvStream,
+        stream_id: u64,
     ) -> Result<(), SessionError> {
         log::trace!("[SUBSCRIBER] recv_stream: new stream received, decoding header");
-        let mut reader = Reader::new(stream);
+        let mut reader = Reader::new(stream, stream_id);
 
         // Decode the stream header
         let stream_header: data::StreamHeader = reader.decode().await?;
@@ -282,20 +292,31 @@ impl Subscriber {
             stream_header.header_type
         );
 
-        // Log subgroup header parsed/received
-        if let Some(ref subgroup_header) = stream_header.subgroup_header {
-            if let Some(ref mlog) = self.mlog {
-                if let Ok(mut mlog_guard) = mlog.lock() {
-                    let time = mlog_guard.elapsed_ms();
-                    let stream_id = 0; // TODO: Placeholder, need actual QUIC stream ID
-                    let event = mlog::subgroup_header_parsed(time, stream_id, subgroup_header);
-                    let _ = mlog_guard.add_event(event);
-                }
+        // Log the stream type and the subgroup or fetch header parsed/received
+        if let Some(ref mlog) = self.mlog {
+            let time = mlog.elapsed_ms();
+            let _ = mlog.add_event(mlog::events::stream_type_set(
+                time,
+                stream_id,
+                mlog::events::StreamOwner::Remote,
+                stream_header.header_type.into(),
+            ));
+
+            if let Some(ref subgroup_header) = stream_header.subgroup_header {
+                let event = mlog::subgroup_header_parsed(time, stream_id, subgroup_header);
+                let _ = mlog.add_event(event);
+            }
+            if let Some(ref fetch_header) = stream_header.fetch_header {
+                let event = mlog::events::fetch_header_parsed(time, stream_id, fetch_header);
+                let _ = mlog.add_event(event);
             }
         }
 
-        // No fetch support yet, so panic if fetch_header for now (via unwrap below)
-        let track_alias = stream_header.subgroup_header.as_ref().unwrap().track_alias;
+        // No fetch support yet
+        let track_alias = match stream_header.subgroup_header.as_ref() {
+            Some(subgroup_header) => subgroup_header.track_alias,
+            None => return Err(ServeError::not_implemented_ctx("fetch streams").into()),
+        };
         log::trace!(
             "[SUBSCRIBER] recv_stream: stream for subscription track_alias={}",
             track_alias
@@ -324,9 +345,9 @@ impl Subscriber {
     /// Continue handling the reception of a new stream from the QUIC session.
     async fn recv_stream_inner(
         &mut self,
-        reader: Reader,
+        mut reader: Reader,
         stream_header: data::StreamHeader,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(), SessionError> {
         let track_alias = stream_header.subgroup_header.as_ref().unwrap().track_alias;
         log::trace!(
@@ -334,6 +355,15 @@ impl Subscriber {
             track_alias
         );
 
+        // For the FirstObjectId header types the subgroup id is the object id of the first object,
+        // so the first object header must be read before the subgroup can be created.
+        let first_object = match stream_header.header_type.has_first_object_id() {
+            true => {
+                Some(Self::decode_subgroup_object(&mut reader, stream_header.header_type).await?)
+            }
+            false => None,
+        };
+
         // This is super silly, but I couldn't figure out a way to avoid the mutex guard across awaits.
         enum Writer {
             //Fetch(serve::FetchWriter),
@@ -355,7 +385,15 @@ impl Subscriber {
                 // Create the appropriate writer based on the stream header type
                 if stream_header.header_type.is_subgroup() {
                     log::trace!("[SUBSCRIBER] recv_stream_inner: creating subgroup writer");
-                    Writer::Subgroup(subscribe.subgroup(stream_header.subgroup_header.unwrap())?)
+                    let header = stream_header.subgroup_header.unwrap();
+
+                    // When subgroup_id is not present in the header type, it is either the first object id
+                    // or implicitly subgroup 0
+                    let subgroup_id = match &first_object {
+                        Some(object) => object.object_id_delta,
+                        None => header.subgroup_id.unwrap_or(0),
+                    };
+                    Writer::Subgroup(subscribe.subgroup(header, subgroup_id)?)
                 } else {
                     return Err(SessionError::Serve(ServeError::internal_ctx(format!(
                         "unsupported stream header type={}",
@@ -375,8 +413,14 @@ impl Subscriber {
             //Writer::Fetch(fetch) => Self::recv_fetch(fetch, reader).await?,
             Writer::Subgroup(subgroup_writer) => {
                 log::trace!("[SUBSCRIBER] recv_stream_inner: receiving subgroup data");
-                Self::recv_subgroup(stream_header.header_type, subgroup_writer, reader, mlog)
-                    .await?
+                Self::recv_subgroup(
+                    stream_header.header_type,
+                    subgroup_writer,
+                    reader,
+                    first_object,
+                    mlog,
+                )
+                .await?
             }
         };
 
@@ -387,146 +431,166 @@ impl Subscriber {
         Ok(())
     }
 
+    /// Decode the next object header on a subgroup stream.  Objects on stream types without extension
+    /// headers are returned with an empty set of extension headers.
+    async fn decode_subgroup_object(
+        reader: &mut Reader,
+        stream_header_type: data::StreamHeaderType,
+    ) -> Result<data::SubgroupObjectExt, SessionError> {
+        match stream_header_type.has_extension_headers() {
+            true => reader.decode::<data::SubgroupObjectExt>().await,
+            false => {
+                let object = reader.decode::<data::SubgroupObject>().await?;
+                Ok(data::SubgroupObjectExt {
+                    object_id_delta: object.object_id_delta,
+                    extension_headers: Default::default(),
+                    payload_length: object.payload_length,
+                    status: object.status,
+                })
+            }
+        }
+    }
+
     /// If new stream is a Subgroup stream, handle reception of subgroup objects and payloads.
     async fn recv_subgroup(
         stream_header_type: data::StreamHeaderType,
         mut subgroup_writer: serve::SubgroupWriter,
         mut reader: Reader,
-        mlog: Option<Arc<Mutex<mlog::MlogWriter>>>,
+        mut first_object: Option<data::SubgroupObjectExt>,
+        mlog: Option<mlog::MlogWriter>,
     ) -> Result<(), SessionError> {
         log::debug!(
-            "[SUBSCRIBER] recv_subgroup: starting - group_id={}, subgroup_id={}, priority={}",
+            "[SUBSCRIBER] recv_subgroup: starting - group_id={}, subgroup_id={}, priority={}, end_of_group={}",
             subgroup_writer.info.group_id,
             subgroup_writer.info.subgroup_id,
-            subgroup_writer.info.priority
+            subgroup_writer.info.priority,
+            subgroup_writer.info.end_of_group
         );
 
         let mut object_count = 0;
-        let mut current_object_id = 0u64;
-        while !reader.done().await? {
-            log::trace!(
-                "[SUBSCRIBER] recv_subgroup: reading object #{} (has_ext_headers={})",
-                object_count + 1,
-                stream_header_type.has_extension_headers()
-            );
+        let mut prev_object_id: Option<u64> = None;
+        loop {
+            // The first object header may have already been read to determine the subgroup id
+            let object = match first_object.take() {
+                Some(object) => object,
+                None => {
+                    if reader.done().await? {
+                        break;
+                    }
 
-            // Need to be able to decode the subgroup object conditionally based on the stream header type
-            // read the object payload length into remaining_bytes
-            let (mut remaining_bytes, object_id_delta, status, decoded_object) =
-                match stream_header_type.has_extension_headers() {
-                    true => {
-                        let object = reader.decode::<data::SubgroupObjectExt>().await?;
-                        log::debug!(
-                        "[SUBSCRIBER] recv_subgroup: object #{} with extension headers - object_id_delta={}, payload_length={}, status={:?}, extension_headers={:?}",
+                    log::trace!(
+                        "[SUBSCRIBER] recv_subgroup: reading object #{} (has_ext_headers={})",
                         object_count + 1,
-                        object.object_id_delta,
-                        object.payload_length,
-                        object.status,
-                        object.extension_headers
+                        stream_header_type.has_extension_headers()
                     );
+                    Self::decode_subgroup_object(&mut reader, stream_header_type).await?
+                }
+            };
 
-                        // Check for known draft-14 extension types
-
-                        // Check for Immutable Extensions (type 0xB = 11)
-                        if object.extension_headers.has(0xB) {
-                            log::info!(
-                                "[SUBSCRIBER] recv_subgroup: object #{} contains IMMUTABLE EXTENSIONS (type 0xB) - will be forwarded",
-                                object_count + 1
-                            );
-                            if let Some(immutable_ext) = object.extension_headers.get(0xB) {
-                                log::debug!(
-                                    "[SUBSCRIBER] recv_subgroup: immutable extension details: {:?}",
-                                    immutable_ext
-                                );
-                            }
-                        }
-
-                        // Check for Prior Group ID Gap (type 0x3C = 60)
-                        if object.extension_headers.has(0x3C) {
-                            log::info!(
-                                "[SUBSCRIBER] recv_subgroup: object #{} contains PRIOR GROUP ID GAP (type 0x3C)",
-                                object_count + 1
-                            );
-                            if let Some(gap_ext) = object.extension_headers.get(0x3C) {
-                                log::debug!(
-                                    "[SUBSCRIBER] recv_subgroup: prior group id gap details: {:?}",
-                                    gap_ext
-                                );
-                            }
-                        }
-
-                        let obj_copy = object.clone();
-                        (
-                            object.payload_length,
-                            object.object_id_delta,
-                            object.status,
-                            Some(obj_copy),
-                        )
-                    }
-                    false => {
-                        let object = reader.decode::<data::SubgroupObject>().await?;
-                        log::debug!(
-                        "[SUBSCRIBER] recv_subgroup: object #{} - object_id_delta={}, payload_length={}, status={:?}",
-                        object_count + 1,
-                        object.object_id_delta,
-                        object.payload_length,
-                        object.status
+            log::debug!(
+                "[SUBSCRIBER] recv_subgroup: object #{} - object_id_delta={}, payload_length={}, status={:?}, extension_headers={:?}",
+                object_count + 1,
+                object.object_id_delta,
+                object.payload_length,
+                object.status,
+                object.extension_headers
+            );
+
+            // Check for known draft-14 extension types
+
+            // Check for Immutable Extensions (type 0xB = 11)
+            if object.extension_headers.has(0xB) {
+                log::info!(
+                    "[SUBSCRIBER] recv_subgroup: object #{} contains IMMUTABLE EXTENSIONS (type 0xB) - will be forwarded",
+                    object_count + 1
+                );
+                if let Some(immutable_ext) = object.extension_headers.get(0xB) {
+                    log::debug!(
+                        "[SUBSCRIBER] recv_subgroup: immutable extension details: {:?}",
+                        immutable_ext
                     );
-                        (
-                            object.payload_length,
-                            object.object_id_delta,
-                            object.status,
-                            None,
-                        )
-                    }
-                };
+                }
+            }
 
-            // Calculate absolute object_id from delta
-            current_object_id += object_id_delta;
+            // Check for Prior Group ID Gap (type 0x3C = 60)
+            if object.extension_headers.has(0x3C) {
+                log::info!(
+                    "[SUBSCRIBER] recv_subgroup: object #{} contains PRIOR GROUP ID GAP (type 0x3C)",
+                    object_count + 1
+                );
+                if let Some(gap_ext) = object.extension_headers.get(0x3C) {
+                    log::debug!(
+                        "[SUBSCRIBER] recv_subgroup: prior group id gap details: {:?}",
+                        gap_ext
+                    );
+                }
+            }
 
-            // Extract extension headers if present
-            let extension_headers = decoded_object
-                .as_ref()
-                .map(|obj| obj.extension_headers.clone());
+            // Calculate absolute object_id from delta.  The first object's delta is its object id,
+            // subsequent deltas are the number of object ids skipped since the previous object.
+            let object_id = match prev_object_id {
+                Some(prev) => prev
+                    .checked_add(object.object_id_delta)
+                    .and_then(|id| id.checked_add(1))
+                    .ok_or(coding::BoundsExceeded)?,
+                None => object.object_id_delta,
+            };
+            prev_object_id = Some(object_id);
 
             // Log subgroup object parsed/received
             if let Some(ref mlog) = mlog {
-                if let Ok(mut mlog_guard) = mlog.lock() {
-                    let time = mlog_guard.elapsed_ms();
-                    let stream_id = 0; // TODO: Placeholder, need actual QUIC stream ID
-                    let event = if let Some(obj_ext) = decoded_object {
-                        mlog::subgroup_object_ext_parsed(
-                            time,
-                            stream_id,
-                            subgroup_writer.info.group_id,
-                            subgroup_writer.info.subgroup_id,
-                            current_object_id,
-                            &obj_ext,
-                        )
-                    } else {
-                        // For non-extension objects, create a temporary SubgroupObject for logging
-                        let temp_obj = data::SubgroupObject {
-                            object_id_delta,
-                            payload_length: remaining_bytes,
-                            status,
-                        };
-                        mlog::subgroup_object_parsed(
-                            time,
-                            stream_id,
-                            subgroup_writer.info.group_id,
-                            subgroup_writer.info.subgroup_id,
-                            current_object_id,
-                            &temp_obj,
-                        )
+                let time = mlog.elapsed_ms();
+                let stream_id = reader.stream_id();
+                let event = if stream_header_type.has_extension_headers() {
+                    mlog::subgroup_object_ext_parsed(
+                        time,
+                        stream_id,
+                        subgroup_writer.info.group_id,
+                        subgroup_writer.info.subgroup_id,
+                        object_id,
+                        &object,
+                    )
+                } else {
+                    // For non-extension objects, create a temporary SubgroupObject for logging
+                    let temp_obj = data::SubgroupObject {
+                        object_id_delta: object.object_id_delta,
+                        payload_length: object.payload_length,
+                        status: object.status,
                     };
-                    let _ = mlog_guard.add_event(event);
-                }
+                    mlog::subgroup_object_parsed(
+                        time,
+                        stream_id,
+                        subgroup_writer.info.group_id,
+                        subgroup_writer.info.subgroup_id,
+                        object_id,
+                        &temp_obj,
+                    )
+                };
+                let _ = mlog.add_event(event);
             }
 
             // Pass extension headers through to the serve layer
-            // TODO SLG - object_id_delta and object status are still being ignored
+            let extension_headers = match stream_header_type.has_extension_headers() {
+                true => Some(object.extension_headers),
+                false => None,
+            };
 
-            let mut object_writer = subgroup_writer.create(remaining_bytes, extension_headers)?;
+            // Objects without a payload carry a status, which is passed through to the serve layer
+            if let Some(status) = object.status {
+                log::debug!(
+                    "[SUBSCRIBER] recv_subgroup: object #{} object_id={} has status={:?}",
+                    object_count + 1,
+                    object_id,
+                    status
+                );
+                subgroup_writer.status(object_id, status, extension_headers)?;
+                object_count += 1;
+                continue;
+            }
+
+            let mut remaining_bytes = object.payload_length;
+            let mut object_writer =
+                subgroup_writer.create_at(object_id, remaining_bytes, extension_headers)?;
             log::trace!(
                 "[SUBSCRIBER] recv_subgroup: reading payload for object #{} ({} bytes)",
                 object_count + 1,
@@ -582,12 +646,9 @@ impl Subscriber {
         let datagram = data::Datagram::decode(&mut cursor)?;
 
         if let Some(ref mlog) = self.mlog {
-            if let Ok(mut mlog_guard) = mlog.lock() {
-                let time = mlog_guard.elapsed_ms();
-                let stream_id = 0; // TODO: Placeholder, need actual QUIC stream ID
-                let _ =
-                    mlog_guard.add_event(mlog::object_datagram_parsed(time, stream_id, &datagram));
-            }
+            let time = mlog.elapsed_ms();
+            let stream_id = self.stream_ids.session();
+            let _ = mlog.add_event(mlog::events::datagram_parsed(time, stream_id, &datagram));
         }
 
         // Check for extension headers in the datagram
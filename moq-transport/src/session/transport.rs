@@ -0,0 +1,114 @@
+use std::sync::atomic;
+
+use super::SessionError;
+
+/// The protocol carrying the session, which determines the QUIC stream IDs used by MoQ.
+///
+/// This is only used to label mlog events with the stream IDs that appear in qlog.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Transport {
+    /// WebTransport over HTTP/3.
+    ///
+    /// The client's CONNECT request uses the first bidirectional stream, and each side opens an HTTP/3
+    /// control stream before any MoQ streams.
+    #[default]
+    WebTransport,
+
+    /// MoQ directly over QUIC, using the MoQ ALPN.
+    Quic,
+}
+
+impl Transport {
+    // The number of streams of each type opened by the transport before any MoQ streams.
+    fn reserved(&self) -> u64 {
+        match self {
+            Self::WebTransport => 1,
+            Self::Quic => 0,
+        }
+    }
+}
+
+/// Assigns QUIC stream IDs to the streams used by a session.
+///
+/// web_transport doesn't expose the underlying stream IDs, so instead they're derived from the order
+/// that streams are opened and accepted, as QUIC allocates them sequentially (RFC 9000 Section 2.1).
+/// Remote streams are assumed to be accepted in the order they were opened; WebTransport reads a
+/// header before accepting a stream, so IDs may be swapped if headers arrive out of order.
+pub(crate) struct StreamIds {
+    transport: Transport,
+    server: bool,
+
+    // The index of the next local unidirectional stream, locked while opening so the order matches.
+    local: tokio::sync::Mutex<u64>,
+
+    // The index of the next remote unidirectional stream.
+    remote: atomic::AtomicU64,
+}
+
+impl StreamIds {
+    pub fn new(transport: Transport, server: bool) -> Self {
+        Self {
+            transport,
+            server,
+            local: tokio::sync::Mutex::new(transport.reserved()),
+            remote: atomic::AtomicU64::new(transport.reserved()),
+        }
+    }
+
+    /// The stream used to establish the session, which datagrams are associated with.
+    ///
+    /// This is the CONNECT stream for WebTransport, or the control stream for QUIC.
+    pub fn session(&self) -> u64 {
+        0
+    }
+
+    /// The bidirectional control stream, which is always opened by the client.
+    pub fn control(&self) -> u64 {
+        stream_id(self.transport.reserved(), false, false)
+    }
+
+    /// Open a unidirectional stream, returning it along with its ID.
+    pub async fn open_uni(
+        &self,
+        session: &mut web_transport::Session,
+    ) -> Result<(web_transport::SendStream, u64), SessionError> {
+        let mut index = self.local.lock().await;
+        let stream = session.open_uni().await?;
+
+        let id = stream_id(*index, self.server, true);
+        *index += 1;
+
+        Ok((stream, id))
+    }
+
+    /// Return the ID of the next accepted unidirectional stream.
+    pub fn accept_uni(&self) -> u64 {
+        let index = self.remote.fetch_add(1, atomic::Ordering::Relaxed);
+        stream_id(index, !self.server, true)
+    }
+}
+
+// Compute the ID of the nth stream of a given type; the low two bits encode the initiator and direction.
+fn stream_id(index: u64, server: bool, uni: bool) -> u64 {
+    (index << 2) | ((uni as u64) << 1) | server as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_ids() {
+        let quic = StreamIds::new(Transport::Quic, true);
+        assert_eq!(quic.control(), 0);
+        assert_eq!(quic.accept_uni(), 2);
+        assert_eq!(quic.accept_uni(), 6);
+
+        // The HTTP/3 control streams are 2 and 3, so the first MoQ streams are 6 and 7.
+        let webtransport = StreamIds::new(Transport::WebTransport, false);
+        assert_eq!(webtransport.control(), 4);
+        assert_eq!(webtransport.accept_uni(), 7);
+        assert_eq!(webtransport.accept_uni(), 11);
+        assert_eq!(webtransport.session(), 0);
+    }
+}
@@ -8,16 +8,24 @@ use bytes::Buf;
 pub struct Writer {
     stream: web_transport::SendStream,
     buffer: bytes::BytesMut,
+
+    // The QUIC stream ID, for logging.
+    stream_id: u64,
 }
 
 impl Writer {
-    pub fn new(stream: web_transport::SendStream) -> Self {
+    pub fn new(stream: web_transport::SendStream, stream_id: u64) -> Self {
         Self {
             stream,
             buffer: Default::default(),
+            stream_id,
         }
     }
 
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
     pub async fn encode<T: Encode>(&mut self, msg: &T) -> Result<(), SessionError> {
         self.buffer.clear();
         log::trace!(